@@ -1,11 +1,11 @@
 use crate::{state::LSPState, IsLSPRange, LSPStore, ProgressCallbackClient};
 use async_lsp::lsp_types as lsp;
-use immt_ontology::uris::{ArchiveId, ArchiveURI, ArchiveURITrait};
-use immt_stex::quickparse::stex::{structs::SymnameMod, AnnotIter, DiagnosticLevel, STeXAnnot, STeXDiagnostic, STeXParseDataI};
+use immt_ontology::uris::{ArchiveId, ArchiveURI, ArchiveURITrait, DocumentURI, ModuleURI, SymbolURI};
+use immt_stex::quickparse::stex::{structs::{MorphismKind, SymnameMod}, AnnotIter, DiagnosticLevel, STeXAnnot, STeXDiagnostic, STeXParseDataI};
 use smallvec::SmallVec;
 use futures::FutureExt;
 use crate::capabilities::STeXSemanticTokens;
-use immt_system::backend::{archives::LocalArchive, Backend, GlobalBackend};
+use immt_system::backend::{archives::{Archive, LocalArchive}, Backend, GlobalBackend};
 use immt_utils::{prelude::TreeChildIter, sourcerefs::{LSPLineCol, SourceRange}};
 
 trait AnnotExt:Sized {
@@ -15,6 +15,15 @@ trait AnnotExt:Sized {
     fn semantic_tokens(&self,cont:&mut impl FnMut(SourceRange<LSPLineCol>,u32));
     fn hover(&self) -> Option<lsp::Hover>;
     fn inlay_hint(&self) -> Option<lsp::InlayHint>;
+    fn referent(&self,pos:LSPLineCol) -> Option<Referent>;
+    fn reference_range(&self,referent:&Referent,include_declaration:bool) -> Option<SourceRange<LSPLineCol>>;
+}
+
+/// The symbol or module some annotation refers to
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum Referent {
+    Symbol(SymbolURI),
+    Module(ModuleURI)
 }
 
 fn uri_from_archive_relpath(id:&ArchiveId,relpath:&str) -> Option<lsp::Url> {
@@ -59,6 +68,17 @@ impl AnnotExt for STeXAnnot {
                     selection_range:main_name_range.into_range(),
                     children:None
                 },&[])),
+            Self::Vardef { name, main_name_range, full_range,.. } =>
+                Some((lsp::DocumentSymbol {
+                    name: name.to_string(),
+                    detail:None,
+                    kind:lsp::SymbolKind::VARIABLE,
+                    tags:None,
+                    deprecated:None,
+                    range:full_range.into_range(),
+                    selection_range:main_name_range.into_range(),
+                    children:None
+                },&[])),
             Self::ImportModule { module, full_range,.. } =>
                 Some((lsp::DocumentSymbol {
                     name: format!("import@{}",module.uri),
@@ -106,7 +126,73 @@ impl AnnotExt for STeXAnnot {
                     selection_range:range.into_range(),
                     children:None
                 },&[])),
-            Self::SemanticMacro { .. } | Self::SymName{ .. } => None
+            Self::Paragraph { kind, fors, full_range, name_range, children } =>
+                Some((lsp::DocumentSymbol {
+                    name: if fors.is_empty() { kind.to_string() } else {
+                        format!("{kind} for {}",fors.iter().map(|(s,_)| s.uri.name().to_string()).collect::<Vec<_>>().join(", "))
+                    },
+                    detail:if fors.is_empty() { None } else {
+                        Some(fors.iter().map(|(s,_)| s.uri.to_string()).collect::<Vec<_>>().join(", "))
+                    },
+                    kind:lsp::SymbolKind::STRUCT,
+                    tags:None,
+                    deprecated:None,
+                    range:full_range.into_range(),
+                    selection_range:name_range.into_range(),
+                    children:None
+                },&children)),
+            Self::MathStructure { uri, full_range, name_range, children, .. } =>
+                Some((lsp::DocumentSymbol {
+                    name: uri.uri.to_string(),
+                    detail:None,
+                    kind:lsp::SymbolKind::STRUCT,
+                    tags:None,
+                    deprecated:None,
+                    range:full_range.into_range(),
+                    selection_range:name_range.into_range(),
+                    children:None
+                },&children)),
+            Self::Extension { uri, targets, full_range, name_range, children, .. } =>
+                Some((lsp::DocumentSymbol {
+                    name: uri.uri.to_string(),
+                    detail:if targets.is_empty() { None } else {
+                        Some(format!("extends {}",targets.iter().map(|(s,_)| s.uri.to_string()).collect::<Vec<_>>().join(", ")))
+                    },
+                    kind:lsp::SymbolKind::STRUCT,
+                    tags:None,
+                    deprecated:None,
+                    range:full_range.into_range(),
+                    selection_range:name_range.into_range(),
+                    children:None
+                },&children)),
+            Self::Morphism { kind, uri, domain, full_range, name_range, children, .. } =>
+                Some((lsp::DocumentSymbol {
+                    name: uri.as_ref().map_or_else(|| domain.uri.to_string(),ToString::to_string),
+                    detail:Some(match kind {
+                        MorphismKind::Copy => format!("copy of {}",domain.uri),
+                        MorphismKind::Interpret => format!("interpretation of {}",domain.uri)
+                    }),
+                    kind:lsp::SymbolKind::INTERFACE,
+                    tags:None,
+                    deprecated:None,
+                    range:full_range.into_range(),
+                    selection_range:name_range.into_range(),
+                    children:None
+                },&children)),
+            Self::Realize { module, full_range,.. } =>
+                Some((lsp::DocumentSymbol {
+                    name: format!("realize@{}",module.uri),
+                    detail:Some(module.uri.to_string()),
+                    kind:lsp::SymbolKind::PACKAGE,
+                    tags:None,
+                    deprecated:None,
+                    range:full_range.into_range(),
+                    selection_range:full_range.into_range(),
+                    children:None
+                },&[])),
+            Self::SemanticMacro { .. } | Self::VariableMacro { .. } | Self::SymName{ .. } | Self::Symref { .. } |
+            Self::Definiendum { .. } | Self::Definame { .. } | Self::Definiens { .. } |
+            Self::UseStructure { .. } | Self::Assign { .. } | Self::RenameDecl { .. } => None
         }
     }
 
@@ -134,7 +220,21 @@ impl AnnotExt for STeXAnnot {
             Self::Module { .. } |
             Self::Symdecl { .. } |
             Self::SymName { .. } |
-            Self::Symdef{ .. } => ()
+            Self::Symref { .. } |
+            Self::Symdef{ .. } |
+            Self::Vardef { .. } |
+            Self::VariableMacro { .. } |
+            Self::MathStructure { .. } |
+            Self::Extension { .. } |
+            Self::UseStructure { .. } |
+            Self::Morphism { .. } |
+            Self::Realize { .. } |
+            Self::Assign { .. } |
+            Self::RenameDecl { .. } |
+            Self::Paragraph { .. } |
+            Self::Definiendum { .. } |
+            Self::Definame { .. } |
+            Self::Definiens { .. } => ()
         }
     }
 
@@ -142,7 +242,9 @@ impl AnnotExt for STeXAnnot {
         match self {
             Self::ImportModule { module,archive_range,path_range,.. } |
             Self::UseModule { module,archive_range,path_range,.. } |
-            Self::SetMetatheory { archive_range, path_range, module, .. } => {
+            Self::SetMetatheory { archive_range, path_range, module, .. } |
+            Self::Realize { archive_range, path_range, module, .. } |
+            Self::Morphism { archive_range, domain_range:path_range, domain:module, .. } => {
                 let range = archive_range.map_or(*path_range,|a|
                     SourceRange { start: a.start, end: path_range.end }
                 );
@@ -154,7 +256,14 @@ impl AnnotExt for STeXAnnot {
                 }))
             }
             Self::SemanticMacro{ uri,token_range:range,.. } |
-            Self::SymName{ uri,name_range:range,.. } => {
+            Self::SymName{ uri,name_range:range,.. } |
+            Self::Symref{ uri,name_range:range,.. } |
+            Self::Definiendum{ uri,name_range:range,.. } |
+            Self::Definame{ uri,name_range:range,.. } |
+            Self::Definiens{ uri:Some((uri,range)),.. } |
+            Self::UseStructure{ structure:uri,structure_range:range,.. } |
+            Self::Assign{ uri,symbol_range:range,.. } |
+            Self::RenameDecl{ uri,symbol_range:range,.. } => {
                 if !range.contains(pos) {return None};
                 let Some(p) = &uri.filepath else {return None};
                 let Ok(url) = lsp::Url::from_file_path(p) else {return None};
@@ -164,7 +273,20 @@ impl AnnotExt for STeXAnnot {
                     range:SourceRange::into_range(uri.range)
                 }))
             }
-            Self::Module{ .. } | Self::Symdecl { .. } | Self::Symdef { .. } | Self::Inputref{ .. } => None
+            Self::Paragraph { fors, .. } |
+            Self::Extension { targets:fors, .. } => {
+                let (uri,_) = fors.iter().find(|(_,r)| r.contains(pos))?;
+                let Some(p) = &uri.filepath else {return None};
+                let Ok(url) = lsp::Url::from_file_path(p) else {return None};
+                Some(lsp::GotoDefinitionResponse::Scalar(lsp::Location {
+                    uri: url,
+                    range:SourceRange::into_range(uri.range)
+                }))
+            }
+            // variables are resolved in LSPState::get_goto_definition, which knows the document
+            Self::Module{ .. } | Self::Symdecl { .. } | Self::Symdef { .. } | Self::Inputref{ .. } |
+            Self::MathStructure { .. } |
+            Self::Vardef { .. } | Self::VariableMacro { .. } | Self::Definiens { uri:None, .. } => None
         }
     }
     fn semantic_tokens(&self,cont:&mut impl FnMut(SourceRange<LSPLineCol>,u32)) {
@@ -184,10 +306,13 @@ impl AnnotExt for STeXAnnot {
             Self::SetMetatheory { token_range,.. } |
             Self::ImportModule { token_range, ..} |
             Self::UseModule { token_range, ..} |
+            Self::Realize { token_range, ..} |
             Self::Inputref{ token_range, .. } =>
                 cont(*token_range,STeXSemanticTokens::DECLARATION),
             Self::SemanticMacro{ token_range,..} =>
                 cont(*token_range,STeXSemanticTokens::SYMBOL),
+            Self::VariableMacro{ token_range,..} =>
+                cont(*token_range,STeXSemanticTokens::VARIABLE),
             Self::Symdecl { main_name_range, name_ranges, token_range, parsed_args, .. } => {
                 cont(*token_range, STeXSemanticTokens::DECLARATION);
                 cont(*main_name_range, STeXSemanticTokens::NAME);
@@ -224,9 +349,12 @@ impl AnnotExt for STeXAnnot {
                     }}
                 }
             }
-            Self::Symdef { main_name_range, name_ranges, token_range, parsed_args, notation_args, notation, .. } => {
+            Self::Symdef { main_name_range, name_ranges, token_range, parsed_args, notation_args, notation, .. } |
+            Self::Vardef { main_name_range, name_ranges, token_range, parsed_args, notation_args, notation, .. } => {
                 cont(*token_range, STeXSemanticTokens::DECLARATION);
-                cont(*main_name_range, STeXSemanticTokens::NAME);
+                cont(*main_name_range, if matches!(self,Self::Vardef { .. }) {
+                    STeXSemanticTokens::VARIABLE
+                } else { STeXSemanticTokens::NAME });
                 
                 let mut props = SmallVec::<(SourceRange<LSPLineCol>,SourceRange<LSPLineCol>,Option<u32>,Option<&Vec<Self>>),4>::new();
                 macro_rules! insert {
@@ -270,17 +398,88 @@ impl AnnotExt for STeXAnnot {
                     }}
                 }
             }
-            Self::SymName { token_range,name_range,..} => {
+            Self::SymName { token_range,name_range,..} |
+            Self::Symref { token_range,name_range,..} |
+            Self::Definiendum { token_range,name_range,..} |
+            Self::Definame { token_range,name_range,..} => {
                 cont(*token_range,STeXSemanticTokens::REF_MACRO);
                 cont(*name_range,STeXSemanticTokens::SYMBOL);
             }
+            Self::Definiens { uri, token_range, children, .. } => {
+                cont(*token_range,STeXSemanticTokens::REF_MACRO);
+                if let Some((_,range)) = uri {
+                    cont(*range,STeXSemanticTokens::SYMBOL);
+                }
+                for c in children {
+                    c.semantic_tokens(cont);
+                }
+            }
+            Self::MathStructure { name_range, full_range, mathstructure_range:env_range, children, .. } |
+            Self::Extension { name_range, full_range, extstructure_range:env_range, children, .. } |
+            Self::Morphism { name_range, full_range, morphism_range:env_range, children, .. } => {
+                cont(*env_range,STeXSemanticTokens::DECLARATION);
+                cont(*name_range,STeXSemanticTokens::NAME);
+                if let Self::Extension { targets, .. } = self {
+                    for (_,range) in targets {
+                        cont(*range,STeXSemanticTokens::SYMBOL);
+                    }
+                }
+                for c in children {
+                    c.semantic_tokens(cont);
+                }
+                if full_range.end.line > env_range.end.line {
+                    let mut end_range = *full_range;
+                    end_range.end.col -= 1;
+                    end_range.start.line = end_range.end.line;
+                    end_range.start.col = end_range.end.col.saturating_sub(env_range.end.col - env_range.start.col);
+                    cont(end_range,STeXSemanticTokens::DECLARATION);
+                }
+            }
+            Self::UseStructure { token_range, structure_range, .. } => {
+                cont(*token_range,STeXSemanticTokens::DECLARATION);
+                cont(*structure_range,STeXSemanticTokens::SYMBOL);
+            }
+            Self::Assign { token_range, symbol_range, definiens:(_,children), .. } => {
+                cont(*token_range,STeXSemanticTokens::DECLARATION);
+                cont(*symbol_range,STeXSemanticTokens::SYMBOL);
+                for c in children {
+                    c.semantic_tokens(cont);
+                }
+            }
+            Self::RenameDecl { token_range, symbol_range, name, macroname, .. } => {
+                cont(*token_range,STeXSemanticTokens::DECLARATION);
+                // the new name comes first: \renamedecl[name]{symbol}{macro}
+                if let Some((_,range)) = name {
+                    cont(*range,STeXSemanticTokens::NAME);
+                }
+                cont(*symbol_range,STeXSemanticTokens::SYMBOL);
+                cont(macroname.1,STeXSemanticTokens::NAME);
+            }
+            Self::Paragraph { fors, full_range, name_range, children, .. } => {
+                cont(*name_range,STeXSemanticTokens::DECLARATION);
+                for (_,range) in fors {
+                    cont(*range,STeXSemanticTokens::SYMBOL);
+                }
+                for c in children {
+                    c.semantic_tokens(cont);
+                }
+                // only if the environment is closed, i.e. ends with `\end{<name>}`
+                if full_range.end.line > name_range.end.line {
+                    let mut end_range = *full_range;
+                    end_range.end.col -= 1;
+                    end_range.start.line = end_range.end.line;
+                    end_range.start.col = end_range.end.col.saturating_sub(name_range.end.col - name_range.start.col);
+                    cont(end_range,STeXSemanticTokens::DECLARATION);
+                }
+            }
         }
     }
 
     fn hover(&self) -> Option<lsp::Hover> {
         match self {
             Self::SemanticMacro { uri, full_range:range,.. } |
-            Self::SymName {uri,name_range:range,.. } =>
+            Self::SymName {uri,name_range:range,.. } |
+            Self::Symref {uri,name_range:range,.. } =>
                 Some(lsp::Hover {
                     range: Some(SourceRange::into_range(*range)),
                     contents:lsp::HoverContents::Markup(lsp::MarkupContent {
@@ -288,12 +487,68 @@ impl AnnotExt for STeXAnnot {
                     value: format!("<b>{}</b>",uri.uri)
                     })
                 }),
+            Self::Definiendum { uri, full_range:range,.. } |
+            Self::Definame { uri, full_range:range,.. } =>
+                Some(lsp::Hover {
+                    range: Some(SourceRange::into_range(*range)),
+                    contents:lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: format!("defines <b>{}</b>",uri.uri)
+                    })
+                }),
+            Self::Definiens { uri:Some((uri,_)), full_range:range,.. } =>
+                Some(lsp::Hover {
+                    range: Some(SourceRange::into_range(*range)),
+                    contents:lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: format!("definiens of <b>{}</b>",uri.uri)
+                    })
+                }),
+            Self::Assign { uri, symbol_range:range,.. } =>
+                Some(lsp::Hover {
+                    range: Some(SourceRange::into_range(*range)),
+                    contents:lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: format!("assigns to <b>{}</b>",uri.uri)
+                    })
+                }),
+            Self::RenameDecl { uri, symbol_range:range,.. } =>
+                Some(lsp::Hover {
+                    range: Some(SourceRange::into_range(*range)),
+                    contents:lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: format!("renames <b>{}</b>",uri.uri)
+                    })
+                }),
+            Self::UseStructure { structure, structure_range:range,.. } =>
+                Some(lsp::Hover {
+                    range: Some(SourceRange::into_range(*range)),
+                    contents:lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: format!("structure <b>{}</b>",structure.uri)
+                    })
+                }),
+            Self::VariableMacro { var, full_range:range,.. } =>
+                Some(lsp::Hover {
+                    range: Some(SourceRange::into_range(*range)),
+                    contents:lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: {
+                        let kind = if var.is_seq { "sequence variable" } else { "variable" };
+                        var.tp.as_ref().map_or_else(
+                            || format!("{kind} <b>{}</b>",var.name),
+                            |tp| format!("{kind} <b>{}</b>\n\nType: `{}`",var.name,normalize_whitespace(tp))
+                        )
+                    }
+                    })
+                }),
             _ => None
         }
     }
     fn inlay_hint(&self) -> Option<lsp::InlayHint> {
         match self {
-            Self::SymName { uri, full_range, token_range, name_range, mod_ } => {
+            Self::SymName { uri, full_range, token_range, name_range, mod_ } |
+            Self::Definame { uri, full_range, token_range, name_range, mod_ } => {
                 let name = uri.uri.name().last_name();
                 let name = name.as_ref();
                 let name = match mod_ {
@@ -327,6 +582,95 @@ impl AnnotExt for STeXAnnot {
                     data:None
                 })
             }
+            Self::VariableMacro { var, full_range, .. } => {
+                let tp = var.tp.as_ref()?;
+                Some(lsp::InlayHint {
+                    position: SourceRange::into_range(*full_range).end,
+                    label:lsp::InlayHintLabel::String(format!(": {}",normalize_whitespace(tp))),
+                    kind: Some(lsp::InlayHintKind::TYPE),
+                    text_edits:None,
+                    tooltip:None,
+                    padding_left:None,
+                    padding_right:None,
+                    data:None
+                })
+            }
+            _ => None
+        }
+    }
+
+    fn referent(&self,pos:LSPLineCol) -> Option<Referent> {
+        match self {
+            Self::Module { uri, name_range, meta_theory, .. } => {
+                if name_range.contains(pos) { return Some(Referent::Module(uri.clone())) }
+                match meta_theory {
+                    Some((m,Some(range))) if range.contains(pos) => Some(Referent::Module(m.uri.clone())),
+                    _ => None
+                }
+            }
+            Self::ImportModule { module,.. } |
+            Self::UseModule { module,.. } |
+            Self::SetMetatheory { module,.. } |
+            Self::Realize { module,.. } => Some(Referent::Module(module.uri.clone())),
+            Self::Morphism { domain, domain_range,.. } if domain_range.contains(pos) =>
+                Some(Referent::Module(domain.uri.clone())),
+            Self::SemanticMacro { uri,.. } |
+            Self::SymName { uri,.. } |
+            Self::Symref { uri,.. } |
+            Self::Symdecl { uri,.. } |
+            Self::Symdef { uri,.. } |
+            Self::Definiendum { uri,.. } |
+            Self::Definame { uri,.. } |
+            Self::Definiens { uri:Some((uri,_)),.. } |
+            Self::MathStructure { uri,.. } |
+            Self::UseStructure { structure:uri,.. } |
+            Self::Assign { uri,.. } |
+            Self::RenameDecl { uri,.. } => Some(Referent::Symbol(uri.uri.clone())),
+            Self::Paragraph { fors,.. } => fors.iter().find(|(_,r)| r.contains(pos))
+                .map(|(uri,_)| Referent::Symbol(uri.uri.clone())),
+            Self::Extension { uri,targets,.. } => Some(Referent::Symbol(
+                targets.iter().find(|(_,r)| r.contains(pos)).map_or(uri,|(t,_)| t).uri.clone()
+            )),
+            Self::Inputref { .. } | Self::Vardef { .. } | Self::VariableMacro { .. } |
+            Self::Definiens { uri:None,.. } | Self::Morphism { .. } => None
+        }
+    }
+
+    fn reference_range(&self,referent:&Referent,include_declaration:bool) -> Option<SourceRange<LSPLineCol>> {
+        match (self,referent) {
+            (Self::Module { uri, name_range, meta_theory, .. },Referent::Module(m)) => {
+                if include_declaration && uri == m { return Some(*name_range) }
+                match meta_theory {
+                    Some((r,Some(range))) if r.uri == *m => Some(*range),
+                    _ => None
+                }
+            }
+            (Self::ImportModule { module,archive_range,path_range,.. } |
+             Self::UseModule { module,archive_range,path_range,.. } |
+             Self::SetMetatheory { module,archive_range,path_range,.. } |
+             Self::Realize { module,archive_range,path_range,.. } |
+             Self::Morphism { domain:module,archive_range,domain_range:path_range,.. },Referent::Module(m)) if module.uri == *m =>
+                Some(archive_range.map_or(*path_range,|a|
+                    SourceRange { start: a.start, end: path_range.end }
+                )),
+            (Self::SemanticMacro { uri,token_range:range,.. } |
+             Self::SymName { uri,name_range:range,.. } |
+             Self::Symref { uri,name_range:range,.. } |
+             Self::Definiendum { uri,name_range:range,.. } |
+             Self::Definame { uri,name_range:range,.. } |
+             Self::Definiens { uri:Some((uri,range)),.. } |
+             Self::UseStructure { structure:uri,structure_range:range,.. } |
+             Self::Assign { uri,symbol_range:range,.. } |
+             Self::RenameDecl { uri,symbol_range:range,.. },Referent::Symbol(s)) if uri.uri == *s => Some(*range),
+            (Self::MathStructure { uri,name_range,.. } |
+             Self::Extension { uri,name_range,.. },Referent::Symbol(s)) if include_declaration && uri.uri == *s =>
+                Some(*name_range),
+            (Self::Paragraph { fors,.. } |
+             Self::Extension { targets:fors,.. },Referent::Symbol(s)) =>
+                fors.iter().find(|(uri,_)| uri.uri == *s).map(|(_,range)| *range),
+            (Self::Symdecl { uri,main_name_range,.. } |
+             Self::Symdef { uri,main_name_range,.. },Referent::Symbol(s)) if include_declaration && uri.uri == *s =>
+                Some(*main_name_range),
             _ => None
         }
     }
@@ -335,7 +679,7 @@ impl AnnotExt for STeXAnnot {
 
 impl LSPState {
     #[must_use]
-    pub fn get_diagnostics(&self,uri:&lsp::Url,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=lsp::DocumentDiagnosticReportResult>> {
+    pub fn get_diagnostics(&self,uri:&lsp::Url,previous:Option<String>,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=lsp::DocumentDiagnosticReportResult>> {
         fn default() -> lsp::DocumentDiagnosticReportResult { lsp::DocumentDiagnosticReportResult::Report(
             lsp::DocumentDiagnosticReport::Full(
                 lsp::RelatedFullDocumentDiagnosticReport::default()
@@ -346,16 +690,26 @@ impl LSPState {
         Some(async move { 
             d.with_annots(slf,|data| {
                 let diags = &data.diagnostics;
+                let id = result_id(diags.iter());
                 let r = lsp::DocumentDiagnosticReportResult::Report(
-                lsp::DocumentDiagnosticReport::Full(
-                    lsp::RelatedFullDocumentDiagnosticReport {
-                        related_documents:None,
-                        full_document_diagnostic_report:lsp::FullDocumentDiagnosticReport {
-                            result_id:None,
-                            items:diags.iter().map(to_diagnostic).collect()
+                if previous.as_ref() == Some(&id) {
+                    lsp::DocumentDiagnosticReport::Unchanged(
+                        lsp::RelatedUnchangedDocumentDiagnosticReport {
+                            related_documents:None,
+                            unchanged_document_diagnostic_report:lsp::UnchangedDocumentDiagnosticReport { result_id:id }
                         }
-                    }
-                )
+                    )
+                } else {
+                    lsp::DocumentDiagnosticReport::Full(
+                        lsp::RelatedFullDocumentDiagnosticReport {
+                            related_documents:None,
+                            full_document_diagnostic_report:lsp::FullDocumentDiagnosticReport {
+                                result_id:Some(id),
+                                items:diags.iter().map(to_diagnostic).collect()
+                            }
+                        }
+                    )
+                }
                 );
                 tracing::trace!("diagnostics: {:?}",r);
                 if let Some(p) = progress { p.finish() }
@@ -439,11 +793,275 @@ impl LSPState {
             line:position.line,
             col:position.character
         };
+        let url = uri.clone();
         Some(d.with_annots(self.clone(),move |data| {
-            at_position(data,pos).and_then(|e| e.goto_definition(pos))
+            at_position(data,pos).and_then(|e| match e {
+                // variables are always declared in the same document
+                STeXAnnot::VariableMacro { var, token_range, .. } if token_range.contains(pos) =>
+                    Some(lsp::GotoDefinitionResponse::Scalar(lsp::Location {
+                        uri:url,
+                        range:var.range.into_range()
+                    })),
+                e => e.goto_definition(pos)
+            })
         }).map(|o| o.flatten()))
     }
 
+    #[must_use]
+    pub fn get_references(&self,uri:&lsp::Url,position:lsp::Position,include_declaration:bool,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::Location>>>> {
+        let d = self.get(uri)?;
+        let pos = LSPLineCol {
+            line:position.line,
+            col:position.character
+        };
+        let slf = self.clone();
+        Some(d.with_annots(self.clone(),move |data| {
+            at_position(data,pos).and_then(|e| e.referent(pos))
+        }).then(move |r| async move {
+            let referent = r.flatten()?;
+            let ret = tokio::task::spawn_blocking(move || slf.references(&referent,include_declaration)).await;
+            if let Some(p) = progress { p.finish() }
+            match ret {
+                Ok(r) => Some(r),
+                Err(e) => {
+                    tracing::error!("Error computing references: {}",e);
+                    None
+                }
+            }
+        }))
+    }
+
+    /// All occurrences in the document of the symbol or module at `position`; declarations
+    /// are highlighted as writes, all other occurrences as reads.
+    #[must_use]
+    pub fn get_highlights(&self,uri:&lsp::Url,position:lsp::Position,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::DocumentHighlight>>>> {
+        let d = self.get(uri)?;
+        let pos = LSPLineCol {
+            line:position.line,
+            col:position.character
+        };
+        Some(d.with_annots(self.clone(),move |data| {
+            let referent = at_position(data,pos).and_then(|e| e.referent(pos))?;
+            let iter : AnnotIter = data.annotations.iter().into();
+            let ret = <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter).filter_map(|e| {
+                let range = e.reference_range(&referent,true)?;
+                let write = match e {
+                    STeXAnnot::Symdecl { .. } | STeXAnnot::Symdef { .. } => true,
+                    STeXAnnot::MathStructure { name_range, .. } |
+                    STeXAnnot::Extension { name_range, .. } => *name_range == range,
+                    STeXAnnot::Module { uri, name_range, .. } =>
+                        *name_range == range && matches!(&referent,Referent::Module(m) if m == uri),
+                    _ => false
+                };
+                Some(lsp::DocumentHighlight {
+                    range:range.into_range(),
+                    kind:Some(if write { lsp::DocumentHighlightKind::WRITE } else { lsp::DocumentHighlightKind::READ })
+                })
+            }).collect();
+            if let Some(p) = progress { p.finish() }
+            Some(ret)
+        }).map(Option::flatten))
+    }
+
+    /// All locations in the currently loaded documents referencing `referent`, after loading
+    /// the documents that reference it according to the relational store.
+    fn references(&self,referent:&Referent,include_declaration:bool) -> Vec<lsp::Location> {
+        self.load_referencing(referent);
+        let mut ret = Vec::new();
+        for (uri,data) in self.all_annotations() {
+            let data = data.lock();
+            let iter : AnnotIter = data.annotations.iter().into();
+            for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+                if let Some(range) = e.reference_range(referent,include_declaration) {
+                    ret.push(lsp::Location { uri:uri.clone(), range:range.into_range() });
+                }
+            }
+        }
+        ret
+    }
+
+    fn load_referencing(&self,referent:&Referent) {
+        let backend = GlobalBackend::get();
+        let docs : Vec<DocumentURI> = match referent {
+            Referent::Symbol(s) => backend.triple_store().referencing_documents(s).collect(),
+            Referent::Module(m) => backend.triple_store().importing_documents(m).collect()
+        };
+        for d in docs {
+            let Some(path) = backend.with_archive(d.archive_id(), |a| a.and_then(|a| {
+                let rel_path = a.find_source(&d)?;
+                let Archive::Local(a) = a;
+                Some(rel_path.split('/').fold(a.source_dir(),|p,s| p.join(s)))
+            })) else { continue };
+            self.load::<false>(&path,&d,|_| ());
+        }
+    }
+
+    #[must_use]
+    pub fn prepare_rename(&self,uri:&lsp::Url,position:lsp::Position) -> Option<impl std::future::Future<Output=Option<lsp::PrepareRenameResponse>>> {
+        let d = self.get(uri)?;
+        let pos = LSPLineCol {
+            line:position.line,
+            col:position.character
+        };
+        Some(d.with_annots(self.clone(),move |data| {
+            let (uri,range) = match at_position(data,pos)? {
+                STeXAnnot::Symdecl { uri, main_name_range, name_ranges, .. } |
+                STeXAnnot::Symdef { uri, main_name_range, name_ranges, .. } =>
+                    (uri,name_ranges.map(|(_,r)| r).filter(|r| r.contains(pos)).unwrap_or(*main_name_range)),
+                STeXAnnot::SemanticMacro { uri, token_range:range, .. } |
+                STeXAnnot::SymName { uri, name_range:range, .. } |
+                STeXAnnot::Symref { uri, name_range:range, .. } |
+                STeXAnnot::Definiendum { uri, name_range:range, .. } |
+                STeXAnnot::Definame { uri, name_range:range, .. } |
+                STeXAnnot::Definiens { uri:Some((uri,range)), .. } |
+                STeXAnnot::Assign { uri, symbol_range:range, .. } |
+                STeXAnnot::RenameDecl { uri, symbol_range:range, .. } => (uri,*range),
+                STeXAnnot::Paragraph { fors, .. } => {
+                    let (uri,range) = fors.iter().find(|(_,r)| r.contains(pos))?;
+                    (uri,*range)
+                }
+                _ => return None
+            };
+            // symbols declared outside of local archives can not be renamed
+            if uri.filepath.is_none() { return None }
+            Some(lsp::PrepareRenameResponse::RangeWithPlaceholder {
+                range:range.into_range(),
+                placeholder:uri.uri.name().last_name().to_string()
+            })
+        }).map(|o| o.flatten()))
+    }
+
+    #[must_use]
+    pub fn get_rename(&self,uri:&lsp::Url,position:lsp::Position,new_name:String,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Result<Option<lsp::WorkspaceEdit>,String>>> {
+        let d = self.get(uri)?;
+        let pos = LSPLineCol {
+            line:position.line,
+            col:position.character
+        };
+        let slf = self.clone();
+        Some(d.with_annots(self.clone(),move |data| {
+            at_position(data,pos).and_then(|e| e.referent(pos))
+        }).then(move |r| async move {
+            let Some(Referent::Symbol(symbol)) = r.flatten() else { return Ok(None) };
+            let ret = tokio::task::spawn_blocking(move || slf.rename_symbol(&symbol,&new_name)).await
+                .map_err(|e| e.to_string())?;
+            if let Some(p) = progress { p.finish() }
+            ret.map(Some)
+        }))
+    }
+
+    /// Renames `symbol` (and its macro, if it coincides with the symbol's name) in its declaration
+    /// and all occurrences in the workspace. Fails if the symbol is referenced in documents of
+    /// archives that are not available locally, or if any of the documents can not be read.
+    fn rename_symbol(&self,symbol:&SymbolURI,new_name:&str) -> Result<lsp::WorkspaceEdit,String> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() || new_name.contains(['?','{','}','[',']','\\','%']) {
+            return Err(format!("Invalid symbol name: {new_name}"))
+        }
+        let backend = GlobalBackend::get();
+        let mut missing : Vec<ArchiveId> = Vec::new();
+        for d in backend.triple_store().referencing_documents(symbol) {
+            let id = d.archive_id();
+            if backend.with_local_archive(id, |a| a.is_none()) && !missing.contains(id) {
+                missing.push(id.clone());
+            }
+        }
+        if !missing.is_empty() {
+            let missing = missing.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+            return Err(format!("{symbol} is used in archives that are not available locally: {missing}"))
+        }
+        self.load_referencing(&Referent::Symbol(symbol.clone()));
+
+        /// What to replace a range with
+        enum Edit {
+            /// the new name
+            Name,
+            /// the new macro name, as a control sequence
+            Macro,
+            /// a name in `\symname`/`\symref` etc., which may be qualified by a module path
+            Reference
+        }
+        // `Some(true)` if the macro is renamed along with the symbol
+        let mut declared = None;
+        let mut documents = Vec::new();
+        for (url,data) in self.all_annotations() {
+            let mut edits : Vec<(SourceRange<LSPLineCol>,Edit)> = Vec::new();
+            let lock = data.lock();
+            let iter : AnnotIter = lock.annotations.iter().into();
+            for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+                match e {
+                    STeXAnnot::Symdecl { uri, macroname, main_name_range, name_ranges, .. } |
+                    STeXAnnot::Symdef { uri, macroname, main_name_range, name_ranges, .. } if uri.uri == *symbol => {
+                        // a macro that differs from the symbol's name is left alone
+                        let rename_macro = macroname.as_deref() == Some(symbol.name().last_name().as_ref());
+                        if rename_macro && (new_name.is_empty() || !new_name.chars().all(|c| c.is_ascii_alphabetic())) {
+                            return Err(format!("{new_name} is not a valid macro name"))
+                        }
+                        declared = Some(rename_macro);
+                        if let Some((_,range)) = name_ranges {
+                            edits.push((*range,Edit::Name));
+                        }
+                        if rename_macro || name_ranges.is_none() {
+                            edits.push((*main_name_range,Edit::Name));
+                        }
+                    }
+                    STeXAnnot::SemanticMacro { uri, token_range, .. } if uri.uri == *symbol =>
+                        edits.push((*token_range,Edit::Macro)),
+                    STeXAnnot::SymName { uri, name_range, .. } |
+                    STeXAnnot::Symref { uri, name_range, .. } |
+                    STeXAnnot::Definiendum { uri, name_range, .. } |
+                    STeXAnnot::Definame { uri, name_range, .. } |
+                    STeXAnnot::Definiens { uri:Some((uri,name_range)), .. } |
+                    STeXAnnot::Assign { uri, symbol_range:name_range, .. } |
+                    STeXAnnot::RenameDecl { uri, symbol_range:name_range, .. } if uri.uri == *symbol =>
+                        edits.push((*name_range,Edit::Reference)),
+                    STeXAnnot::Paragraph { fors, .. } => edits.extend(
+                        fors.iter().filter(|(uri,_)| uri.uri == *symbol).map(|(_,range)| (*range,Edit::Reference))
+                    ),
+                    _ => ()
+                }
+            }
+            drop(lock);
+            if !edits.is_empty() { documents.push((url,edits)); }
+        }
+        let Some(rename_macro) = declared else {
+            return Err(format!("The declaration of {symbol} was not found in the workspace"))
+        };
+
+        let mut changes = std::collections::HashMap::new();
+        for (url,edits) in documents {
+            let edits : Vec<_> = edits.into_iter().filter(|(_,e)| rename_macro || !matches!(e,Edit::Macro)).collect();
+            if edits.is_empty() { continue }
+            let text = if edits.iter().any(|(_,e)| matches!(e,Edit::Reference)) {
+                Some(self.text_of(&url).ok_or_else(|| format!("Could not read {url}"))?)
+            } else { None };
+            let edits = edits.into_iter().map(|(range,edit)| {
+                let new_text = match edit {
+                    Edit::Name => new_name.to_string(),
+                    Edit::Macro => format!("\\{new_name}"),
+                    Edit::Reference => {
+                        let old = text.as_deref().and_then(|t| text_in_range(t,range))
+                            .ok_or_else(|| format!("{url} has changed; please try again"))?;
+                        old.rsplit_once('?').map_or_else(
+                            || new_name.to_string(),
+                            |(path,_)| format!("{path}?{new_name}")
+                        )
+                    }
+                };
+                Ok(lsp::TextEdit { range:range.into_range(), new_text })
+            }).collect::<Result<Vec<_>,String>>()?;
+            changes.insert(url,edits);
+        }
+        Ok(lsp::WorkspaceEdit { changes:Some(changes), ..Default::default() })
+    }
+
+    pub(crate) fn text_of(&self,uri:&lsp::Url) -> Option<String> {
+        if let Some(d) = self.get(uri) {
+            return Some(d.with_text(ToString::to_string))
+        }
+        std::fs::read_to_string(uri.to_file_path().ok()?).ok()
+    }
+
     #[must_use]
     pub fn get_inlay_hints(&self,uri:&lsp::Url,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::InlayHint>>>> {
         let d = self.get(uri)?;
@@ -513,7 +1131,41 @@ fn at_position(data:&STeXParseDataI,position:LSPLineCol) -> Option<&STeXAnnot> {
     ret
 }
 
+/// Collapses the whitespace (including line breaks) in LaTeX source to single spaces
+fn normalize_whitespace(s:&str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The part of `text` in the given range
+pub(crate) fn text_in_range(text:&str,range:SourceRange<LSPLineCol>) -> Option<&str> {
+    let offset = |pos:LSPLineCol| {
+        let mut off = 0;
+        for (i,line) in text.split_inclusive('\n').enumerate() {
+            if i == pos.line as usize {
+                return Some(off + line.chars().take(pos.col as usize).map(char::len_utf8).sum::<usize>())
+            }
+            off += line.len();
+        }
+        None
+    };
+    text.get(offset(range.start)?..offset(range.end)?)
+}
+
 #[must_use]
+/// Identifies a set of diagnostics in `resultId`s of diagnostic reports, so that clients
+/// can be told that the diagnostics of a document are unchanged since the last pull
+pub fn result_id<'a>(diags:impl Iterator<Item=&'a STeXDiagnostic>) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::hash::DefaultHasher::new();
+    for d in diags {
+        d.message.hash(&mut hasher);
+        (d.range.start.line,d.range.start.col,d.range.end.line,d.range.end.col).hash(&mut hasher);
+        (d.level as u8).hash(&mut hasher);
+        d.code.as_ref().map(|c| (c.code(),c.name())).hash(&mut hasher);
+    }
+    format!("{:016x}",hasher.finish())
+}
+
 pub fn to_diagnostic(diag:&STeXDiagnostic) -> lsp::Diagnostic {
     lsp::Diagnostic {
         range: diag.range.into_range(),
@@ -523,12 +1175,12 @@ pub fn to_diagnostic(diag:&STeXDiagnostic) -> lsp::Diagnostic {
             DiagnosticLevel::Warning => lsp::DiagnosticSeverity::WARNING,
             DiagnosticLevel::Hint => lsp::DiagnosticSeverity::HINT
         }),
-        code:None,
+        code:diag.code.as_ref().map(|c| lsp::NumberOrString::String(c.code().to_string())),
         code_description:None,
         source:None,
         message:diag.message.clone(),
         related_information:None,
         tags:None,
-        data:None
+        data:diag.code.as_ref().map(|c| serde_json::Value::String(c.name().to_string()))
     }
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use async_lsp::lsp_types::{self as lsp, CompletionOptions};
 pub struct STeXSemanticTokens;
 impl STeXSemanticTokens {
   pub const NAME:u32 = 0;           // dark blue          light blue  !
-  pub const TEMP:u32 = 1;           // light blue         dark blue   !
+  pub const VARIABLE:u32 = 1;       // light blue         dark blue   !
   pub const KEYWORD:u32 = 2;        // violet             pink        !
   pub const SYMBOL:u32 = 3;         // brown              red         !
   pub const DECLARATION: u32 = 4;   // Dark Purple        dark brown  !
@@ -112,7 +112,7 @@ pub fn capabilities() -> lsp::ServerCapabilities { lsp::ServerCapabilities {
   )),
   completion_provider: Some(CompletionOptions {
     resolve_provider: Some(true),
-    trigger_characters:None,
+    trigger_characters:Some(vec!["\\".to_string(),"{".to_string(),"[".to_string()]),
     all_commit_characters:None,
     work_done_progress_options: lsp::WorkDoneProgressOptions { work_done_progress: Some(true) },
     completion_item:Some(lsp::CompletionOptionsCompletionItem {
@@ -120,8 +120,8 @@ pub fn capabilities() -> lsp::ServerCapabilities { lsp::ServerCapabilities {
     })
   }),
   signature_help_provider: Some(lsp::SignatureHelpOptions {
-    trigger_characters:None,
-    retrigger_characters:None,
+    trigger_characters:Some(vec!["{".to_string()]),
+    retrigger_characters:Some(vec!["}".to_string()]),
     work_done_progress_options: lsp::WorkDoneProgressOptions { work_done_progress: Some(true) }
   }),
   definition_provider:Some(lsp::OneOf::Right(lsp::DefinitionOptions {
@@ -210,7 +210,7 @@ pub fn capabilities() -> lsp::ServerCapabilities { lsp::ServerCapabilities {
         work_done_progress_options:lsp::WorkDoneProgressOptions { work_done_progress:Some(true) },
         identifier:Some("stex-diagnostic".to_string()),
         inter_file_dependencies:true,
-        workspace_diagnostics:false // <- this does not seem to work
+        workspace_diagnostics:true
       }
     }
   )),
@@ -234,7 +234,8 @@ pub fn capabilities() -> lsp::ServerCapabilities { lsp::ServerCapabilities {
   document_range_formatting_provider: None,
   document_on_type_formatting_provider: None,
   color_provider:None,
-  call_hierarchy_provider:None,
+  // the module import graph like the type hierarchy, but with the `\importmodule`s as calls
+  call_hierarchy_provider:Some(lsp::CallHierarchyServerCapability::Simple(true)),
   //inline_completion_provider:None,
   experimental:None
 }}
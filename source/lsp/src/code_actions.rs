@@ -0,0 +1,241 @@
+use async_lsp::lsp_types as lsp;
+use futures::FutureExt;
+use immt_ontology::uris::{ArchiveId, ArchiveURITrait, ModuleURI, PathURITrait};
+use immt_stex::quickparse::{latex::LaTeXToken, stex::{structs::{ModuleReference, ModuleRule, ModuleRules}, AnnotIter, DiagnosticCode, DiagnosticLevel, STeXAnnot, STeXDiagnostic, STeXParseDataI}};
+use immt_utils::{prelude::{HMap, HSet, TreeChildIter}, sourcerefs::{LSPLineCol, SourceRange}};
+
+use crate::{completion::scope, ranges::{tokenize, Token}, state::{DocOrData, LSPState}, IsLSPRange, ProgressCallbackClient};
+
+/// Something at the requested range that a quick fix can be offered for
+enum Problem {
+  /// The name in a `\symname`, `\symref` etc. does not resolve to a symbol in scope
+  UnknownSymbol(String,lsp::Diagnostic),
+  /// The module in an `\importmodule`, `\usemodule` etc. could not be found
+  UnresolvedModule { macroname:String, module:String, diagnostic:lsp::Diagnostic },
+  /// The semantic macro of a symbol that is not in scope
+  UnknownMacro(String,lsp::Diagnostic)
+}
+
+/// The (relevant) state of the document at the requested range
+struct Context {
+  /// The innermost module containing the range, and the line after its `\begin{smodule}{...}`
+  module:Option<(ModuleURI,u32)>,
+  own:Vec<ModuleRules<LSPLineCol>>,
+  refs:Vec<ModuleReference>
+}
+impl Context {
+  fn new(data:&STeXParseDataI,pos:LSPLineCol) -> Self {
+    let mut module = None;
+    let iter : AnnotIter = data.annotations.iter().into();
+    for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+      if let STeXAnnot::Module { uri, name_range, full_range, .. } = e {
+        if full_range.contains(pos) {
+          module = Some((uri.clone(),name_range.end.line + 1));
+        }
+      }
+    }
+    let (own,refs) = scope(data,pos);
+    Self { module, own, refs }
+  }
+}
+
+/// Adds [`DiagnosticCode::UnknownMacro`] diagnostics for control sequences in `text` that are
+/// not in scope, but are the semantic macros of symbols declared in other loaded documents.
+pub(crate) fn unknown_macros(uri:&lsp::Url,text:&str,docs:&HMap<lsp::Url,DocOrData>,data:&mut STeXParseDataI) {
+  fn control_sequences<'a>(tokens:&[Token<'a>],out:&mut Vec<(LSPLineCol,&'a str)>) {
+    for t in tokens { match t {
+      LaTeXToken::ControlSequence { start, name } => out.push((*start,name)),
+      LaTeXToken::Group { children, .. } | LaTeXToken::Math { children, .. } => control_sequences(children, out),
+      LaTeXToken::Environment(e) => control_sequences(&e.children, out),
+      LaTeXToken::MacroApplication(_) | LaTeXToken::Comment(_) | LaTeXToken::Text { .. } => ()
+    }}
+  }
+  let macros : HSet<std::sync::Arc<str>> = docs.iter().filter(|(u,_)| *u != uri).flat_map(|(_,d)|
+    d.annotations().lock().modules.iter().flat_map(|(_,rules)| rules.rules.iter().filter_map(|r|
+      if let ModuleRule::Symbol(s) = r { s.macroname.clone() } else { None }
+    )).collect::<Vec<_>>()
+  ).collect();
+  if macros.is_empty() { return }
+  let iter : AnnotIter = data.annotations.iter().into();
+  let in_scope : Vec<LSPLineCol> = <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter).filter_map(|e|
+    if let STeXAnnot::SemanticMacro { token_range, .. } = e { Some(token_range.start) } else { None }
+  ).collect();
+  let tokens = tokenize(text);
+  let mut found = Vec::new();
+  control_sequences(&tokens, &mut found);
+  for (start,name) in found {
+    if in_scope.contains(&start) || !macros.contains(name) { continue }
+    #[allow(clippy::cast_possible_truncation)]
+    let end = LSPLineCol { line:start.line, col:start.col + 1 + name.chars().count() as u32 };
+    data.diagnostics.insert(STeXDiagnostic {
+      level:DiagnosticLevel::Hint,
+      message:format!("\\{name} is not in scope"),
+      range:SourceRange { start, end },
+      code:Some(DiagnosticCode::UnknownMacro(name.into()))
+    });
+  }
+}
+
+impl LSPState {
+  #[must_use]
+  pub fn get_code_actions(&self,uri:&lsp::Url,range:lsp::Range,diagnostics:Vec<lsp::Diagnostic>,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<lsp::CodeActionResponse>>> {
+    let d = self.get(uri)?;
+    let pos = LSPLineCol {
+      line:range.start.line,
+      col:range.start.character
+    };
+    let archive = d.archive().map(|a| a.archive_id().clone());
+    let problems = d.with_text(|text| {
+      let mut problems = Vec::new();
+      for diagnostic in diagnostics {
+        let code = match (&diagnostic.code,&diagnostic.data) {
+          (Some(lsp::NumberOrString::String(code)),Some(serde_json::Value::String(name))) => DiagnosticCode::parse(code,name),
+          _ => None
+        };
+        match code {
+          Some(DiagnosticCode::UnknownSymbol(name)) => problems.push(Problem::UnknownSymbol(name.into(),diagnostic)),
+          Some(DiagnosticCode::ModuleNotFound(module)) => {
+            let start = SourceRange::from_range(diagnostic.range).start;
+            let Some((macroname,_)) = control_sequence_at(text,start) else { continue };
+            problems.push(Problem::UnresolvedModule { macroname, module:module.into(), diagnostic });
+          }
+          Some(DiagnosticCode::UnknownMacro(name)) => problems.push(Problem::UnknownMacro(name.into(),diagnostic)),
+          None => ()
+        }
+      }
+      problems
+    });
+    let uri = uri.clone();
+    let slf = self.clone();
+    Some(d.with_annots(self.clone(),move |data| (Context::new(data,pos),problems))
+      .then(move |r| async move {
+        let (context,problems) = r?;
+        let ret = tokio::task::spawn_blocking(move || slf.code_actions(&uri,pos,archive.as_ref(),context,problems)).await.ok();
+        if let Some(p) = progress { p.finish() }
+        ret
+      })
+    )
+  }
+
+  fn code_actions(&self,uri:&lsp::Url,pos:LSPLineCol,archive:Option<&ArchiveId>,context:Context,problems:Vec<Problem>) -> lsp::CodeActionResponse {
+    let Context { module, own, refs } = context;
+    let modules = self.all_modules();
+    let symbols : Vec<_> = modules.iter().flat_map(|(m,rules)| rules.rules.iter().filter_map(move |r|
+      if let ModuleRule::Symbol(s) = r { Some((m,s)) } else { None }
+    )).collect();
+    let in_scope = if problems.iter().any(|p| matches!(p,Problem::UnknownMacro(..))) {
+      self.symbols_in_scope(own,refs)
+    } else { Vec::new() };
+    // imports go directly after \begin{smodule}{...}, \usemodule before the current line otherwise
+    let import = |m:&ModuleURI| {
+      let (macroname,line) = module.as_ref().map_or(("usemodule",pos.line),|(_,l)| ("importmodule",*l));
+      let text = import_text(macroname,m,archive);
+      let at = LSPLineCol { line, col:0 };
+      (text.clone(),edit(uri,SourceRange { start:at, end:at },format!("{text}\n")))
+    };
+    let declare = |decl:String| module.as_ref().map(|(m,line)| {
+      let at = LSPLineCol { line:*line, col:0 };
+      (format!("Declare new symbol {decl} in module {}",m.name()),edit(uri,SourceRange { start:at, end:at },format!("{decl}\n")))
+    });
+
+    let mut ret = Vec::new();
+    for p in problems {
+      match p {
+        Problem::UnknownSymbol(name,diagnostic) => {
+          let name = name.rsplit_once('?').map_or(name.as_str(),|(_,n)| n);
+          let mut found : Vec<&ModuleURI> = Vec::new();
+          for (m,s) in &symbols {
+            if s.uri.uri.name().last_name().as_ref() == name && !found.contains(m) {
+              found.push(*m);
+            }
+          }
+          for m in found {
+            let (text,edit) = import(m);
+            ret.push(action(format!("Add {text} for symbol {name}"),Some(&diagnostic),edit));
+          }
+          if let Some((title,edit)) = declare(format!("\\symdecl*{{{name}}}")) {
+            ret.push(action(title,Some(&diagnostic),edit));
+          }
+        }
+        Problem::UnresolvedModule { macroname, module:path, diagnostic } => {
+          let name = path.rsplit_once('?').map_or(path.as_str(),|(_,n)| n);
+          for (m,_) in modules.iter().filter(|(m,_)| m.name().to_string() == name) {
+            // always qualify with the archive, since the module was not found without it
+            let text = import_text(&macroname,m,None);
+            let range = SourceRange::from_range(diagnostic.range);
+            ret.push(action(format!("Replace with {text}"),Some(&diagnostic),edit(uri,range,text)));
+          }
+        }
+        Problem::UnknownMacro(name,diagnostic) => {
+          if in_scope.iter().any(|s| s.macroname.as_deref() == Some(name.as_str())) { continue }
+          let mut found : Vec<&ModuleURI> = Vec::new();
+          for (m,s) in &symbols {
+            if s.macroname.as_deref() == Some(name.as_str()) && !found.contains(m)
+              && !module.as_ref().is_some_and(|(own,_)| own == *m) {
+              found.push(*m);
+            }
+          }
+          for m in &found {
+            let (text,edit) = import(m);
+            ret.push(action(format!("Add {text} for \\{name}"),Some(&diagnostic),edit));
+          }
+          if let Some(s) = in_scope.iter().find(|s| s.uri.uri.name().last_name().as_ref() == name) {
+            let text = format!("\\symref{{{name}}}{{{name}}}");
+            let range = SourceRange::from_range(diagnostic.range);
+            ret.push(action(format!("Replace with {text} ({})",s.uri.uri),Some(&diagnostic),edit(uri,range,text)));
+          }
+        }
+      }
+    }
+    ret
+  }
+
+  /// All modules declared in any loaded document
+  fn all_modules(&self) -> Vec<(ModuleURI,ModuleRules<LSPLineCol>)> {
+    self.all_annotations().into_iter().flat_map(|(_,data)| data.lock().modules.to_vec()).collect()
+  }
+}
+
+/// `\macroname[archive]{path?Module}`; the archive is omitted if it is `in_archive`
+fn import_text(macroname:&str,module:&ModuleURI,in_archive:Option<&ArchiveId>) -> String {
+  let path = module.path().map_or_else(
+    || module.name().to_string(),
+    |p| format!("{p}?{}",module.name())
+  );
+  if in_archive == Some(module.archive_id()) {
+    format!("\\{macroname}{{{path}}}")
+  } else {
+    format!("\\{macroname}[{}]{{{path}}}",module.archive_id())
+  }
+}
+
+/// The name and range of the control sequence at `pos`, if any
+#[allow(clippy::cast_possible_truncation)]
+fn control_sequence_at(text:&str,pos:LSPLineCol) -> Option<(String,SourceRange<LSPLineCol>)> {
+  let line : Vec<char> = text.lines().nth(pos.line as usize)?.chars().collect();
+  let mut col = (pos.col as usize).min(line.len());
+  if line.get(col) == Some(&'\\') { col += 1; }
+  let start = col - line[..col].iter().rev().take_while(|c| c.is_ascii_alphabetic()).count();
+  let end = col + line[col..].iter().take_while(|c| c.is_ascii_alphabetic()).count();
+  if start == end || start == 0 || line[start - 1] != '\\' { return None }
+  Some((line[start..end].iter().collect(),SourceRange {
+    start:LSPLineCol { line:pos.line, col:(start - 1) as u32 },
+    end:LSPLineCol { line:pos.line, col:end as u32 }
+  }))
+}
+
+fn edit(uri:&lsp::Url,range:SourceRange<LSPLineCol>,new_text:String) -> lsp::WorkspaceEdit {
+  let mut changes = std::collections::HashMap::new();
+  changes.insert(uri.clone(),vec![lsp::TextEdit { range:range.into_range(), new_text }]);
+  lsp::WorkspaceEdit { changes:Some(changes), ..Default::default() }
+}
+
+fn action(title:String,diagnostic:Option<&lsp::Diagnostic>,edit:lsp::WorkspaceEdit) -> lsp::CodeActionOrCommand {
+  lsp::CodeActionOrCommand::CodeAction(lsp::CodeAction {
+    title,
+    kind:Some(lsp::CodeActionKind::QUICKFIX),
+    diagnostics:diagnostic.map(|d| vec![d.clone()]),
+    edit:Some(edit),
+    ..Default::default()
+  })
+}
@@ -0,0 +1,285 @@
+use async_lsp::lsp_types as lsp;
+use futures::FutureExt;
+use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURITrait, ModuleURI, SymbolURI}};
+use immt_stex::quickparse::stex::{structs::{ModuleReference, ModuleRule, ModuleRules, SymbolRule}, AnnotIter, STeXAnnot, STeXParseDataI};
+use immt_system::backend::{archives::{source_files::{SourceDir, SourceEntry}, Archive}, Backend, GlobalBackend};
+use immt_utils::{prelude::TreeChildIter, sourcerefs::LSPLineCol};
+
+use crate::{state::LSPState, ProgressCallbackClient};
+
+/// Macros whose (optional) argument is an archive id and whose (mandatory) argument
+/// is a module path
+const MODULE_MACROS: [&str;4] = ["importmodule","usemodule","requiremodule","setmetatheory"];
+/// Macros whose argument is a symbol name
+const SYMNAME_MACROS: [&str;10] = ["symname","sn","Symname","Sn","symnames","sns","Symnames","Sns","symref","sr"];
+
+enum CompletionKind {
+  Macro,
+  SymbolName,
+  Module(Option<ArchiveId>),
+  Archive
+}
+
+/// What is being completed, determined by the text of the current line before the cursor
+struct CompletionContext {
+  kind:CompletionKind,
+  prefix:String,
+  range:lsp::Range
+}
+impl CompletionContext {
+  #[allow(clippy::cast_possible_truncation)]
+  fn new(line:&str,position:lsp::Position) -> Option<Self> {
+    let i = line.rfind('\\')?;
+    let rest = &line[i+1..];
+    let col = |s:&str| line[..line.len() - s.len()].chars().count() as u32;
+    let ret = |kind,s:&str| Some(Self {
+      kind,prefix:s.to_string(),
+      range:lsp::Range {
+        start:lsp::Position { line:position.line, character:col(s) },
+        end:position
+      }
+    });
+    let name_len = rest.find(|c:char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+    let (name,mut rest) = rest.split_at(name_len);
+    if rest.is_empty() { return ret(CompletionKind::Macro,name) }
+    let is_module = MODULE_MACROS.contains(&name);
+    let mut archive = None;
+    if let Some(r) = rest.strip_prefix('[') {
+      match r.find(']') {
+        None if is_module || name == "inputref" => return ret(CompletionKind::Archive,r),
+        None => return None,
+        Some(j) => {
+          archive = Some(&r[..j]);
+          rest = &r[j+1..];
+        }
+      }
+    }
+    let arg = rest.strip_prefix('{')?;
+    if arg.contains('}') { return None }
+    if is_module {
+      ret(CompletionKind::Module(archive.map(|a| ArchiveId::new(a.trim()))),arg)
+    } else if SYMNAME_MACROS.contains(&name) {
+      ret(CompletionKind::SymbolName,arg)
+    } else { None }
+  }
+}
+
+impl LSPState {
+  #[must_use]
+  pub fn get_completion(&self,uri:&lsp::Url,position:lsp::Position,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<lsp::CompletionResponse>>> {
+    let d = self.get(uri)?;
+    let offset = d.get_position(position);
+    let context = d.with_text(|text| {
+      let text = text.get(..offset)?;
+      let line = text.rfind(['\n','\r']).map_or(text,|i| &text[i+1..]);
+      CompletionContext::new(line,position)
+    })?;
+    let archive = d.archive().map(|a| a.archive_id().clone());
+    let pos = LSPLineCol {
+      line:position.line,
+      col:position.character
+    };
+    let slf = self.clone();
+    Some(d.with_annots(self.clone(),move |data| scope(data,pos)).then(move |scope| async move {
+      let CompletionContext{kind,prefix,range} = context;
+      let items = match kind {
+        CompletionKind::Archive => archive_items(&prefix,range),
+        CompletionKind::Module(a) => {
+          let a = a.or(archive)?;
+          tokio::task::spawn_blocking(move || slf.module_items(&a,&prefix,range)).await.ok()?
+        }
+        k @ (CompletionKind::Macro | CompletionKind::SymbolName) => {
+          let (own,refs) = scope?;
+          let symbols = tokio::task::spawn_blocking(move || slf.symbols_in_scope(own,refs)).await.ok()?;
+          if matches!(k,CompletionKind::Macro) {
+            macro_items(&symbols,&prefix,range)
+          } else {
+            symname_items(&symbols,&prefix,range)
+          }
+        }
+      };
+      if let Some(p) = progress { p.finish() }
+      Some(lsp::CompletionResponse::Array(items))
+    }))
+  }
+
+  /// Adds the rendered notation of the symbol of a semantic macro to its documentation
+  pub fn resolve_completion(mut item:lsp::CompletionItem) -> impl std::future::Future<Output=lsp::CompletionItem> {
+    async move {
+      let Some(uri) = item.data.as_ref().and_then(lsp::LSPAny::as_str).and_then(|s| s.parse::<SymbolURI>().ok()) else {
+        return item
+      };
+      let notation = tokio::task::spawn_blocking(move || {
+        let notations = GlobalBackend::get().get_notations(&uri)?;
+        let (_,n) = notations.iter().next()?;
+        Some((n.display_shtml(false,false,&uri).to_string(),uri))
+      }).await.ok().flatten();
+      if let Some((html,uri)) = notation {
+        item.documentation = Some(lsp::Documentation::MarkupContent(lsp::MarkupContent {
+          kind:lsp::MarkupKind::Markdown,
+          value:format!("<b>{uri}</b>\n\n{html}")
+        }));
+      }
+      item
+    }
+  }
+
+  /// Collects all symbols available from the given module rules and (transitively) the
+  /// given modules.
+  pub(crate) fn symbols_in_scope(&self,mut todo:Vec<ModuleRules<LSPLineCol>>,mut refs:Vec<ModuleReference>) -> Vec<SymbolRule<LSPLineCol>> {
+    let mut visited : Vec<ModuleURI> = Vec::new();
+    let mut ret = Vec::new();
+    loop {
+      if let Some(rules) = todo.pop() {
+        for r in rules.rules.iter() {
+          match r {
+            ModuleRule::Symbol(s) => ret.push(s.clone()),
+            ModuleRule::Import(m) => refs.push(m.clone())
+          }
+        }
+      } else if let Some(m) = refs.pop() {
+        if visited.contains(&m.uri) { continue }
+        if let Some(rules) = self.module_rules(&m) { todo.push(rules); }
+        visited.push(m.uri);
+      } else { break }
+    }
+    ret
+  }
+
+  fn module_rules(&self,module:&ModuleReference) -> Option<ModuleRules<LSPLineCol>> {
+    let path = module.full_path.as_ref()?;
+    let url = lsp::Url::from_file_path(path).ok()?;
+    let get = || self.documents.read().get(&url).map(|d| d.annotations().clone());
+    let data = get().or_else(|| {
+      self.load::<false>(path,&module.doc_uri()?,|_| ());
+      get()
+    })?;
+    let data = data.lock();
+    data.modules.iter().find(|(uri,_)| *uri == module.uri).map(|(_,rules)| rules.clone())
+  }
+
+  /// Module paths (`path?Module`) of all modules in the given archive
+  fn module_items(&self,archive:&ArchiveId,prefix:&str,range:lsp::Range) -> Vec<lsp::CompletionItem> {
+    let files = GlobalBackend::get().with_archive(archive, |a| {
+      let Some(Archive::Local(a)) = a else { return Vec::new() };
+      let source = a.source_dir();
+      let mut files = Vec::new();
+      a.with_sources(|d| for e in <_ as TreeChildIter<SourceDir>>::dfs(d.children.iter()) {
+        if let SourceEntry::File(f) = e {
+          let path = f.relative_path.split('/').fold(source.clone(),|p,s| p.join(s));
+          files.push((f.relative_path.clone(),path));
+        }
+      });
+      files
+    });
+    let mut ret = Vec::new();
+    for (rel_path,path) in files {
+      let Some(stem) = rel_path.strip_suffix(".tex") else { continue };
+      let stem = stem.rsplit_once('.')
+        .filter(|(_,l)| l.parse::<Language>().is_ok())
+        .map_or(stem,|(s,_)| s);
+      let (dir,file) = stem.rsplit_once('/').unwrap_or(("",stem));
+      let data = lsp::Url::from_file_path(&path).ok().and_then(|url| self.documents.read().get(&url).map(|d| d.annotations().clone()));
+      let modules : Vec<String> = data.map(|d| d.lock().modules.iter()
+        .map(|(uri,_)| uri.name().to_string()).collect()
+      ).unwrap_or_default();
+      for m in modules {
+        let label = if m == file { format!("{dir}?{m}") } else { format!("{stem}?{m}") };
+        if !label.starts_with(prefix) { continue }
+        ret.push(lsp::CompletionItem {
+          kind:Some(lsp::CompletionItemKind::MODULE),
+          detail:Some(rel_path.to_string()),
+          text_edit:Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit { range, new_text:label.clone() })),
+          label,
+          ..Default::default()
+        });
+      }
+    }
+    ret
+  }
+}
+
+/// The module rules of all modules in `data` containing `pos`, and the modules used
+/// (or set as meta theory) before `pos`.
+pub(crate) fn scope(data:&STeXParseDataI,pos:LSPLineCol) -> (Vec<ModuleRules<LSPLineCol>>,Vec<ModuleReference>) {
+  let mut own = Vec::new();
+  let mut refs = Vec::new();
+  let iter : AnnotIter = data.annotations.iter().into();
+  for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+    match e {
+      STeXAnnot::Module { uri, meta_theory, full_range, .. } if full_range.contains(pos) => {
+        if let Some((_,rules)) = data.modules.iter().find(|(u,_)| u == uri) {
+          own.push(rules.clone());
+        }
+        if let Some((m,_)) = meta_theory { refs.push(m.clone()); }
+      }
+      STeXAnnot::MathStructure { uri, full_range, .. } |
+      STeXAnnot::Extension { uri, full_range, .. } if full_range.contains(pos) => {
+        let uri = uri.uri.clone().into_module();
+        if let Some((_,rules)) = data.modules.iter().find(|(u,_)| *u == uri) {
+          own.push(rules.clone());
+        }
+      }
+      STeXAnnot::UseModule { module, full_range, .. } if full_range.end < pos => refs.push(module.clone()),
+      // the symbols of the domain are available within the morphism
+      STeXAnnot::Morphism { domain, full_range, .. } if full_range.contains(pos) => refs.push(domain.clone()),
+      STeXAnnot::UseStructure { structure, full_range, .. } if full_range.end < pos =>
+        refs.push(ModuleReference::of_structure(structure)),
+      _ => ()
+    }
+  }
+  (own,refs)
+}
+
+fn macro_items(symbols:&[SymbolRule<LSPLineCol>],prefix:&str,range:lsp::Range) -> Vec<lsp::CompletionItem> {
+  let mut ret : Vec<lsp::CompletionItem> = Vec::new();
+  for s in symbols {
+    let Some(name) = s.macroname.as_deref() else { continue };
+    if !name.starts_with(prefix) || ret.iter().any(|i| i.filter_text.as_deref() == Some(name)) { continue }
+    let mut snippet = name.to_string();
+    for i in 1..=s.argnum {
+      snippet.push_str(&format!("{{${i}}}"));
+    }
+    ret.push(lsp::CompletionItem {
+      label:format!("\\{name}"),
+      kind:Some(if s.argnum == 0 { lsp::CompletionItemKind::CONSTANT } else { lsp::CompletionItemKind::FUNCTION }),
+      detail:Some(s.uri.uri.to_string()),
+      filter_text:Some(name.to_string()),
+      insert_text_format:Some(lsp::InsertTextFormat::SNIPPET),
+      text_edit:Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit { range, new_text:snippet })),
+      data:Some(s.uri.uri.to_string().into()),
+      ..Default::default()
+    });
+  }
+  ret
+}
+
+fn symname_items(symbols:&[SymbolRule<LSPLineCol>],prefix:&str,range:lsp::Range) -> Vec<lsp::CompletionItem> {
+  let mut ret : Vec<lsp::CompletionItem> = Vec::new();
+  for s in symbols {
+    let name = s.uri.uri.name().last_name().to_string();
+    let uri = s.uri.uri.to_string();
+    if !name.starts_with(prefix) || ret.iter().any(|i| i.detail.as_deref() == Some(&uri)) { continue }
+    ret.push(lsp::CompletionItem {
+      kind:Some(lsp::CompletionItemKind::TEXT),
+      detail:Some(uri),
+      text_edit:Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit { range, new_text:name.clone() })),
+      label:name,
+      ..Default::default()
+    });
+  }
+  ret
+}
+
+fn archive_items(prefix:&str,range:lsp::Range) -> Vec<lsp::CompletionItem> {
+  GlobalBackend::get().all_archives().iter().filter_map(|a| {
+    let id = a.id().to_string();
+    if !id.starts_with(prefix) { return None }
+    Some(lsp::CompletionItem {
+      kind:Some(lsp::CompletionItemKind::FOLDER),
+      text_edit:Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit { range, new_text:id.clone() })),
+      label:id,
+      ..Default::default()
+    })
+  }).collect()
+}
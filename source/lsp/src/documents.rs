@@ -118,12 +118,13 @@ impl LSPDocument {
 
     let mut docs = state.documents.write();
     let mut store = LSPStore::<true>::new(&mut *docs);
-    let (data,t) = measure(|| immt_stex::quickparse::stex::quickparse(
+    let (mut data,t) = measure(|| immt_stex::quickparse::stex::quickparse(
       uri,&lock.text, path,
       &AnyBackend::Global(GlobalBackend::get()),
       &mut store
     ));
     drop(store);
+    crate::code_actions::unknown_macros(&self.data.lsp_uri,&lock.text,&docs,&mut data);
     drop(docs);
     tracing::info!("quickparse took {t}");
     data.replace(&self.annotations);
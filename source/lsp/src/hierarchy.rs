@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+
+use async_lsp::lsp_types as lsp;
+use futures::FutureExt;
+use immt_ontology::uris::{ArchiveURITrait, DocumentURI, ModuleURI};
+use immt_stex::quickparse::stex::{AnnotIter, STeXAnnot, STeXParseDataI};
+use immt_system::backend::{archives::Archive, GlobalBackend};
+use immt_utils::{prelude::TreeChildIter, sourcerefs::{LSPLineCol, SourceRange}};
+
+use crate::{state::LSPState, IsLSPRange, ProgressCallbackClient};
+
+/// A module declared in some loaded document
+struct ModuleDecl {
+  uri:ModuleURI,
+  url:lsp::Url,
+  full_range:SourceRange<LSPLineCol>,
+  name_range:SourceRange<LSPLineCol>,
+  /// The modules imported directly in this module, with the ranges of the respective `\importmodule`s
+  imports:Vec<(ModuleURI,SourceRange<LSPLineCol>)>
+}
+impl ModuleDecl {
+  fn item(&self) -> lsp::CallHierarchyItem {
+    lsp::CallHierarchyItem {
+      name:self.uri.name().to_string(),
+      kind:lsp::SymbolKind::MODULE,
+      tags:None,
+      detail:Some(self.uri.to_string()),
+      uri:self.url.clone(),
+      range:self.full_range.into_range(),
+      selection_range:self.name_range.into_range(),
+      data:Some(self.uri.to_string().into())
+    }
+  }
+}
+
+fn module_of(item:&lsp::CallHierarchyItem) -> Option<ModuleURI> {
+  item.data.as_ref().and_then(lsp::LSPAny::as_str).and_then(|s| s.parse().ok())
+}
+
+/// The source file of the document declaring the module `uri`, according to the relational store
+fn declaring_document(uri:&ModuleURI) -> Option<(DocumentURI,PathBuf)> {
+  let backend = GlobalBackend::get();
+  let doc = backend.triple_store().document_of(&!uri.clone())?;
+  let path = backend.with_archive(doc.archive_id(), |a| a.and_then(|a| {
+    let rel_path = a.find_source(&doc)?;
+    let Archive::Local(a) = a;
+    Some(rel_path.split('/').fold(a.source_dir(),|p,s| p.join(s)))
+  }))?;
+  Some((doc,path))
+}
+
+/// The item for the module `uri`; if it is not declared in a loaded document, it points
+/// to the start of the source file of the document declaring it.
+fn item_for(decls:&[ModuleDecl],uri:&ModuleURI) -> Option<lsp::CallHierarchyItem> {
+  if let Some(d) = decls.iter().find(|d| d.uri == *uri) {
+    return Some(d.item())
+  }
+  let (_,path) = declaring_document(uri)?;
+  Some(lsp::CallHierarchyItem {
+    name:uri.name().to_string(),
+    kind:lsp::SymbolKind::MODULE,
+    tags:None,
+    detail:Some(uri.to_string()),
+    uri:lsp::Url::from_file_path(path).ok()?,
+    range:lsp::Range::default(),
+    selection_range:lsp::Range::default(),
+    data:Some(uri.to_string().into())
+  })
+}
+
+/// The module imported at `pos`, or otherwise the innermost module containing `pos`
+fn module_at(data:&STeXParseDataI,pos:LSPLineCol) -> Option<ModuleURI> {
+  let mut ret = None;
+  let iter : AnnotIter = data.annotations.iter().into();
+  for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+    match e {
+      STeXAnnot::ImportModule { module, full_range, .. } |
+      STeXAnnot::UseModule { module, full_range, .. } |
+      STeXAnnot::SetMetatheory { module, full_range, .. } |
+      STeXAnnot::Realize { module, full_range, .. } if full_range.contains(pos) =>
+        return Some(module.uri.clone()),
+      STeXAnnot::Module { uri, full_range, .. } if full_range.contains(pos) =>
+        ret = Some(uri.clone()),
+      STeXAnnot::MathStructure { uri, full_range, .. } |
+      STeXAnnot::Extension { uri, full_range, .. } if full_range.contains(pos) =>
+        ret = Some(uri.uri.clone().into_module()),
+      _ => ()
+    }
+  }
+  ret
+}
+
+/// The module import graph: a module's supertypes (and outgoing calls) are the modules
+/// it imports, its subtypes (and incoming calls) the modules importing it. Structures are
+/// modules too, so their supertypes are the modules they import; an extension additionally
+/// has the structures it extends as supertypes, but only if it is declared in a loaded document
+/// (the relational store does not record extensions).
+///
+/// The call hierarchy shows the same graph, but unlike the type hierarchy it can point to the
+/// individual `\importmodule`s (the "calls") in the importing module.
+impl LSPState {
+  #[must_use]
+  pub fn prepare_module_hierarchy(&self,uri:&lsp::Url,position:lsp::Position,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::CallHierarchyItem>>>> {
+    let d = self.get(uri)?;
+    let pos = LSPLineCol {
+      line:position.line,
+      col:position.character
+    };
+    let slf = self.clone();
+    Some(d.with_annots(self.clone(),move |data| module_at(data,pos)).then(move |m| async move {
+      let m = m.flatten()?;
+      let ret = tokio::task::spawn_blocking(move ||
+        item_for(&slf.module_decls_for(std::slice::from_ref(&m)),&m)
+      ).await.ok().flatten();
+      if let Some(p) = progress { p.finish() }
+      ret.map(|i| vec![i])
+    }))
+  }
+
+  #[must_use]
+  pub fn get_supertypes(&self,item:&lsp::TypeHierarchyItem,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::TypeHierarchyItem>>>> {
+    self.related_modules(item,true,progress)
+  }
+
+  #[must_use]
+  pub fn get_subtypes(&self,item:&lsp::TypeHierarchyItem,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::TypeHierarchyItem>>>> {
+    self.related_modules(item,false,progress)
+  }
+
+  #[must_use]
+  pub fn get_incoming_calls(&self,item:&lsp::CallHierarchyItem,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::CallHierarchyIncomingCall>>>> {
+    let m = module_of(item)?;
+    let slf = self.clone();
+    Some(async move {
+      let ret = tokio::task::spawn_blocking(move || {
+        let importing = GlobalBackend::get().triple_store().importing_modules(&m).collect::<Vec<_>>();
+        slf.module_decls_for(&importing).iter().filter_map(|d| {
+        let from_ranges : Vec<_> = d.imports.iter().filter(|(i,_)| *i == m).map(|(_,r)| r.into_range()).collect();
+        if from_ranges.is_empty() { None } else {
+          Some(lsp::CallHierarchyIncomingCall { from:d.item(), from_ranges })
+        }
+        }).collect()
+      }).await.ok();
+      if let Some(p) = progress { p.finish() }
+      ret
+    })
+  }
+
+  #[must_use]
+  pub fn get_outgoing_calls(&self,item:&lsp::CallHierarchyItem,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::CallHierarchyOutgoingCall>>>> {
+    let m = module_of(item)?;
+    let url = item.uri.clone();
+    let slf = self.clone();
+    Some(async move {
+      let ret = tokio::task::spawn_blocking(move || {
+        let decls = slf.module_decls();
+        let Some(decl) = decls.iter().find(|d| d.uri == m && d.url == url) else { return Vec::new() };
+        let imports = decl.imports.iter().map(|(i,_)| i.clone()).collect::<Vec<_>>();
+        let decls = slf.module_decls_for(&imports);
+        let Some(decl) = decls.iter().find(|d| d.uri == m && d.url == url) else { return Vec::new() };
+        let mut ret : Vec<lsp::CallHierarchyOutgoingCall> = Vec::new();
+        for (i,range) in &decl.imports {
+          if let Some(call) = ret.iter_mut().find(|c| module_of(&c.to).as_ref() == Some(i)) {
+            call.from_ranges.push(range.into_range());
+          } else if let Some(to) = item_for(&decls,i) {
+            ret.push(lsp::CallHierarchyOutgoingCall { to, from_ranges:vec![range.into_range()] });
+          }
+        }
+        ret
+      }).await.ok();
+      if let Some(p) = progress { p.finish() }
+      ret
+    })
+  }
+
+  /// The modules imported by (`supertypes == true`) or importing the module of `item`;
+  /// combines the imports in the loaded documents with the import relation in the relational store.
+  fn related_modules(&self,item:&lsp::TypeHierarchyItem,supertypes:bool,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::TypeHierarchyItem>>>> {
+    let m = module_of(item)?;
+    let slf = self.clone();
+    Some(async move {
+      let ret = tokio::task::spawn_blocking(move || {
+        let decls = slf.module_decls();
+        let store = GlobalBackend::get().triple_store();
+        let mut related : Vec<ModuleURI> = if supertypes {
+          decls.iter().filter(|d| d.uri == m)
+            .flat_map(|d| d.imports.iter().map(|(i,_)| i.clone()))
+            .chain(store.imported_modules(&m))
+            .collect()
+        } else {
+          decls.iter().filter(|d| d.imports.iter().any(|(i,_)| *i == m))
+            .map(|d| d.uri.clone())
+            .chain(store.importing_modules(&m))
+            .collect()
+        };
+        related.sort_unstable_by(|a,b| a.to_string().cmp(&b.to_string()));
+        related.dedup();
+        let decls = slf.module_decls_for(&related);
+        related.iter().filter_map(|r| item_for(&decls,r)).collect()
+      }).await.ok();
+      if let Some(p) = progress { p.finish() }
+      ret
+    })
+  }
+
+  /// Like [`module_decls`](Self::module_decls), but first loads the documents declaring any of
+  /// the given `modules` that is not declared in a loaded document yet
+  fn module_decls_for(&self,modules:&[ModuleURI]) -> Vec<ModuleDecl> {
+    let decls = self.module_decls();
+    let mut loaded = false;
+    for m in modules.iter().filter(|m| !decls.iter().any(|d| d.uri == **m)) {
+      let Some((doc,path)) = declaring_document(m) else { continue };
+      self.load::<false>(&path,&doc,|_| ());
+      loaded = true;
+    }
+    if loaded { self.module_decls() } else { decls }
+  }
+
+  fn module_decls(&self) -> Vec<ModuleDecl> {
+    let mut ret = Vec::new();
+    for (url,data) in self.all_annotations() {
+      let lock = data.lock();
+      let iter : AnnotIter = lock.annotations.iter().into();
+      for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+        let (uri,full_range,name_range,imports) = match e {
+          STeXAnnot::Module { uri, full_range, name_range, children, .. } =>
+            (uri.clone(),full_range,name_range,imports_in(children)),
+          STeXAnnot::MathStructure { uri, full_range, name_range, children, .. } =>
+            (uri.uri.clone().into_module(),full_range,name_range,imports_in(children)),
+          // an extension "imports" the structures it extends
+          STeXAnnot::Extension { uri, targets, full_range, name_range, children, .. } =>
+            (uri.uri.clone().into_module(),full_range,name_range,
+              targets.iter().map(|(t,r)| (t.uri.clone().into_module(),*r))
+                .chain(imports_in(children)).collect()),
+          _ => continue
+        };
+        ret.push(ModuleDecl {
+          uri,
+          url:url.clone(),
+          full_range:*full_range,
+          name_range:*name_range,
+          imports
+        });
+      }
+    }
+    ret
+  }
+}
+
+fn imports_in(children:&[STeXAnnot]) -> Vec<(ModuleURI,SourceRange<LSPLineCol>)> {
+  children.iter().filter_map(|c| match c {
+    STeXAnnot::ImportModule { module, full_range, .. } |
+    STeXAnnot::Realize { module, full_range, .. } => Some((module.uri.clone(),*full_range)),
+    _ => None
+  }).collect()
+}
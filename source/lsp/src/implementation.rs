@@ -2,7 +2,7 @@
 
 use std::ops::ControlFlow;
 
-use crate::{annotations::to_diagnostic, LSPStore};
+use crate::{annotations::to_diagnostic, state::LSPState, LSPStore};
 
 use super::{IMMTLSPServer,ServerWrapper};
 use async_lsp::{lsp_types::{self as lsp}, LanguageClient, LanguageServer, ResponseError};
@@ -223,6 +223,63 @@ impl<T:IMMTLSPServer> LanguageServer for ServerWrapper<T> {
         })
     }
 
+    #[must_use]
+    fn code_action(&mut self, params: lsp::CodeActionParams) -> Res<Option<lsp::CodeActionResponse>> {
+        tracing::trace_span!("code_action").in_scope(move || {
+            tracing::trace!("uri: {},range: {:?}, context: {:?}, work_done_progress_params: {:?}, partial_results: {:?}",
+                params.text_document.uri,
+                params.range,
+                params.context,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_code_actions(&params.text_document.uri,params.range,params.context.diagnostics,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn folding_range(&mut self, params: lsp::FoldingRangeParams) -> Res<Option<Vec<lsp::FoldingRange>>> {
+        tracing::trace_span!("folding_range").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, partial_results: {:?}",
+                params.text_document.uri,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_folding_ranges(&params.text_document.uri,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn selection_range(&mut self, params: lsp::SelectionRangeParams) -> Res<Option<Vec<lsp::SelectionRange>>> {
+        tracing::trace_span!("selection_range").in_scope(move || {
+            tracing::trace!("uri: {},positions: {:?}, work_done_progress_params: {:?}, partial_results: {:?}",
+                params.text_document.uri,
+                params.positions,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_selection_ranges(&params.text_document.uri,params.positions,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
     #[must_use]
     // impl_request!(! document_diagnostic = DocumentDiagnosticRequest => (lsp::DocumentDiagnosticReportResult::Report(lsp::DocumentDiagnosticReport::Full(lsp::RelatedFullDocumentDiagnosticReport::default()))));
     fn document_diagnostic(&mut self, params: lsp::DocumentDiagnosticParams) -> Res<lsp::DocumentDiagnosticReportResult> {
@@ -242,32 +299,58 @@ impl<T:IMMTLSPServer> LanguageServer for ServerWrapper<T> {
             let p = params.work_done_progress_params.work_done_token.map(
                 |tk| self.get_progress(tk)
             );
-            self.inner.state().get_diagnostics(&params.text_document.uri,p)
+            self.inner.state().get_diagnostics(&params.text_document.uri,params.previous_result_id,p)
                 .map_or_else(|| Box::pin(std::future::ready(Ok(default()))) as _,
                 |f| Box::pin(f.map(Result::Ok)) as _
             )
         })
     }
 
-    //#[must_use]
-    impl_request!(! references = References => (None));
-    /*fn references(&mut self, params: lsp::ReferenceParams) -> Res<Option<Vec<lsp::Location>>> {
+    #[must_use]
+    fn document_highlight(&mut self, params: lsp::DocumentHighlightParams) -> Res<Option<Vec<lsp::DocumentHighlight>>> {
+        tracing::trace_span!("document_highlight").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}",
+                params.text_document_position_params.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position_params.position
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_highlights(
+                &params.text_document_position_params.text_document.uri,
+                params.text_document_position_params.position,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn references(&mut self, params: lsp::ReferenceParams) -> Res<Option<Vec<lsp::Location>>> {
         tracing::trace_span!("references").in_scope(move || {
-            tracing::trace!("work_done_progress_params: {:?}, partial_results: {:?}, position: {:?}, context: {:?}",
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}, context: {:?}",
+                params.text_document_position.text_document.uri,
                 params.work_done_progress_params,
-                params.partial_result_params,
-                params.text_document_position,
+                params.text_document_position.position,
                 params.context
             );
-            if let Some(p) = params.work_done_progress_params.work_done_token {
-                self.get_progress(p).finish();
-            }
-            if let Some(p) = params.partial_result_params.partial_result_token {
-                self.get_progress(p).finish();
-            }
-            Box::pin(std::future::ready(Ok(Some(Vec::new()))))
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_references(
+                &params.text_document_position.text_document.uri,
+                params.text_document_position.position,
+                params.context.include_declaration,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
         })
-    }*/
+    }
 
 
     #[must_use]
@@ -339,51 +422,27 @@ impl<T:IMMTLSPServer> LanguageServer for ServerWrapper<T> {
 
     impl_request!(! declaration = GotoDefinition => (None));
 
-    impl_request!(! workspace_diagnostic = WorkspaceDiagnosticRequest => (lsp::WorkspaceDiagnosticReportResult::Report(lsp::WorkspaceDiagnosticReport {items:Vec::new()})));
-/*
     #[must_use]
     fn workspace_diagnostic(&mut self, params: lsp::WorkspaceDiagnosticParams) -> Res<lsp::WorkspaceDiagnosticReportResult> {
-        tracing::info_span!("workspace_diagnostics").in_scope(move || {
-            tracing::info!("work_done_progress_params: {:?}, partial_results: {:?}, identifier: {:?}, previous_results_id: {:?}",
+        tracing::trace_span!("workspace_diagnostics").in_scope(move || {
+            tracing::trace!("work_done_progress_params: {:?}, partial_results: {:?}, identifier: {:?}, previous_results_id: {:?}",
                 params.work_done_progress_params,
                 params.partial_result_params,
                 params.identifier,
                 params.previous_result_ids
             );
-            if let Some(_token) = params.partial_result_params.partial_result_token {
-                if self.ws_diagnostics.load(Ordering::Relaxed) {
-                    self.ws_diagnostics.store(false, Ordering::Relaxed);
-                    return Box::pin(std::future::ready(Ok(
-                        lsp::WorkspaceDiagnosticReportResult::Partial(lsp::WorkspaceDiagnosticReportPartialResult {
-                            items:Vec::new()
-                        })
-                    )))
-                }
-                
-                self.ws_diagnostics.store(true, Ordering::Relaxed);
-                return Box::pin(std::future::ready(Ok(
-                    lsp::WorkspaceDiagnosticReportResult::Report(lsp::WorkspaceDiagnosticReport {
-                        items:Vec::new()
-                    })
-                )))
-            }
-    
-            /*
-            if let Some(p) = params.work_done_progress_params.work_done_token {
-                self.get_progress(p).finish_delay();
-            }
-            if let Some(p) = params.partial_result_params.partial_result_token {
-                self.get_progress(p).finish_delay();
-            }
-            */
-            Box::pin(std::future::ready(Ok(
-                lsp::WorkspaceDiagnosticReportResult::Report(lsp::WorkspaceDiagnosticReport {
-                    items:Vec::new()
-                })
-            )))
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            Box::pin(self.inner.state().get_workspace_diagnostics(
+                params.previous_result_ids,
+                self.inner.client().clone(),
+                params.partial_result_params.partial_result_token,
+                p
+            ).map(Result::Ok))
         })
     }
-*/
+
     #[must_use]
     //impl_request!(! inlay_hint = InlayHintRequest => (None));
     fn inlay_hint(&mut self, params: lsp::InlayHintParams) -> Res<Option<Vec<lsp::InlayHint>>> {
@@ -408,28 +467,166 @@ impl<T:IMMTLSPServer> LanguageServer for ServerWrapper<T> {
     impl_request!(inlay_hint_resolve = InlayHintResolveRequest);
 
 
-    impl_request!(! code_action = CodeActionRequest => (None));
-    impl_request!(! document_highlight = DocumentHighlightRequest => (None));
-    impl_request!(! folding_range = FoldingRangeRequest => (None));
     
     impl_request!(implementation = GotoImplementation);
     impl_request!(type_definition = GotoTypeDefinition);
     impl_request!(document_color = DocumentColor);
     impl_request!(color_presentation = ColorPresentationRequest);
-    impl_request!(selection_range = SelectionRangeRequest);
     impl_request!(moniker = MonikerRequest);
     impl_request!(inline_value = InlineValueRequest);
     impl_request!(on_type_formatting = OnTypeFormatting);
     impl_request!(range_formatting = RangeFormatting);
     impl_request!(formatting = Formatting);
-    impl_request!(prepare_rename = PrepareRenameRequest);
-    impl_request!(rename = Rename);
-    impl_request!(prepare_type_hierarchy = TypeHierarchyPrepare);
+
+    #[must_use]
+    fn prepare_rename(&mut self, params: lsp::TextDocumentPositionParams) -> Res<Option<lsp::PrepareRenameResponse>> {
+        tracing::trace_span!("prepare_rename").in_scope(move || {
+            tracing::trace!("uri: {}, position: {:?}",
+                params.text_document.uri,
+                params.position
+            );
+            self.inner.state().prepare_rename(
+                &params.text_document.uri,
+                params.position
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn rename(&mut self, params: lsp::RenameParams) -> Res<Option<lsp::WorkspaceEdit>> {
+        tracing::trace_span!("rename").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}, new_name: {}",
+                params.text_document_position.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position.position,
+                params.new_name
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_rename(
+                &params.text_document_position.text_document.uri,
+                params.text_document_position.position,
+                params.new_name,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map_err(|e| ResponseError::new(async_lsp::ErrorCode::REQUEST_FAILED,e))) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn prepare_type_hierarchy(&mut self, params: lsp::TypeHierarchyPrepareParams) -> Res<Option<Vec<lsp::TypeHierarchyItem>>> {
+        tracing::trace_span!("prepare_type_hierarchy").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}",
+                params.text_document_position_params.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position_params.position
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().prepare_module_hierarchy(
+                &params.text_document_position_params.text_document.uri,
+                params.text_document_position_params.position,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
     impl_request!(will_save_wait_until = WillSaveWaitUntil);
-    impl_request!(completion = Completion);
-    impl_request!(signature_help = SignatureHelpRequest);
-    impl_request!(linked_editing_range = LinkedEditingRange);
-    impl_request!(prepare_call_hierarchy = CallHierarchyPrepare);
+
+    #[must_use]
+    fn completion(&mut self, params: lsp::CompletionParams) -> Res<Option<lsp::CompletionResponse>> {
+        tracing::trace_span!("completion").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}, context: {:?}",
+                params.text_document_position.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position.position,
+                params.context
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_completion(
+                &params.text_document_position.text_document.uri,
+                params.text_document_position.position,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+    #[must_use]
+    fn signature_help(&mut self, params: lsp::SignatureHelpParams) -> Res<Option<lsp::SignatureHelp>> {
+        tracing::trace_span!("signature_help").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}",
+                params.text_document_position_params.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position_params.position
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_signature_help(
+                &params.text_document_position_params.text_document.uri,
+                params.text_document_position_params.position,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+    #[must_use]
+    fn linked_editing_range(&mut self, params: lsp::LinkedEditingRangeParams) -> Res<Option<lsp::LinkedEditingRanges>> {
+        tracing::trace_span!("linked_editing_range").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}",
+                params.text_document_position_params.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position_params.position
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_linked_editing_ranges(
+                &params.text_document_position_params.text_document.uri,
+                params.text_document_position_params.position,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+    #[must_use]
+    fn prepare_call_hierarchy(&mut self, params: lsp::CallHierarchyPrepareParams) -> Res<Option<Vec<lsp::CallHierarchyItem>>> {
+        tracing::trace_span!("prepare_call_hierarchy").in_scope(move || {
+            tracing::trace!("uri: {},work_done_progress_params: {:?}, position: {:?}",
+                params.text_document_position_params.text_document.uri,
+                params.work_done_progress_params,
+                params.text_document_position_params.position
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().prepare_module_hierarchy(
+                &params.text_document_position_params.text_document.uri,
+                params.text_document_position_params.position,
+                p
+            )
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
         // semanticTokens/
         #[must_use]
         // impl_request!(semantic_tokens_full = SemanticTokensFullRequest);
@@ -485,28 +682,119 @@ impl<T:IMMTLSPServer> LanguageServer for ServerWrapper<T> {
         }
 
     // callHierarchy/
-    impl_request!(incoming_calls = CallHierarchyIncomingCalls);
-    impl_request!(outgoing_calls = CallHierarchyOutgoingCalls);
+    #[must_use]
+    fn incoming_calls(&mut self, params: lsp::CallHierarchyIncomingCallsParams) -> Res<Option<Vec<lsp::CallHierarchyIncomingCall>>> {
+        tracing::trace_span!("incoming_calls").in_scope(move || {
+            tracing::trace!("item: {:?},work_done_progress_params: {:?}, partial_results: {:?}",
+                params.item,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_incoming_calls(&params.item,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn outgoing_calls(&mut self, params: lsp::CallHierarchyOutgoingCallsParams) -> Res<Option<Vec<lsp::CallHierarchyOutgoingCall>>> {
+        tracing::trace_span!("outgoing_calls").in_scope(move || {
+            tracing::trace!("item: {:?},work_done_progress_params: {:?}, partial_results: {:?}",
+                params.item,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_outgoing_calls(&params.item,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
 
     // workspace/
     impl_request!(will_create_files = WillCreateFiles);
     impl_request!(will_rename_files = WillRenameFiles);
     impl_request!(will_delete_files = WillDeleteFiles);
-    impl_request!(symbol = WorkspaceSymbolRequest);
+    #[must_use]
+    fn symbol(&mut self, params: lsp::WorkspaceSymbolParams) -> Res<Option<lsp::WorkspaceSymbolResponse>> {
+        tracing::trace_span!("workspace_symbol").in_scope(move || {
+            tracing::trace!("query: {},work_done_progress_params: {:?}, partial_results: {:?}",
+                params.query,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            Box::pin(self.inner.state().get_workspace_symbols(params.query,p).map(Result::Ok))
+        })
+    }
     impl_request!(execute_command = ExecuteCommand);
 
     // typeHierarchy/
-    impl_request!(supertypes = TypeHierarchySupertypes);
-    impl_request!(subtypes = TypeHierarchySubtypes);
+    #[must_use]
+    fn supertypes(&mut self, params: lsp::TypeHierarchySupertypesParams) -> Res<Option<Vec<lsp::TypeHierarchyItem>>> {
+        tracing::trace_span!("supertypes").in_scope(move || {
+            tracing::trace!("item: {:?},work_done_progress_params: {:?}, partial_results: {:?}",
+                params.item,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_supertypes(&params.item,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
+
+    #[must_use]
+    fn subtypes(&mut self, params: lsp::TypeHierarchySubtypesParams) -> Res<Option<Vec<lsp::TypeHierarchyItem>>> {
+        tracing::trace_span!("subtypes").in_scope(move || {
+            tracing::trace!("item: {:?},work_done_progress_params: {:?}, partial_results: {:?}",
+                params.item,
+                params.work_done_progress_params,
+                params.partial_result_params
+            );
+            let p = params.work_done_progress_params.work_done_token.map(
+                |tk| self.get_progress(tk)
+            );
+            self.inner.state().get_subtypes(&params.item,p)
+                .map_or_else(|| Box::pin(std::future::ready(Ok(None))) as _,
+                |f| Box::pin(f.map(Result::Ok)) as _
+                )
+        })
+    }
 
     // completionItem/
-    impl_request!(completion_item_resolve = ResolveCompletionItem);
+    #[must_use]
+    fn completion_item_resolve(&mut self, params: lsp::CompletionItem) -> Res<lsp::CompletionItem> {
+        tracing::trace!("completion_item_resolve: {}",params.label);
+        Box::pin(LSPState::resolve_completion(params).map(Result::Ok))
+    }
 
     // codeAction/
-    impl_request!(code_action_resolve = CodeActionResolveRequest);
+    #[must_use]
+    fn code_action_resolve(&mut self, params: lsp::CodeAction) -> Res<lsp::CodeAction> {
+        // all code actions are returned with their edits already computed
+        Box::pin(std::future::ready(Ok(params)))
+    }
 
     // workspaceSymbol/
-    impl_request!(workspace_symbol_resolve = WorkspaceSymbolResolve);
+    #[must_use]
+    fn workspace_symbol_resolve(&mut self, params: lsp::WorkspaceSymbol) -> Res<lsp::WorkspaceSymbol> {
+        // workspace symbols are returned with their locations already
+        Box::pin(std::future::ready(Ok(params)))
+    }
 
     // codeLens/
     impl_request!(code_lens_resolve = CodeLensResolve);
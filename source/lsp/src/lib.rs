@@ -1,8 +1,14 @@
 mod implementation;
 pub mod annotations;
+pub mod code_actions;
+pub mod completion;
 pub mod documents;
 pub mod capabilities;
+pub mod hierarchy;
+pub mod ranges;
+pub mod signature;
 pub mod state;
+pub mod workspace;
 #[cfg(feature="ws")]
 pub mod ws;
 
@@ -270,6 +276,37 @@ impl Drop for ProgressCallbackServer {
 
 impl ProgressCallbackClient {
 
+  pub fn begin(&self,title:String,percentage:Option<u32>) {
+    let _ = self.client.clone().progress(async_lsp::lsp_types::ProgressParams {
+      token:self.token.clone(),
+      value:async_lsp::lsp_types::ProgressParamsValue::WorkDone(
+        async_lsp::lsp_types::WorkDoneProgress::Begin(
+          async_lsp::lsp_types::WorkDoneProgressBegin {
+            message:None,
+            title,
+            percentage,
+            cancellable:None
+          }
+        )
+      )
+    });
+  }
+
+  pub fn update(&self,message:String,percentage:Option<u32>) {
+    let _ = self.client.clone().progress(async_lsp::lsp_types::ProgressParams {
+      token:self.token.clone(),
+      value:async_lsp::lsp_types::ProgressParamsValue::WorkDone(
+        async_lsp::lsp_types::WorkDoneProgress::Report(
+          async_lsp::lsp_types::WorkDoneProgressReport {
+            message:Some(message),
+            percentage,
+            cancellable:None
+          }
+        )
+      )
+    });
+  }
+
   pub fn finish(mut self) {
     let _ = self.client.progress(async_lsp::lsp_types::ProgressParams {
       token:self.token,
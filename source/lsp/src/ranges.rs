@@ -0,0 +1,316 @@
+use async_lsp::lsp_types as lsp;
+use immt_stex::quickparse::{latex::{LaTeXParser, LaTeXToken}, stex::{AnnotIter, DiagnosticCode, DiagnosticLevel, STeXAnnot}};
+use immt_utils::{parsing::ParseStr, prelude::TreeChildIter, sourcerefs::{LSPLineCol, SourceRange}};
+
+use crate::{state::LSPState, IsLSPRange, ProgressCallbackClient};
+
+pub(crate) type Token<'a> = LaTeXToken<'a,LSPLineCol,&'a str>;
+
+/// Sectioning macros, from the outermost to the innermost level
+const SECTIONS: [&str;7] = ["part","chapter","section","subsection","subsubsection","paragraph","subparagraph"];
+
+pub(crate) fn tokenize(text:&str) -> Vec<Token<'_>> {
+  LaTeXParser::<'_,_,Token<'_>,_,()>::new(
+    ParseStr::new(text),(),
+    |_:String,_:SourceRange<LSPLineCol>,_:DiagnosticLevel,_:Option<DiagnosticCode>| ()
+  ).collect()
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn last_line(text:&str) -> u32 {
+  text.lines().count().saturating_sub(1) as u32
+}
+
+#[derive(Default)]
+struct Folder {
+  ranges:Vec<lsp::FoldingRange>,
+  /// first and last line of the current block of consecutive comment lines
+  comments:Option<(u32,u32)>
+}
+impl Folder {
+  fn push(&mut self,start_line:u32,end_line:u32,kind:lsp::FoldingRangeKind) {
+    if end_line > start_line {
+      self.ranges.push(lsp::FoldingRange {
+        start_line,end_line,
+        start_character:None,end_character:None,
+        kind:Some(kind),
+        collapsed_text:None
+      });
+    }
+  }
+
+  fn comment(&mut self,line:u32) {
+    match &mut self.comments {
+      Some((_,end)) if *end == line || *end + 1 == line => *end = line,
+      _ => {
+        self.flush_comments();
+        self.comments = Some((line,line));
+      }
+    }
+  }
+
+  fn flush_comments(&mut self) {
+    if let Some((start,end)) = self.comments.take() {
+      self.push(start,end,lsp::FoldingRangeKind::Comment);
+    }
+  }
+
+  /// A section extends up to the next section of the same or a higher level
+  fn section(&mut self,open:&mut Vec<(usize,u32)>,name:&str,line:u32) {
+    let Some(level) = SECTIONS.iter().position(|s| *s == name) else { return };
+    while open.last().is_some_and(|(l,_)| *l >= level) {
+      if let Some((_,start)) = open.pop() {
+        self.push(start,line.saturating_sub(1),lsp::FoldingRangeKind::Region);
+      }
+    }
+    open.push((level,line));
+  }
+
+  fn fold(&mut self,tokens:&[Token<'_>],end_line:u32) {
+    let mut sections = Vec::new();
+    for t in tokens {
+      match t {
+        LaTeXToken::Comment(range) => { self.comment(range.start.line); continue }
+        LaTeXToken::Text { text,.. } if text.trim().is_empty() => continue,
+        _ => self.flush_comments()
+      }
+      match t {
+        LaTeXToken::ControlSequence { start, name } => self.section(&mut sections,name,start.line),
+        LaTeXToken::MacroApplication(m) => self.section(&mut sections,m.name,m.range.start.line),
+        LaTeXToken::Group { range, children } |
+        LaTeXToken::Math { range, children,.. } => self.fold(children,range.end.line),
+        LaTeXToken::Environment(e) => {
+          let end = e.end.as_ref().map_or(end_line,|m| m.range.start.line.saturating_sub(1));
+          self.push(e.begin.range.start.line,end,lsp::FoldingRangeKind::Region);
+          self.fold(&e.children,end);
+        }
+        LaTeXToken::Comment(_) | LaTeXToken::Text { .. } => ()
+      }
+    }
+    self.flush_comments();
+    for (_,start) in sections {
+      self.push(start,end_line,lsp::FoldingRangeKind::Region);
+    }
+  }
+}
+
+/// Collects the ranges of all tokens containing `pos`
+#[allow(clippy::cast_possible_truncation)]
+fn token_ranges(tokens:&[Token<'_>],pos:LSPLineCol,out:&mut Vec<SourceRange<LSPLineCol>>) {
+  for t in tokens {
+    match t {
+      LaTeXToken::Comment(range) | LaTeXToken::Text { range,.. } if range.contains(pos) =>
+        out.push(*range),
+      LaTeXToken::Group { range, children } |
+      LaTeXToken::Math { range, children,.. } if range.contains(pos) => {
+        out.push(*range);
+        token_ranges(children,pos,out);
+      }
+      LaTeXToken::ControlSequence { start, name } => {
+        let range = SourceRange {
+          start:*start,
+          end:LSPLineCol { line:start.line, col:start.col + name.chars().count() as u32 + 1 }
+        };
+        if range.contains(pos) { out.push(range) }
+      }
+      LaTeXToken::MacroApplication(m) if m.range.contains(pos) => {
+        out.push(m.range);
+        if m.token_range.contains(pos) { out.push(m.token_range) }
+      }
+      LaTeXToken::Environment(e) => {
+        if let Some(end) = &e.end {
+          let range = SourceRange { start:e.begin.range.start, end:end.range.end };
+          if !range.contains(pos) { continue }
+          out.push(range);
+          let body = SourceRange { start:e.begin.range.end, end:end.range.start };
+          if body.contains(pos) { out.push(body) }
+          if end.range.contains(pos) { out.push(end.range) }
+        }
+        if e.begin.range.contains(pos) { out.push(e.begin.range) }
+        if e.name_range.contains(pos) { out.push(e.name_range) }
+        token_ranges(&e.children,pos,out);
+      }
+      _ => ()
+    }
+  }
+}
+
+/// Collects the ranges of (the components of) an annotation containing `pos`
+fn annot_ranges(annot:&STeXAnnot,pos:LSPLineCol,out:&mut Vec<SourceRange<LSPLineCol>>) {
+  let mut push = |r:&SourceRange<LSPLineCol>| if r.contains(pos) { out.push(*r) };
+  match annot {
+    STeXAnnot::Module { name_range, full_range, smodule_range,.. } => {
+      push(full_range);push(smodule_range);push(name_range);
+    }
+    STeXAnnot::SemanticMacro { token_range, full_range,.. } |
+    STeXAnnot::VariableMacro { token_range, full_range,.. } => {
+      push(full_range);push(token_range);
+    }
+    STeXAnnot::ImportModule { archive_range, path_range, token_range, full_range,.. } |
+    STeXAnnot::UseModule { archive_range, path_range, token_range, full_range,.. } |
+    STeXAnnot::SetMetatheory { archive_range, path_range, token_range, full_range,.. } |
+    STeXAnnot::Realize { archive_range, path_range, token_range, full_range,.. } => {
+      push(full_range);push(token_range);push(path_range);
+      if let Some(r) = archive_range { push(r) }
+    }
+    STeXAnnot::Inputref { archive, filepath, token_range, range } => {
+      push(range);push(token_range);push(&filepath.1);
+      if let Some((_,r)) = archive { push(r) }
+    }
+    STeXAnnot::Symdecl { main_name_range, name_ranges, token_range, full_range,.. } |
+    STeXAnnot::Symdef { main_name_range, name_ranges, token_range, full_range,.. } |
+    STeXAnnot::Vardef { main_name_range, name_ranges, token_range, full_range,.. } => {
+      push(full_range);push(token_range);push(main_name_range);
+      if let Some((k,v)) = name_ranges { push(k);push(v); }
+    }
+    STeXAnnot::SymName { full_range, token_range, name_range,.. } |
+    STeXAnnot::Symref { full_range, token_range, name_range,.. } |
+    STeXAnnot::Definiendum { full_range, token_range, name_range,.. } |
+    STeXAnnot::Definame { full_range, token_range, name_range,.. } => {
+      push(full_range);push(token_range);push(name_range);
+    }
+    STeXAnnot::Definiens { uri, full_range, token_range,.. } => {
+      push(full_range);push(token_range);
+      if let Some((_,r)) = uri { push(r) }
+    }
+    STeXAnnot::Paragraph { fors, full_range, name_range,.. } => {
+      push(full_range);push(name_range);
+      for (_,r) in fors { push(r) }
+    }
+    STeXAnnot::MathStructure { full_range, name_range, mathstructure_range,.. } => {
+      push(full_range);push(mathstructure_range);push(name_range);
+    }
+    STeXAnnot::Extension { targets, full_range, name_range, extstructure_range,.. } => {
+      push(full_range);push(extstructure_range);push(name_range);
+      for (_,r) in targets { push(r) }
+    }
+    STeXAnnot::UseStructure { structure_range, token_range, full_range,.. } => {
+      push(full_range);push(token_range);push(structure_range);
+    }
+    STeXAnnot::Morphism { archive_range, domain_range, name_range, full_range, morphism_range,.. } => {
+      push(full_range);push(morphism_range);push(domain_range);push(name_range);
+      if let Some(r) = archive_range { push(r) }
+    }
+    STeXAnnot::Assign { symbol_range, token_range, full_range, definiens,.. } => {
+      push(full_range);push(token_range);push(symbol_range);push(&definiens.0);
+    }
+    STeXAnnot::RenameDecl { symbol_range, name, macroname, token_range, full_range,.. } => {
+      push(full_range);push(token_range);push(symbol_range);push(&macroname.1);
+      if let Some((_,r)) = name { push(r) }
+    }
+  }
+}
+
+/// The range of the word (alphanumeric characters) at `pos`
+#[allow(clippy::cast_possible_truncation)]
+fn word_at(text:&str,pos:LSPLineCol) -> Option<SourceRange<LSPLineCol>> {
+  let line : Vec<char> = text.lines().nth(pos.line as usize)?.chars().collect();
+  let col = (pos.col as usize).min(line.len());
+  let before = line[..col].iter().rev().take_while(|c| c.is_alphanumeric()).count();
+  let after = line[col..].iter().take_while(|c| c.is_alphanumeric()).count();
+  if before + after == 0 { return None }
+  Some(SourceRange {
+    start:LSPLineCol { line:pos.line, col:(col - before) as u32 },
+    end:LSPLineCol { line:pos.line, col:(col + after) as u32 }
+  })
+}
+
+/// Builds the chain of strictly nested ranges containing `pos`, from the innermost to the outermost
+fn selection_range(mut ranges:Vec<SourceRange<LSPLineCol>>,pos:LSPLineCol) -> lsp::SelectionRange {
+  ranges.sort_by(|a,b| a.start.cmp(&b.start).then_with(|| b.end.cmp(&a.end)));
+  let mut ret : Option<lsp::SelectionRange> = None;
+  let mut last : Option<SourceRange<LSPLineCol>> = None;
+  for r in ranges {
+    if last.is_some_and(|l| l == r || r.start < l.start || r.end > l.end) { continue }
+    last = Some(r);
+    ret = Some(lsp::SelectionRange { range:r.into_range(), parent:ret.map(Box::new) });
+  }
+  ret.unwrap_or_else(|| lsp::SelectionRange {
+    range:SourceRange { start:pos, end:pos }.into_range(),
+    parent:None
+  })
+}
+
+/// The names of the `\begin` and `\end` of the environment whose begin or end name contains `pos`
+#[allow(clippy::cast_possible_truncation)]
+fn environment_names(tokens:&[Token<'_>],pos:LSPLineCol) -> Option<[SourceRange<LSPLineCol>;2]> {
+  for t in tokens {
+    match t {
+      LaTeXToken::Environment(e) => {
+        if let Some(end) = &e.end {
+          // `\end{` is 5 characters long; environments ended with spaces (`\end {name}`) are not linked
+          let start = LSPLineCol { line:end.range.start.line, col:end.range.start.col + 5 };
+          let end_name = SourceRange {
+            start,
+            end:LSPLineCol { line:start.line, col:start.col + e.name.chars().count() as u32 }
+          };
+          let plain = end.range.end == LSPLineCol { line:start.line, col:end_name.end.col + 1 };
+          if plain && (e.name_range.contains(pos) || end_name.contains(pos)) {
+            return Some([e.name_range,end_name])
+          }
+        }
+        if let Some(r) = environment_names(&e.children,pos) { return Some(r) }
+      }
+      LaTeXToken::Group { children, .. } |
+      LaTeXToken::Math { children, .. } => if let Some(r) = environment_names(children,pos) { return Some(r) },
+      _ => ()
+    }
+  }
+  None
+}
+
+impl LSPState {
+  #[must_use]
+  pub fn get_folding_ranges(&self,uri:&lsp::Url,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::FoldingRange>>>> {
+    let d = self.get(uri)?;
+    let ranges = d.with_text(|text| {
+      let mut folder = Folder::default();
+      folder.fold(&tokenize(text),last_line(text));
+      folder.ranges
+    });
+    if let Some(p) = progress { p.finish() }
+    Some(std::future::ready(Some(ranges)))
+  }
+
+  #[must_use]
+  pub fn get_linked_editing_ranges(&self,uri:&lsp::Url,position:lsp::Position,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<lsp::LinkedEditingRanges>>> {
+    let d = self.get(uri)?;
+    let pos = LSPLineCol {
+      line:position.line,
+      col:position.character
+    };
+    let ranges = d.with_text(|text| environment_names(&tokenize(text),pos));
+    if let Some(p) = progress { p.finish() }
+    Some(std::future::ready(ranges.map(|r| lsp::LinkedEditingRanges {
+      ranges:r.into_iter().map(IsLSPRange::into_range).collect(),
+      word_pattern:None
+    })))
+  }
+
+  #[must_use]
+  pub fn get_selection_ranges(&self,uri:&lsp::Url,positions:Vec<lsp::Position>,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<Vec<lsp::SelectionRange>>>> {
+    let d = self.get(uri)?;
+    let positions : Vec<_> = positions.into_iter().map(|p| LSPLineCol {
+      line:p.line,
+      col:p.character
+    }).collect();
+    let mut ranges : Vec<_> = d.with_text(|text| {
+      let tokens = tokenize(text);
+      positions.iter().map(|p| {
+        let mut v : Vec<_> = word_at(text,*p).into_iter().collect();
+        token_ranges(&tokens,*p,&mut v);
+        v
+      }).collect()
+    });
+    Some(d.with_annots(self.clone(),move |data| {
+      let iter : AnnotIter = data.annotations.iter().into();
+      for a in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+        for (p,v) in positions.iter().zip(ranges.iter_mut()) {
+          annot_ranges(a,*p,v);
+        }
+      }
+      if let Some(p) = progress { p.finish() }
+      positions.into_iter().zip(ranges).map(|(p,v)| selection_range(v,p)).collect()
+    }))
+  }
+}
@@ -0,0 +1,210 @@
+use std::fmt::Write;
+
+use async_lsp::lsp_types as lsp;
+use futures::FutureExt;
+use immt_ontology::content::{declarations::symbols::{ArgSpec, Symbol}, terms::ArgMode};
+use immt_stex::quickparse::stex::{structs::SymbolReference, AnnotIter, STeXAnnot, STeXParseDataI};
+use immt_system::backend::{Backend, GlobalBackend};
+use immt_utils::{prelude::TreeChildIter, sourcerefs::{LSPLineCol, SourceRange}};
+
+use crate::{annotations::text_in_range, state::LSPState, ProgressCallbackClient};
+
+/// What is known about the arguments of a symbol
+struct Signature {
+  arity:ArgSpec,
+  /// The type of the symbol; LaTeX source if declared in a loaded document
+  tp:Option<String>
+}
+
+/// All semantic macros with arguments that start before `pos`, in document order
+fn macros_before(data:&STeXParseDataI,pos:LSPLineCol) -> Vec<(SymbolReference<LSPLineCol>,u8,SourceRange<LSPLineCol>)> {
+  let iter : AnnotIter = data.annotations.iter().into();
+  <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter).filter_map(|e| match e {
+    STeXAnnot::SemanticMacro { uri, argnum, token_range, .. }
+      if *argnum > 0 && token_range.end <= pos => Some((uri.clone(),*argnum,*token_range)),
+    _ => None
+  }).collect()
+}
+
+/// The byte offset of `pos` in `text`, given the offsets of the starts of all lines
+fn offset(text:&str,line_starts:&[usize],pos:LSPLineCol) -> Option<usize> {
+  let start = *line_starts.get(pos.line as usize)?;
+  let line = &text[start..];
+  Some(start + line.chars().take(pos.col as usize).map(char::len_utf8).sum::<usize>())
+}
+
+/// The innermost of the `macros` (as returned by [`macros_before`]) whose arguments contain the
+/// end of `text`, together with the index of the argument the end of `text` is in (or in front of)
+fn macro_at(
+  text:&str,macros:Vec<(SymbolReference<LSPLineCol>,u8,SourceRange<LSPLineCol>)>,pos:LSPLineCol
+) -> Option<(SymbolReference<LSPLineCol>,u8,SourceRange<LSPLineCol>,u32)> {
+  let line_starts = std::iter::once(0).chain(
+    text.char_indices().filter(|(_,c)| *c == '\n').map(|(i,_)| i + 1)
+  ).collect::<Vec<_>>();
+  let end = offset(text,&line_starts,pos)?;
+  macros.into_iter().rev().find_map(|(uri,argnum,token_range)| {
+    let start = offset(text,&line_starts,token_range.end)?;
+    let active = active_argument(text.get(start..end)?,argnum)?;
+    Some((uri,argnum,token_range,active))
+  })
+}
+
+/// The index of the argument the end of `text` is in (or in front of), where `text`
+/// starts directly after a macro with `argnum` arguments; `None` if `text` extends beyond
+/// the arguments of the macro. Notation options (`\foo[op]{a}`) are skipped.
+fn active_argument(text:&str,argnum:u8) -> Option<u32> {
+  let argnum = u32::from(argnum);
+  let mut depth = 0u32;
+  let mut index = 0;
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == '%' {
+      chars.by_ref().find(|c| *c == '\n');
+      continue
+    }
+    if depth > 0 {
+      match c {
+        // escaped characters (in particular braces) do not change the depth
+        '\\' => { chars.next(); }
+        '{' => depth += 1,
+        '}' => {
+          depth -= 1;
+          if depth == 0 { index += 1; }
+        }
+        _ => ()
+      }
+      continue
+    }
+    if c.is_whitespace() { continue }
+    if index >= argnum { return None }
+    match c {
+      '[' if index == 0 => {
+        // notation options; may contain braces, but no unbalanced brackets
+        let mut inner = 0u32;
+        let mut closed = false;
+        while let Some(c) = chars.next() {
+          match c {
+            '\\' => { chars.next(); }
+            '{' => inner += 1,
+            '}' => inner = inner.saturating_sub(1),
+            ']' if inner == 0 => { closed = true; break }
+            _ => ()
+          }
+        }
+        if !closed { return Some(0) }
+      }
+      '\\' => {
+        // a control sequence on its own is an argument, too
+        index += 1;
+        if chars.next().is_some_and(|c| c.is_ascii_alphabetic()) {
+          while chars.next_if(char::is_ascii_alphabetic).is_some() {}
+        }
+      }
+      '{' => depth = 1,
+      _ => index += 1
+    }
+  }
+  if depth == 0 && index >= argnum { None } else { Some(index) }
+}
+
+const fn describe(mode:ArgMode) -> &'static str {
+  match mode {
+    ArgMode::Normal => "argument",
+    ArgMode::Sequence => "sequence of arguments",
+    ArgMode::Binding => "bound variable",
+    ArgMode::BindingSequence => "sequence of bound variables"
+  }
+}
+
+impl LSPState {
+  #[must_use]
+  pub fn get_signature_help(&self,uri:&lsp::Url,position:lsp::Position,progress:Option<ProgressCallbackClient>) -> Option<impl std::future::Future<Output=Option<lsp::SignatureHelp>>> {
+    let d = self.get(uri)?;
+    let pos = LSPLineCol {
+      line:position.line,
+      col:position.character
+    };
+    let slf = self.clone();
+    Some(d.clone().with_annots(self.clone(),move |data| macros_before(data,pos)).then(move |r| async move {
+      let macros = r?;
+      let (symbol,argnum,macroname,active) = d.with_text(|text| {
+        let (symbol,argnum,token_range,active) = macro_at(text,macros,pos)?;
+        let macroname = text_in_range(text,token_range)?.to_string();
+        Some((symbol,argnum,macroname,active))
+      })?;
+      let ret = tokio::task::spawn_blocking(move || {
+        let signature = slf.signature(&symbol,argnum);
+        signature_help(&symbol,macroname,signature,active)
+      }).await.ok();
+      if let Some(p) = progress { p.finish() }
+      ret
+    }))
+  }
+
+  /// The argument modes and type of `symbol`, from its declaration if it is in a loaded
+  /// document, otherwise from the backend
+  fn signature(&self,symbol:&SymbolReference<LSPLineCol>,argnum:u8) -> Signature {
+    if let Some(url) = symbol.filepath.as_ref().and_then(|p| lsp::Url::from_file_path(p).ok()) {
+      let data = self.documents.read().get(&url).map(|d| d.annotations().clone());
+      if let Some(data) = data {
+        let lock = data.lock();
+        let iter : AnnotIter = lock.annotations.iter().into();
+        let args = <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter).find_map(|e| match e {
+          STeXAnnot::Symdecl { uri, parsed_args, .. } |
+          STeXAnnot::Symdef { uri, parsed_args, .. } if uri.uri == symbol.uri =>
+            Some((parsed_args.args.as_ref().map(|(a,_,_)| a.clone()),parsed_args.tp.as_ref().map(|(_,v,_)| *v))),
+          _ => None
+        });
+        drop(lock);
+        if let Some((arity,tp)) = args {
+          let tp = tp.and_then(|range|
+            self.text_of(&url).and_then(|t| text_in_range(&t,range).map(ToString::to_string))
+          );
+          return Signature { arity:arity.unwrap_or_default(), tp }
+        }
+      }
+    }
+    if let Some(s) = GlobalBackend::get().get_declaration::<Symbol>(&symbol.uri) {
+      let s = s.as_ref();
+      return Signature { arity:s.arity.clone(), tp:s.tp.as_ref().map(ToString::to_string) }
+    }
+    Signature { arity:argnum.to_string().parse().unwrap_or_default(), tp:None }
+  }
+}
+
+/// `\macro{#1:i}{#2:a}...`, with the argument modes as parameters
+#[allow(clippy::cast_possible_truncation)]
+fn signature_help(symbol:&SymbolReference<LSPLineCol>,mut label:String,signature:Signature,active:u32) -> lsp::SignatureHelp {
+  let Signature { arity, tp } = signature;
+  // parameter offsets are in UTF-16 code units
+  let len = |s:&str| s.encode_utf16().count() as u32;
+  let parameters = arity.into_iter().enumerate().map(|(i,mode)| {
+    label.push('{');
+    let start = len(&label);
+    let _ = write!(label,"#{}:{mode}",i + 1);
+    let end = len(&label);
+    label.push('}');
+    lsp::ParameterInformation {
+      label:lsp::ParameterLabel::LabelOffsets([start,end]),
+      documentation:Some(lsp::Documentation::String(describe(mode).to_string()))
+    }
+  }).collect();
+  let mut documentation = format!("<b>{}</b>",symbol.uri);
+  if let Some(tp) = tp {
+    let tp = tp.split_whitespace().collect::<Vec<_>>().join(" ");
+    let _ = write!(documentation,"\n\nType: `{tp}`");
+  }
+  lsp::SignatureHelp {
+    signatures:vec![lsp::SignatureInformation {
+      label,
+      documentation:Some(lsp::Documentation::MarkupContent(lsp::MarkupContent {
+        kind:lsp::MarkupKind::Markdown,
+        value:documentation
+      })),
+      parameters:Some(parameters),
+      active_parameter:Some(active)
+    }],
+    active_signature:Some(0),
+    active_parameter:Some(active)
+  }
+}
@@ -1,9 +1,9 @@
-use std::{collections::hash_map::Entry, path::Path};
+use std::{collections::hash_map::Entry, path::{Path, PathBuf}};
 
 use async_lsp::{lsp_types as lsp, ClientSocket, LanguageClient};
-use immt_ontology::uris::DocumentURI;
+use immt_ontology::uris::{DocumentURI, URIRefTrait};
 use immt_stex::{quickparse::stex::{DiagnosticLevel, STeXAnnot, STeXDiagnostic, STeXParseData, STeXParseDataI}, OutputCont, RusTeX};
-use immt_system::{backend::{archives::LocalArchive, AnyBackend, Backend, GlobalBackend, TemporaryBackend}, formats::OMDocResult};
+use immt_system::{backend::{archives::{source_files::{SourceDir, SourceEntry}, Archive, LocalArchive}, AnyBackend, Backend, GlobalBackend, TemporaryBackend}, formats::OMDocResult};
 use immt_utils::{prelude::{HMap, TreeChildIter}, sourcerefs::{LSPLineCol, SourceRange}};
 use smallvec::SmallVec;
 
@@ -14,6 +14,15 @@ pub enum DocOrData {
   Doc(LSPDocument),
   Data(STeXParseData)
 }
+impl DocOrData {
+  #[inline]#[must_use]
+  pub const fn annotations(&self) -> &STeXParseData {
+    match self {
+      Self::Doc(d) => &d.annotations,
+      Self::Data(d) => d
+    }
+  }
+}
 
 
 #[derive(Default,Clone)]
@@ -57,7 +66,8 @@ impl LSPState {
         lock.diagnostics.insert(STeXDiagnostic {
           level: DiagnosticLevel::Error,
           message: format!("RusTeX Error: {e}"),
-          range: SourceRange::default()
+          range: SourceRange::default(),
+          code: None
         });
         let _ = client.publish_diagnostics(lsp::PublishDiagnosticsParams {
           uri:uri.clone(),version:None,diagnostics:lock.diagnostics.iter().map(to_diagnostic).collect()
@@ -118,6 +128,24 @@ impl LSPState {
     }
   }
 
+  /// The paths and [`DocumentURI`]s of all source files in local archives
+  #[must_use]
+  pub fn archive_files() -> Vec<(PathBuf,DocumentURI)> {
+    let mut files = Vec::new();
+    for a in GlobalBackend::get().all_archives().iter() {
+      let Archive::Local(a) = a else { continue };
+      a.with_sources(|d| for e in <_ as TreeChildIter<SourceDir>>::dfs(d.children.iter()) {
+        if let SourceEntry::File(f) = e {
+          files.push((
+            f.relative_path.split('/').fold(a.source_dir(),|p,s| p.join(s)),
+            DocumentURI::from_archive_relpath(a.uri().owned(), &f.relative_path)
+          ));
+        }
+      });
+    }
+    files
+  }
+
   pub fn load<const FULL:bool>(&self,p:&Path,uri:&DocumentURI,and_then:impl FnOnce(&STeXParseData)) {
     let Some(lsp_uri) = lsp::Url::from_file_path(p).ok() else {return};
     if self.documents.read().get(&lsp_uri).is_some() { return }
@@ -145,6 +173,12 @@ impl LSPState {
     self.documents.write().insert(uri,DocOrData::Doc(doc));
   }
 
+  /// The annotations of all loaded documents
+  #[must_use]
+  pub fn all_annotations(&self) -> Vec<(lsp::Url,STeXParseData)> {
+    self.documents.read().iter().map(|(uri,d)| (uri.clone(),d.annotations().clone())).collect()
+  }
+
   #[must_use]
   pub fn get(&self,uri:&lsp::Url) -> Option<LSPDocument> {
     if let Some(DocOrData::Doc(doc)) = self.documents.read().get(uri) {
@@ -0,0 +1,168 @@
+use async_lsp::{lsp_types as lsp, ClientSocket};
+use immt_stex::quickparse::stex::{AnnotIter, STeXAnnot};
+use immt_utils::prelude::TreeChildIter;
+
+use crate::{annotations::{result_id, to_diagnostic}, state::LSPState, IsLSPRange, ProgressCallbackClient};
+
+/// Number of documents whose diagnostics are sent in one partial result
+const CHUNK_SIZE: usize = 100;
+/// Maximum number of results for a workspace symbol query
+const MAX_SYMBOLS: usize = 256;
+
+/// `$/progress` notification carrying partial results of a workspace diagnostic request
+struct WorkspaceDiagnosticPartialResult;
+impl lsp::notification::Notification for WorkspaceDiagnosticPartialResult {
+  type Params = PartialResultParams;
+  const METHOD : &str = "$/progress";
+}
+#[derive(serde::Serialize,serde::Deserialize)]
+struct PartialResultParams {
+  token:lsp::ProgressToken,
+  value:lsp::WorkspaceDiagnosticReportPartialResult
+}
+
+impl LSPState {
+  /// Quick-parses all `.tex` files in local archives that are not loaded yet and
+  /// reports the diagnostics of all documents; if `partial` is given, the reports are sent
+  /// in chunks as partial results and the final result is empty. Documents whose diagnostics
+  /// match the `previous` result id are reported as unchanged, and documents the client
+  /// knows diagnostics for that are no longer loaded are reported as empty.
+  pub fn get_workspace_diagnostics(&self,previous:Vec<lsp::PreviousResultId>,client:ClientSocket,partial:Option<lsp::ProgressToken>,progress:Option<ProgressCallbackClient>) -> impl std::future::Future<Output=lsp::WorkspaceDiagnosticReportResult> {
+    let slf = self.clone();
+    async move {
+      let items = tokio::task::spawn_blocking(move || slf.workspace_diagnostics(&previous,&client,partial,progress)).await
+        .unwrap_or_default();
+      lsp::WorkspaceDiagnosticReportResult::Report(lsp::WorkspaceDiagnosticReport { items })
+    }
+  }
+
+  #[allow(clippy::cast_possible_truncation)]
+  fn workspace_diagnostics(&self,previous:&[lsp::PreviousResultId],client:&ClientSocket,partial:Option<lsp::ProgressToken>,progress:Option<ProgressCallbackClient>) -> Vec<lsp::WorkspaceDocumentDiagnosticReport> {
+    if let Some(p) = &progress { p.begin("Collecting diagnostics".to_string(),Some(0)); }
+    let files : Vec<_> = {
+      let docs = self.documents.read();
+      Self::archive_files().into_iter().filter(|(p,_)|
+        p.extension().is_some_and(|e| e == "tex") &&
+        lsp::Url::from_file_path(p).is_ok_and(|u| !docs.contains_key(&u))
+      ).collect()
+    };
+    self.load_all(files,|_,_| ());
+
+    let docs = self.all_annotations();
+    let previous_id = |uri:&lsp::Url| previous.iter().find(|p| p.uri == *uri).map(|p| p.value.as_str());
+    let mut items : Vec<_> = previous.iter()
+      // documents the client knows diagnostics for, that no longer exist
+      .filter(|p| !docs.iter().any(|(uri,_)| *uri == p.uri))
+      .map(|p| lsp::WorkspaceDocumentDiagnosticReport::Full(lsp::WorkspaceFullDocumentDiagnosticReport {
+        uri:p.uri.clone(),
+        version:None,
+        full_document_diagnostic_report:lsp::FullDocumentDiagnosticReport::default()
+      }))
+      .collect();
+    if let Some(token) = partial.as_ref().filter(|_| !items.is_empty()) {
+      let _ = client.notify::<WorkspaceDiagnosticPartialResult>(PartialResultParams {
+        token:token.clone(),
+        value:lsp::WorkspaceDiagnosticReportPartialResult { items:std::mem::take(&mut items) }
+      });
+    }
+    for (i,chunk) in docs.chunks(CHUNK_SIZE).enumerate() {
+      let reports = chunk.iter().filter_map(|(uri,data)| {
+        let lock = data.lock();
+        let id = result_id(lock.diagnostics.iter());
+        match previous_id(uri) {
+          Some(p) if p == id => return Some(lsp::WorkspaceDocumentDiagnosticReport::Unchanged(lsp::WorkspaceUnchangedDocumentDiagnosticReport {
+            uri:uri.clone(),
+            version:None,
+            unchanged_document_diagnostic_report:lsp::UnchangedDocumentDiagnosticReport { result_id:id }
+          })),
+          // documents without diagnostics only need to be reported if the client knows older ones
+          None if lock.diagnostics.is_empty() => return None,
+          _ => ()
+        }
+        Some(lsp::WorkspaceDocumentDiagnosticReport::Full(lsp::WorkspaceFullDocumentDiagnosticReport {
+          uri:uri.clone(),
+          version:None,
+          full_document_diagnostic_report:lsp::FullDocumentDiagnosticReport {
+            result_id:Some(id),
+            items:lock.diagnostics.iter().map(to_diagnostic).collect()
+          }
+        }))
+      });
+      if let Some(token) = &partial {
+        let _ = client.notify::<WorkspaceDiagnosticPartialResult>(PartialResultParams {
+          token:token.clone(),
+          value:lsp::WorkspaceDiagnosticReportPartialResult { items:reports.collect() }
+        });
+      } else {
+        items.extend(reports);
+      }
+      if let Some(p) = &progress {
+        let done = ((i + 1) * CHUNK_SIZE).min(docs.len());
+        p.update(format!("{done}/{}",docs.len()),Some((100 * done / docs.len()) as u32));
+      }
+    }
+    if let Some(p) = progress { p.finish() }
+    items
+  }
+
+  /// Modules and symbols in all loaded documents whose names fuzzily match `query`;
+  /// the container name of each result is its full [`ModuleURI`](immt_ontology::uris::ModuleURI)
+  /// or [`SymbolURI`](immt_ontology::uris::SymbolURI).
+  pub fn get_workspace_symbols(&self,query:String,progress:Option<ProgressCallbackClient>) -> impl std::future::Future<Output=Option<lsp::WorkspaceSymbolResponse>> {
+    let slf = self.clone();
+    async move {
+      let ret = tokio::task::spawn_blocking(move || slf.workspace_symbols(&query)).await.ok();
+      if let Some(p) = progress { p.finish() }
+      ret.map(lsp::WorkspaceSymbolResponse::Nested)
+    }
+  }
+
+  fn workspace_symbols(&self,query:&str) -> Vec<lsp::WorkspaceSymbol> {
+    let query = query.to_lowercase();
+    let mut ret = Vec::new();
+    for (url,data) in self.all_annotations() {
+      let lock = data.lock();
+      let iter : AnnotIter = lock.annotations.iter().into();
+      for e in <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter) {
+        let (name,uri,kind,range) = match e {
+          STeXAnnot::Module { uri, name_range, .. } =>
+            (uri.name().to_string(),uri.to_string(),lsp::SymbolKind::MODULE,name_range),
+          STeXAnnot::Symdecl { uri, main_name_range, .. } |
+          STeXAnnot::Symdef { uri, main_name_range, .. } =>
+            (uri.uri.name().to_string(),uri.uri.to_string(),lsp::SymbolKind::OBJECT,main_name_range),
+          STeXAnnot::MathStructure { uri, name_range, .. } |
+          STeXAnnot::Extension { uri, name_range, .. } =>
+            (uri.uri.name().to_string(),uri.uri.to_string(),lsp::SymbolKind::STRUCT,name_range),
+          _ => continue
+        };
+        let Some(score) = fuzzy_score(&name.to_lowercase(),&query) else { continue };
+        ret.push((score,lsp::WorkspaceSymbol {
+          name,kind,
+          tags:None,
+          container_name:Some(uri),
+          location:lsp::OneOf::Left(lsp::Location { uri:url.clone(), range:range.into_range() }),
+          data:None
+        }));
+      }
+    }
+    ret.sort_by(|(a,l),(b,r)| a.cmp(b).then_with(|| l.name.cmp(&r.name)));
+    ret.into_iter().take(MAX_SYMBOLS).map(|(_,s)| s).collect()
+  }
+}
+
+/// Whether all characters of `query` occur in `name` in order; lower scores mean
+/// fewer (and shorter) gaps between the matched characters.
+fn fuzzy_score(name:&str,query:&str) -> Option<usize> {
+  let mut score = 0;
+  let mut chars = name.chars();
+  for q in query.chars() {
+    let mut gap = 0;
+    loop {
+      let c = chars.next()?;
+      if c == q { break }
+      gap += 1;
+    }
+    score += gap;
+  }
+  Some(score)
+}
@@ -7,6 +7,7 @@
  * | [`/api/settings`](get_settings) | (None) | [Settings](SettingsSpec) (requires admin login) |
  * | [`/api/login`](login) | `username=<STRING>`, `password=<STRING>` | log in |
  * | [`/api/login_state`](login_state) | (None) | [LoginState] |
+ * | [`/api/search`](search) | `q=<STRING>`, (optional) `kind=symbol\|paragraph\|document` | `Vec<`[SearchResult](crate::router::search::SearchResult)`>` - full-text/fuzzy search over symbol names, macro names, definienda, paragraphs and section titles (GET) |
  * | **Backend** | | |
  * | [`/api/backend/group_entries`](backend::group_entries) | (optional) `in=<STRING>` | `(Vec<`[ArchiveGroupData](crate::router::backend::ArchiveGroupData)`>,Vec<`[ArchiveData](crate::router::backend::ArchiveData)`>)` - the archives and archive groups in the provided archive group (if given) or on the top-level (if None) |
  * | [`/api/backend/archive_entries`](backend::archive_entries) | `archive=<STRING>`, (optional) `path=<STRING>` | `(Vec<`[DirectoryData](crate::router::backend::DirectoryData)`>,Vec<`[FileData](crate::router::backend::FileData)`>)` - the source directories and files in the provided archive, or (if given) the relative path within the provided archive |
@@ -18,6 +19,7 @@
  * | [`/api/buildqueue/run`](buildqueue::run) | `id=<NonZeroU32>` | runs the build queue with the given id (requires admin login)|
  * | [`/api/buildqueue/requeue`](buildqueue::requeue) | `id=<NonZeroU32>` | requeues failed tasks in the queue with the given id (requires admin login)|
  * | [`/api/buildqueue/log`](buildqueue::get_log) | `archive=<STRING>`, `rel_path=<STRING>`, `target=<STRING>` | returns the log of the stated build job (requires admin login)|
+ * | [`/api/buildqueue/report`](buildqueue::report) | `queue=<NonZeroU32>` | `Option<`[BuildReport](buildqueue::BuildReport)`>` - the structured report (per task: archive, path, target, duration, errors) of the last completed run of the given queue; also written as JUnit XML to `<log_dir>/reports/<queue>.xml` |
  * | [`/api/buildqueue/migrate`](buildqueue::migrate) | `id=<NonZeroU32>` | |
  * | [`/api/buildqueue/delete`](buildqueue::delete) | `id=<NonZeroU32>` | |
  * | **Git** | | |
@@ -51,6 +53,7 @@ use crate::{
     settings::get_settings,
     backend,
     query::query_api,
+    search::search,
     buildqueue,
     content,
     git
@@ -1,13 +1,13 @@
 #[cfg(feature = "ssr")]
 fn main() {
-    use immt::server::settings;
+    use immt::server::settings::{self, Command};
     use immt_system::settings::SettingsSpec;
     #[allow(unused_imports)]
     use immt_stex::STEX;
-    fn exit() {
+    fn exit(code:i32) -> ! {
       immt_system::building::queue_manager::QueueManager::clear();
       let _ = immt_system::settings::Settings::get().close();
-      std::process::exit(0)
+      std::process::exit(code)
     }
 
     #[allow(clippy::redundant_pub_crate)]
@@ -21,24 +21,47 @@ fn main() {
             tokio::select! {
               () = immt::server::run(Some(sender)) => {},
               () = immt::server::lsp::lsp(recv) => {},
-              _ = tokio::signal::ctrl_c() => exit()
+              _ = tokio::signal::ctrl_c() => exit(0)
             }
         } else {
             tokio::select! {
               () = immt::server::run(None) => {},
-              _ = tokio::signal::ctrl_c() => exit()
+              _ = tokio::signal::ctrl_c() => exit(0)
             }
         }
     }
 
+    #[allow(clippy::future_not_send)]
+    async fn build(mut settings: SettingsSpec,args:settings::BuildArgs) {
+        let _ce = color_eyre::install();
+        // no server, so no accounts (which would disable the global queue) and no watching
+        settings.server.admin_pwd = None;
+        settings.buildqueue.watch = Some(false);
+        settings.lsp = false;
+        immt_system::initialize(settings);
+        tokio::select! {
+          r = immt::server::build::build(args) => match r {
+            Ok(true) => exit(0),
+            Ok(false) => exit(1),
+            Err(e) => {
+              eprintln!("Error: {e}");
+              exit(2)
+            }
+          },
+          _ = tokio::signal::ctrl_c() => exit(130)
+        }
+    }
 
-    let settings = settings::get_settings();
-    tokio::runtime::Builder::new_multi_thread()
+    let (settings,command) = settings::get_settings();
+    let rt = tokio::runtime::Builder::new_multi_thread()
       .enable_all()
       //.thread_stack_size(15 * 1024 * 1024)
       .build()
-      .expect("Failed to initialize Tokio runtime")
-      .block_on(run(settings));
+      .expect("Failed to initialize Tokio runtime");
+    match command {
+      Some(Command::Build(args)) => rt.block_on(build(settings,args)),
+      None => rt.block_on(run(settings))
+    }
 }
 
 
@@ -1,7 +1,7 @@
 use std::num::NonZeroU32;
 
 use immt_ontology::uris::ArchiveId;
-use immt_utils::{time::{Delta, Eta}, vecmap::VecMap};
+use immt_utils::{time::{Delta, Eta, Timestamp}, vecmap::VecMap};
 use immt_web_utils::inject_css;
 use leptos::{either::EitherOf4, prelude::*};
 use leptos_router::hooks::use_params_map;
@@ -32,6 +32,44 @@ pub enum FormatOrTarget {
   Targets(Vec<String>)
 }
 
+#[derive(Debug,Clone,serde::Serialize,serde::Deserialize)]
+pub struct TaskReport {
+  pub archive:ArchiveId,
+  pub rel_path:String,
+  pub target:String,
+  pub duration_ms:u64,
+  pub failed:bool,
+  pub errors:Vec<String>
+}
+
+#[derive(Debug,Clone,serde::Serialize,serde::Deserialize)]
+pub struct BuildReport {
+  pub queue:String,
+  pub started:Timestamp,
+  pub finished:Timestamp,
+  pub tasks:Vec<TaskReport>
+}
+
+#[cfg(feature="ssr")]
+impl From<immt_system::building::report::BuildReport> for BuildReport {
+  fn from(r:immt_system::building::report::BuildReport) -> Self {
+    use immt_system::building::report::TaskOutcome;
+    Self {
+      queue:r.queue,
+      started:r.started,
+      finished:r.finished,
+      tasks:r.tasks.into_iter().map(|t| TaskReport {
+        archive:t.archive,
+        rel_path:t.rel_path,
+        target:t.target,
+        duration_ms:t.duration_ms,
+        failed:t.outcome == TaskOutcome::Failed,
+        errors:t.errors
+      }).collect()
+    }
+  }
+}
+
 #[cfg(feature="ssr")]
 impl LoginState {
   pub(crate) fn with_queue<R>(&self,id:NonZeroU32,f:impl FnOnce(&immt_system::building::Queue) -> R) -> Result<R,String> {
@@ -281,6 +319,19 @@ pub async fn get_log(queue:NonZeroU32,archive:ArchiveId,rel_path:String,target:S
   Ok(String::from_utf8_lossy(&v).to_string())
 }
 
+#[server(
+  prefix="/api/buildqueue",
+  endpoint="report",
+  output=server_fn::codec::Json
+)]
+#[allow(clippy::unused_async)]
+pub async fn report(queue:NonZeroU32) -> Result<Option<BuildReport>,ServerFnError<String>> {
+  let login = LoginState::get_server();
+  tokio::task::spawn_blocking(move ||
+    login.with_queue(queue, |q| q.last_report().map(Into::into))
+  ).await.map_err(|e| e.to_string())?.map_err(Into::into)
+}
+
 
 #[server(
   prefix="/api/buildqueue",
@@ -876,6 +927,7 @@ pub struct Entry{
   id:u32,
   archive:ArchiveId,
   rel_path:String,
+  pulled_in_by:Option<String>,
   #[cfg(feature="hydrate")]
   steps:RwSignal<VecMap<String,TaskState>>,
   #[cfg(not(feature="hydrate"))]
@@ -887,7 +939,7 @@ impl Entry {
   #[cfg(not(feature="hydrate"))]
   fn as_view(&self) -> impl IntoView {
     view!{
-      <li>{format!("[{}]{}",self.archive,self.rel_path)}</li>
+      <li>{format!("[{}]{}",self.archive,self.rel_path)}{self.pulled_in_by.as_ref().map(|r| view!(<i>" ("{r.clone()}")"</i>))}</li>
     }
   }
 
@@ -904,10 +956,12 @@ impl Entry {
     });
     let rel_path = self.rel_path.clone();
     let archive = self.archive.clone();
+    let pulled_in_by = self.pulled_in_by.clone();
     view!{
       <li><Collapsible>
         <Header slot>
           <b>{title}{move || {let (i,s) = current(); format!(" ({i}/{total}) {s}")}}</b>
+          {pulled_in_by.map(|r| view!(<i>" ("{r}")"</i>))}
         </Header>
         <ol>
         {let rel_path = rel_path.clone();
@@ -931,6 +985,7 @@ impl From<immt_system::building::QueueEntry> for Entry {
       id:e.id.into(),
       archive:e.archive,
       rel_path:e.rel_path.to_string(),
+      pulled_in_by:e.pulled_in_by.map(|r| r.to_string()),
       steps:e.steps.into_iter().map(|(k,v)| (k.to_string(),v.into())).collect()
     }
   }
@@ -1,4 +1,4 @@
-use leptos::{either::{Either, EitherOf4, EitherOf7, EitherOf9}, prelude::*};
+use leptos::{either::{Either, EitherOf10, EitherOf4, EitherOf7}, prelude::*};
 use leptos_meta::Stylesheet;
 use leptos_router::{components::{Outlet, Redirect}, hooks::use_navigate};
 use crate::users::{Login,LoginState};
@@ -16,16 +16,17 @@ use immt_web_utils::components::display_error;
 
 fn do_main(page:Page) -> impl IntoView {
   let inner =  || match page {
-    Page::Home => EitherOf9::A(view!(<span>"TODO"</span>)),
-    Page::MathHub => EitherOf9::B(view!{<super::backend::ArchivesTop/>}),
+    Page::Home => EitherOf10::A(view!(<span>"TODO"</span>)),
+    Page::MathHub => EitherOf10::B(view!{<super::backend::ArchivesTop/>}),
     //Page::Graphs => view!{<GraphTest/>},
-    Page::Log => EitherOf9::C(view!{<super::logging::Logger/>}),
-    Page::Queue => EitherOf9::D(view!{<super::buildqueue::QueuesTop/>}),
-    Page::Query => EitherOf9::E(view!{<super::query::Query/>}),
-    Page::Settings => EitherOf9::F(view!{<super::settings::Settings/>}),
-    Page::MyArchives => EitherOf9::G(view!{<super::git::Archives/>}),
-    Page::Users => EitherOf9::H(view!{<super::users::Users/>}),
-    _ => EitherOf9::I(view!(<span>"TODO"</span>)),
+    Page::Log => EitherOf10::C(view!{<super::logging::Logger/>}),
+    Page::Queue => EitherOf10::D(view!{<super::buildqueue::QueuesTop/>}),
+    Page::Query => EitherOf10::E(view!{<super::query::Query/>}),
+    Page::Search => EitherOf10::F(view!{<super::search::Search/>}),
+    Page::Settings => EitherOf10::G(view!{<super::settings::Settings/>}),
+    Page::MyArchives => EitherOf10::H(view!{<super::git::Archives/>}),
+    Page::Users => EitherOf10::I(view!{<super::users::Users/>}),
+    _ => EitherOf10::J(view!(<span>"TODO"</span>)),
     //Page::Login => view!{<LoginPage/>}
   };
   view!(<main style="height:100%">{inner()}</main>)
@@ -91,6 +92,7 @@ fn side_menu(page:Page) -> impl IntoView {
         <NavDrawer selected_value=page.to_string() class="immt-menu-inner">
             <NavItem value="home" href="/">"Home"</NavItem>
             <NavItem value="mathhub" href="/dashboard/mathhub">"MathHub"</NavItem>
+            <NavItem value="search" href="/dashboard/search">"Search"</NavItem>
             <NavItem value="query" href="/dashboard/query">"Queries"</NavItem>
             {move || match LoginState::get() {
                 LoginState::NoAccounts => leptos::either::EitherOf5::A(view!{
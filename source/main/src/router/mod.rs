@@ -5,6 +5,7 @@ pub mod content;
 pub mod settings;
 pub mod backend;
 pub mod query;
+pub mod search;
 pub mod git;
 pub(crate) mod buildqueue;
 pub(crate) mod logging;
@@ -40,6 +41,7 @@ pub fn Main() -> impl IntoView {
                         <Route path=StaticSegment("queue") view=|| view!(<MainPage page=Page::Queue/>)/>
                         <Route path=StaticSegment("settings") view=|| view!(<MainPage page=Page::Settings/>)/>
                         <Route path=StaticSegment("query") view=|| view!(<MainPage page=Page::Query/>)/>
+                        <Route path=StaticSegment("search") view=|| view!(<MainPage page=Page::Search/>)/>
                         <Route path=StaticSegment("archives") view=|| view!(<MainPage page=Page::MyArchives/>)/>
                         <Route path=StaticSegment("users") view=|| view!(<MainPage page=Page::Users/>)/>
                         <Route path=StaticSegment("") view=|| view!(<MainPage page=Page::Home/>)/>
@@ -77,6 +79,7 @@ enum Page {
     Settings,
     Login,
     Query,
+    Search,
     MyArchives,
     Users
 }
@@ -92,6 +95,7 @@ impl Page {
             Queue => "queue",
             Settings => "settings",
             Query => "query",
+            Search => "search",
             MyArchives => "archives",
             Users => "users",
             NotFound => "notfound"
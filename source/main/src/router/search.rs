@@ -0,0 +1,103 @@
+use immt_ontology::{languages::Language, search::SearchKind};
+use leptos::prelude::*;
+
+#[derive(Debug,Clone,serde::Serialize,serde::Deserialize)]
+pub struct SearchResult {
+  pub kind:SearchKind,
+  pub uri:String,
+  pub name:String,
+  pub macroname:Option<String>,
+  pub snippet:String,
+  pub language:Language,
+  pub score:f32
+}
+
+/// Maximum length (in characters) of the text snippets returned by [`search`]
+const SNIPPET_LEN: usize = 200;
+/// Maximum number of results returned by [`search`]
+const MAX_RESULTS: usize = 50;
+
+#[server(
+  prefix="/api",
+  endpoint="search",
+  input=server_fn::codec::GetUrl,
+  output=server_fn::codec::Json
+)]
+pub async fn search(q:String,kind:Option<SearchKind>) -> Result<Vec<SearchResult>,ServerFnError<String>> {
+  use immt_system::backend::GlobalBackend;
+  tokio::task::spawn_blocking(move || {
+    GlobalBackend::get().search_index().search(&q, kind, MAX_RESULTS)
+      .into_iter().map(|(score,e)| {
+        let mut snippet : String = e.text.chars().take(SNIPPET_LEN).collect();
+        if snippet.len() < e.text.len() { snippet.push('…'); }
+        SearchResult {
+          kind:e.kind,
+          uri:e.uri.into(),
+          name:e.name.into(),
+          macroname:e.macroname.map(Into::into),
+          snippet,
+          language:e.language,
+          score
+        }
+      }).collect()
+  }).await.map_err(|e| ServerFnError::WrappedServerError(e.to_string()))
+}
+
+#[component]
+pub fn Search() -> impl IntoView {
+  let query = RwSignal::new(String::new());
+  let kind = RwSignal::new(None::<SearchKind>);
+  let r = Resource::new(move || (query.get(),kind.get()),|(q,k)| async move {
+    if q.trim().is_empty() { Ok(Vec::new()) } else { search(q,k).await }
+  });
+  view!{
+    <div>
+      <h1>"Search"</h1>
+      <input type="search" placeholder="Search..."
+        on:change=move |ev| query.set(event_target_value(&ev))
+      />
+      <select on:change=move |ev| kind.set(SearchKind::from_key(&event_target_value(&ev)))>
+        <option value="">"All"</option>
+        {SearchKind::ALL.into_iter().map(|k|
+          view!(<option value=k.key()>{k.key()}</option>)
+        ).collect_view()}
+      </select>
+      <Suspense fallback = || view!(<immt_web_utils::components::Spinner/>)>{move ||
+        match r.get() {
+          Some(Ok(results)) if results.is_empty() => leptos::either::EitherOf4::A(
+            if query.with(|q| q.trim().is_empty()) {""} else {"(No results)"}
+          ),
+          Some(Err(e)) => leptos::either::EitherOf4::B(
+            immt_web_utils::components::display_error(e.to_string().into())
+          ),
+          None => leptos::either::EitherOf4::C(view!(<immt_web_utils::components::Spinner/>)),
+          Some(Ok(results)) => leptos::either::EitherOf4::D(result_table(results))
+        }
+      }</Suspense>
+    </div>
+  }
+}
+
+fn result_table(v:Vec<SearchResult>) -> impl IntoView {
+  use thaw::{Table,TableHeader,TableHeaderCell,TableBody,TableRow,TableCell,TableCellLayout};
+  view!{<Table>
+    <TableHeader><TableRow>
+      <TableHeaderCell>"Name"</TableHeaderCell>
+      <TableHeaderCell>"Kind"</TableHeaderCell>
+      <TableHeaderCell>"Language"</TableHeaderCell>
+      <TableHeaderCell>"Text"</TableHeaderCell>
+    </TableRow></TableHeader>
+    <TableBody>{v.into_iter().map(|SearchResult{kind,uri,name,macroname,snippet,language,..}| {
+      let link = format!("/?uri={}",urlencoding::encode(&uri));
+      view!{<TableRow>
+        <TableCell><TableCellLayout>
+          <a href=link title=uri>{name}</a>
+          {macroname.map(|m| view!(" "<code>"\\"{m}</code>))}
+        </TableCellLayout></TableCell>
+        <TableCell><TableCellLayout>{kind.key()}</TableCellLayout></TableCell>
+        <TableCell><TableCellLayout>{language.to_string()}</TableCellLayout></TableCell>
+        <TableCell><TableCellLayout>{snippet}</TableCellLayout></TableCell>
+      </TableRow>}
+    }).collect_view()}</TableBody>
+  </Table>}
+}
@@ -73,6 +73,10 @@ pub(super) fn Settings() -> impl IntoView {
             <td class="immt-settings-col"><b>"Database Path"</b></td>
             <td class="immt-settings-col">{settings.database.unwrap_or_else(|| unreachable!()).display().to_string()}</td>
           </tr>
+          <tr>
+            <td class="immt-settings-col"><b>"Relational Store"</b></td>
+            <td class="immt-settings-col">{settings.rdf_database.map_or_else(|| "(in memory)".to_string(),|p| p.display().to_string())}</td>
+          </tr>
           <tr>
             <td class="immt-settings-col"><b>"Temp Directory"</b></td>
             <td class="immt-settings-col">{settings.temp_dir.unwrap_or_else(|| unreachable!()).display().to_string()}</td>
@@ -99,6 +103,14 @@ pub(super) fn Settings() -> impl IntoView {
             <td class="immt-settings-col"><b>"Threads:"</b></td>
             <td class="immt-settings-col">{settings.buildqueue.num_threads}</td>
           </tr>
+          <tr>
+            <td class="immt-settings-col"><b>"Watch files:"</b></td>
+            <td class="immt-settings-col">{settings.buildqueue.watch.unwrap_or_default().to_string()}</td>
+          </tr>
+          <tr>
+            <td class="immt-settings-col"><b>"Build on change:"</b></td>
+            <td class="immt-settings-col">{settings.buildqueue.auto_build.unwrap_or_default().to_string()}</td>
+          </tr>
       </tbody></Table>
   ))
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+use immt_ontology::uris::ArchiveId;
+use immt_system::{
+  backend::{archives::ArchiveOrGroup as AoG, Backend, GlobalBackend},
+  building::{queue_manager::{QueueId, QueueManager}, Queue, QueueEntry, QueueMessage, TaskState},
+  formats::{BuildTarget, BuildTargetId, FormatOrTargets, SourceFormatId},
+};
+use immt_utils::prelude::HMap;
+
+use super::settings::BuildArgs;
+
+/// Enqueues the requested build jobs into the global queue, runs it and prints
+/// the results of all tasks. Returns whether all tasks succeeded.
+/// ### Errors
+/// if the archive or some target does not exist, or the build queue could not be run
+pub async fn build(args:BuildArgs) -> Result<bool,String> {
+  let BuildArgs{archive,path,target,stale_only} = args;
+  let archive = ArchiveId::new(&archive);
+  let targets = match target {
+    Some(t) => t.split(',').map(|s| BuildTarget::get_from_str(s.trim())
+      .ok_or_else(|| format!("Unknown build target: {s}"))
+    ).collect::<Result<Vec<BuildTargetId>,_>>()?,
+    None => Vec::new()
+  };
+
+  let backend = GlobalBackend::get();
+  let group = backend.with_archive_tree(|tree| match tree.find(&archive) {
+    Some(AoG::Archive(_)) => Ok(false),
+    Some(AoG::Group(_)) => Ok(true),
+    None => Err(format!("Archive {archive} not found"))
+  })?;
+  if group && path.is_some() {
+    return Err("Must specify either an archive with optional path or a group".to_string())
+  }
+  let formats:Vec<SourceFormatId> = if targets.is_empty() {
+    let mut formats = Vec::new();
+    for a in backend.all_archives().iter().filter(|a| in_group(a.id(),&archive)) {
+      for f in a.formats() {
+        if !formats.contains(f) { formats.push(*f); }
+      }
+    }
+    formats
+  } else { Vec::new() };
+
+  // stale-only enqueueing and checking query the relational store
+  let _ = tokio::task::spawn_blocking(immt_system::wait_for_initial_load).await;
+
+  let (mut listener,count) = QueueManager::get().with_global(|q| {
+    let listener = q.listener();
+    let count = if targets.is_empty() {
+      formats.iter().map(|f| {
+        let fot = FormatOrTargets::Format(*f);
+        if group { q.enqueue_group(&archive, fot, stale_only) }
+        else { q.enqueue_archive(&archive, fot, stale_only, path.as_deref()) }
+      }).sum()
+    } else {
+      let fot = FormatOrTargets::Targets(&targets);
+      if group { q.enqueue_group(&archive, fot, stale_only) }
+      else { q.enqueue_archive(&archive, fot, stale_only, path.as_deref()) }
+    };
+    (listener,count)
+  });
+
+  if count == 0 {
+    println!("Nothing to build");
+    return Ok(true)
+  }
+  println!("Building {count} tasks...");
+
+  let mut start = tokio::task::spawn_blocking(|| QueueManager::get().start_queue(QueueId::global()));
+  let mut started = false;
+
+  let mut tasks = HMap::default();
+  let (done,failed) = loop {
+    let msg = tokio::select! {
+      // if starting the queue fails, no further messages will arrive
+      r = &mut start, if !started => {
+        started = true;
+        queue_started(r)?;
+        continue
+      }
+      msg = listener.read_or_lag() => msg
+    };
+    let msg = match msg {
+      Ok(Some(msg)) => msg,
+      Ok(None) => return Err("Build queue closed unexpectedly".to_string()),
+      // we fell behind; resynchronize with the current state of the queue
+      Err(missed) => {
+        println!("(skipped {missed} progress messages)");
+        QueueManager::get().with_global(Queue::state_message)
+      }
+    };
+    match msg {
+      QueueMessage::Started{running,queue,blocked,failed,done} => {
+        for e in running.into_iter().chain(queue).chain(blocked).chain(failed).chain(done) {
+          tasks.insert(u32::from(e.id),e);
+        }
+      }
+      QueueMessage::TaskSuccess{id,target,..} => if let Some(e) = tasks.get(&u32::from(id)) {
+        println!("[ok]     [{}]{} ({target})",e.archive,e.rel_path);
+      },
+      QueueMessage::TaskFailed{id,target,..} => if let Some(e) = tasks.get(&u32::from(id)) {
+        println!("[failed] [{}]{} ({target})",e.archive,e.rel_path);
+      },
+      QueueMessage::Finished{done,failed} => break (done,failed),
+      QueueMessage::Idle(_) | QueueMessage::TaskStarted{..} => ()
+    }
+  };
+  if !started { queue_started(start.await)?; }
+
+  if !failed.is_empty() {
+    println!("\nFailed tasks:");
+    for QueueEntry{archive,rel_path,steps,..} in &failed {
+      for (target,_) in steps.iter().filter(|(_,s)| matches!(s,TaskState::Failed)) {
+        let log = backend.with_archive(archive, |a| a.map(|a| a.get_log(rel_path, *target)));
+        match log {
+          Some(log) => println!("  [{archive}]{rel_path} ({target}): see {}",log.display()),
+          None => println!("  [{archive}]{rel_path} ({target})")
+        }
+      }
+    }
+  }
+  println!("\n{} succeeded, {} failed",done.len(),failed.len());
+  println!("JUnit report: {}",
+    immt_system::settings::Settings::get().log_dir.join("reports").join("global.xml").display()
+  );
+  Ok(failed.is_empty())
+}
+
+/// Turns the outcome of [`QueueManager::start_queue`] (including panics) into an error message
+fn queue_started(r:Result<Result<(),()>,tokio::task::JoinError>) -> Result<(),String> {
+  match r {
+    Ok(Ok(())) => Ok(()),
+    Ok(Err(())) => Err("The global build queue does not exist".to_string()),
+    Err(e) => Err(format!("Running the build queue failed: {e}"))
+  }
+}
+
+fn in_group(id:&ArchiveId,group:&ArchiveId) -> bool {
+  let mut steps = id.steps();
+  group.steps().all(|s| steps.next() == Some(s))
+}
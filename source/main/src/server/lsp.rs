@@ -1,8 +1,7 @@
 use immt_lsp::{annotations::to_diagnostic,async_lsp::{client_monitor::ClientProcessMonitorLayer, concurrency::ConcurrencyLayer, panic::CatchUnwindLayer, router::Router, server::LifecycleLayer, tracing::TracingLayer, ClientSocket, LanguageClient}, state::LSPState, IMMTLSPServer, ProgressCallbackServer};
 
-use immt_ontology::uris::{DocumentURI, URIRefTrait};
-use immt_system::{backend::{archives::{source_files::{SourceDir, SourceEntry}, Archive}, GlobalBackend}, settings::Settings};
-use immt_utils::{prelude::TreeChildIter, time::measure};
+use immt_system::settings::Settings;
+use immt_utils::time::measure;
 use tower::ServiceBuilder;
 use tracing::Level;
 use immt_lsp::async_lsp::lsp_types as lsp;
@@ -30,20 +29,7 @@ impl STDIOLSPServer {
     let workspaces = self.workspaces.clone();
     let _ = tokio::task::spawn_blocking(move || {
       let (_,t) = measure(move || {
-        let mut files = Vec::new();
-        for a in GlobalBackend::get().all_archives().iter() {
-          if let Archive::Local(a) =a { 
-            a.with_sources(|d| for e in <_ as TreeChildIter<SourceDir>>::dfs(d.children.iter()) {
-              match e {
-                SourceEntry::File(f) => files.push((
-                  f.relative_path.split('/').fold(a.source_dir(),|p,s| p.join(s)),
-                  DocumentURI::from_archive_relpath(a.uri().owned(), &f.relative_path)
-              )),
-                _ => {}
-              }
-            })
-          }
-        }
+        let files = LSPState::archive_files();
         ProgressCallbackServer::with(client,"Initializing".to_string(),Some(files.len() as _),|mut p| {
           //let mut i = 0;
           state.load_all(files.into_iter().map(|(path,uri)| {
@@ -1,5 +1,6 @@
 pub mod db;
 pub mod settings;
+pub mod build;
 pub mod lsp;
 pub mod img;
 
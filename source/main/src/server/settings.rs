@@ -48,10 +48,22 @@ struct Cli {
     /// The database file to use for account management etc.
     pub(crate) db: Option<PathBuf>,
 
+    #[arg(long)]
+    /// The directory to use for a persistent relational store
+    pub(crate) rdf_db: Option<PathBuf>,
+
     /// The number of threads to use for the buildqueue
     #[arg(short, long)]
     pub(crate) threads: Option<u8>,
 
+    /// whether to watch the `MathHub` directories for changes
+    #[arg(long)]
+    pub(crate) watch: Option<bool>,
+
+    /// whether to automatically build changed files (requires --watch)
+    #[arg(long)]
+    pub(crate) auto_build: Option<bool>,
+
     /// enter lsp mode
     #[arg(long)]
     pub(crate) lsp: bool,
@@ -66,9 +78,39 @@ struct Cli {
     #[arg(long)]
     pub(crate) gitlab_app_secret: Option<String>,
     #[arg(long)]
-    pub(crate) gitlab_redirect_url: Option<String>
+    pub(crate) gitlab_redirect_url: Option<String>,
+
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>
+}
+
+#[derive(clap::Subcommand,Debug)]
+pub enum Command {
+    /// Build an archive (or archive group) without starting the server, and
+    /// exit with a non-zero status if any build task fails
+    Build(BuildArgs)
+}
+
+#[derive(clap::Args,Debug)]
+pub struct BuildArgs {
+    /// The archive (or archive group) to build
+    #[arg(short, long)]
+    pub archive: String,
+
+    /// Only build the file or directory at this path (relative to the archive's source directory)
+    #[arg(short, long)]
+    pub path: Option<String>,
+
+    /// A comma-separated list of build targets (e.g. `check`); if not given,
+    /// all formats of the archive are built
+    #[arg(long)]
+    pub target: Option<String>,
+
+    /// Only build files that have changed since the last build
+    #[arg(long)]
+    pub stale_only: bool
 }
-impl From<Cli> for (Option<PathBuf>, SettingsSpec) {
+impl From<Cli> for (Option<PathBuf>, SettingsSpec, Option<Command>) {
     fn from(cli: Cli) -> Self {
         let settings = SettingsSpec {
             mathhubs: cli
@@ -81,6 +123,7 @@ impl From<Cli> for (Option<PathBuf>, SettingsSpec) {
                 .unwrap_or_default(),
             debug: cli.debug,
             database: cli.db.map(PathBuf::into_boxed_path),
+            rdf_database: cli.rdf_db.map(PathBuf::into_boxed_path),
             log_dir: cli.log_dir.map(PathBuf::into_boxed_path),
             temp_dir: cli.temp_dir.map(PathBuf::into_boxed_path),
             server: ServerSettings {
@@ -90,6 +133,8 @@ impl From<Cli> for (Option<PathBuf>, SettingsSpec) {
             },
             buildqueue: BuildQueueSettings {
                 num_threads: cli.threads,
+                watch: cli.watch,
+                auto_build: cli.auto_build,
             },
             gitlab: GitlabSettings {
                 url: cli.gitlab_url.map(Into::into),
@@ -100,7 +145,7 @@ impl From<Cli> for (Option<PathBuf>, SettingsSpec) {
             },
             lsp: cli.lsp
         };
-        (cli.config_file, settings)
+        (cli.config_file, settings, cli.command)
     }
 }
 
@@ -113,7 +158,7 @@ impl Cli {
 
 #[must_use]
 #[allow(clippy::missing_panics_doc)]
-pub fn get_settings() -> SettingsSpec {
+pub fn get_settings() -> (SettingsSpec,Option<Command>) {
     fn from_file(cfg_file:&Path) -> SettingsSpec {
         let cfg = std::fs::read_to_string(cfg_file).unwrap_or_else(|e| {
             panic!("Could not read config file {}: {e}", cfg_file.display())
@@ -124,7 +169,7 @@ pub fn get_settings() -> SettingsSpec {
         cfg
     }
     let cli = Cli::get();
-    let (cfg, mut settings) = cli.into();
+    let (cfg, mut settings, command) = cli.into();
     settings += SettingsSpec::from_envs();
     if let Some(cfg_file) = cfg {
         if cfg_file.exists() {
@@ -140,5 +185,5 @@ pub fn get_settings() -> SettingsSpec {
             }
         }
     }
-    settings
+    (settings,command)
 }
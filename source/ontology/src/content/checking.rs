@@ -4,7 +4,7 @@ use crate::{
 
 use super::{
     declarations::{
-        morphisms::Morphism,
+        morphisms::{Assignment, Morphism, MorphismAssignment},
         structures::{Extension, MathStructure},
         Declaration, OpenDeclaration
     },
@@ -24,9 +24,11 @@ enum Elem {
         macroname: Option<Box<str>>,
     },
     Morphism {
-        uri: Option<SymbolURI>,
+        uri: SymbolURI,
         domain: ModuleURI,
         total: bool,
+        assignments: Vec<Assignment>,
+        morphism_assignments: Vec<MorphismAssignment>,
     },
 }
 
@@ -57,13 +59,15 @@ impl Elem {
                     elements: v.into_boxed_slice(),
                 })
             }
-            Self::Morphism { uri, domain, total } => {
+            Self::Morphism { uri, domain, total, assignments, morphism_assignments } => {
                 //println!("Require domain {domain}");
                 let domain = MaybeResolved::resolve(domain,|d| checker.get_module(d));
                 Declaration::Morphism(Morphism {
                     uri,
                     domain,
                     total,
+                    assignments: assignments.into_boxed_slice(),
+                    morphism_assignments: morphism_assignments.into_boxed_slice(),
                     elements: v.into_boxed_slice(),
                 })
             }
@@ -159,12 +163,17 @@ impl<Check: ModuleChecker> ModuleCheckIter<'_, Check> {
                 uri,
                 domain,
                 total,
+                assignments,
+                morphism_assignments,
                 elements,
             }) => {
                 let old_in = std::mem::replace(&mut self.curr_in, elements.into_iter());
                 let old_out = std::mem::take(&mut self.curr_out);
-                self.stack
-                    .push((Elem::Morphism { uri, domain, total }, old_in, old_out));
+                self.stack.push((
+                    Elem::Morphism { uri, domain, total, assignments, morphism_assignments },
+                    old_in,
+                    old_out,
+                ));
             }
         }
     }
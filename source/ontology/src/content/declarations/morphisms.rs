@@ -1,20 +1,44 @@
 use crate::{
-    content::ModuleTrait, uris::{ContentURIRef, SymbolURI}, Checked, CheckingState, Resolvable
+    content::{terms::{Arg, Term}, ModuleTrait},
+    uris::{ContentURI, ContentURIRef, ContentURITrait, ModuleURI, Name, SymbolURI},
+    Checked, CheckingState, Resolvable
 };
 
-use super::{Declaration, DeclarationTrait, OpenDeclaration};
+use super::{symbols::Symbol, Declaration, DeclarationTrait, OpenDeclaration};
 
 #[derive(Debug)]
 pub struct Morphism<State:CheckingState> {
-    pub uri: Option<SymbolURI>,
+    pub uri: SymbolURI,
     pub domain: State::ModuleLike,
     pub total: bool,
+    pub assignments: State::Seq<Assignment>,
+    pub morphism_assignments: State::Seq<MorphismAssignment>,
     pub elements: State::Seq<OpenDeclaration<State>>,
 }
+
+/// An assignment to and/or renaming of a symbol in the domain of a [`Morphism`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Assignment {
+    pub original: SymbolURI,
+    pub definiens: Option<Term>,
+    pub new_name: Option<Name>,
+    pub macroname: Option<Box<str>>,
+}
+
+/// Assigns a morphism `to` to a morphism `from` declared in the domain of a [`Morphism`];
+/// symbols accessed through `from` are translated to the ones accessed through `to`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MorphismAssignment {
+    pub from: SymbolURI,
+    pub to: SymbolURI,
+}
+
 impl Resolvable for Morphism<Checked> {
     type From = SymbolURI;
     fn id(&self) -> std::borrow::Cow<'_,Self::From> {
-        todo!()
+        std::borrow::Cow::Borrowed(&self.uri)
     }
 }
 impl super::private::Sealed for Morphism<Checked> {}
@@ -34,9 +58,315 @@ impl ModuleTrait for Morphism<Checked> {
     }
     #[inline]
     fn content_uri(&self) -> ContentURIRef {
-        ContentURIRef::Symbol(self.uri.as_ref().unwrap_or_else(|| unreachable!()))
+        ContentURIRef::Symbol(&self.uri)
+    }
+}
+
+impl Morphism<Checked> {
+    /// The symbols induced by this morphism: every symbol of the domain (and the modules it imports)
+    /// translated along the morphism, taking renamings and assignments into account. Symbols that
+    /// are already declared in the morphism itself are skipped. Returns an empty vector
+    /// if the domain could not be resolved.
+    #[must_use]
+    pub fn induced_symbols(&self) -> Vec<Symbol> {
+        let Some(domain) = self.domain.get() else {
+            return Vec::new();
+        };
+        let mut seen = vec![self.domain.id().into_owned()];
+        let mut originals = Vec::new();
+        collect_symbols(domain.declarations(), &mut seen, &mut originals);
+
+        let module = self.uri.clone().into_module();
+        let translated: Vec<(&Symbol, Option<&Assignment>, SymbolURI)> = originals
+            .into_iter()
+            .map(|s| {
+                let assignment = self.assignments.iter().find(|a| a.original == s.uri);
+                let name = assignment
+                    .and_then(|a| a.new_name.clone())
+                    .unwrap_or_else(|| s.uri.name().clone());
+                (s, assignment, module.clone() | name)
+            })
+            .collect();
+        let morphisms: Vec<(ModuleURI, ModuleURI)> = self
+            .morphism_assignments
+            .iter()
+            .map(|m| (m.from.clone().into_module(), m.to.clone().into_module()))
+            .collect();
+        let translate_uri = |uri: &SymbolURI| {
+            translated
+                .iter()
+                .find_map(|(s, _, n)| (s.uri == *uri).then(|| n.clone()))
+                .or_else(|| {
+                    morphisms
+                        .iter()
+                        .find_map(|(from, to)| (uri.module() == from).then(|| to.clone() | uri.name().clone()))
+                })
+        };
+
+        translated
+            .iter()
+            .filter(|(_, _, uri)| {
+                !self.elements.iter().any(|d| {
+                    matches!(d, Declaration::Symbol(s) if s.uri.name() == uri.name())
+                })
+            })
+            .map(|(s, assignment, uri)| Symbol {
+                uri: uri.clone(),
+                arity: s.arity.clone(),
+                macroname: assignment.and_then(|a| a.macroname.clone()),
+                role: s.role.clone(),
+                tp: s.tp.as_ref().map(|t| translate(t, &translate_uri)),
+                df: assignment
+                    .and_then(|a| a.definiens.clone())
+                    .or_else(|| s.df.as_ref().map(|t| translate(t, &translate_uri))),
+                assoctype: s.assoctype,
+                reordering: s.reordering.clone(),
+            })
+            .collect()
+    }
+
+    /// Adds the [induced symbols](Self::induced_symbols) to the elements of this morphism, so
+    /// that symbols accessed through the morphism can be resolved like any other declaration
+    pub fn add_induced_symbols(&mut self) {
+        let induced = self.induced_symbols();
+        if induced.is_empty() {
+            return;
+        }
+        let mut elements = std::mem::take(&mut self.elements).into_vec();
+        elements.extend(induced.into_iter().map(Declaration::Symbol));
+        self.elements = elements.into_boxed_slice();
     }
 }
+
+fn collect_symbols<'a>(decls: &'a [Declaration], seen: &mut Vec<ModuleURI>, symbols: &mut Vec<&'a Symbol>) {
+    for d in decls {
+        match d {
+            Declaration::Symbol(s) => symbols.push(s),
+            Declaration::Import(m) => {
+                let id = m.id();
+                if seen.contains(&*id) {
+                    continue;
+                }
+                seen.push(id.into_owned());
+                if let Some(m) = m.get() {
+                    collect_symbols(m.declarations(), seen, symbols);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn translate(tm: &Term, f: &impl Fn(&SymbolURI) -> Option<SymbolURI>) -> Term {
+    match tm {
+        Term::OMID(ContentURI::Symbol(s)) => f(s).map_or_else(|| tm.clone(), |s| Term::OMID(ContentURI::Symbol(s))),
+        Term::OMID(_) | Term::OMV(_) => tm.clone(),
+        Term::OMA { head, args } => Term::OMA {
+            head: Box::new(translate(head, f)),
+            args: args
+                .iter()
+                .map(|a| Arg { term: translate(&a.term, f), mode: a.mode })
+                .collect(),
+        },
+        Term::Field { record, key, owner } => Term::Field {
+            record: Box::new(translate(record, f)),
+            key: key.clone(),
+            owner: owner.as_ref().map(|o| Box::new(translate(o, f))),
+        },
+        Term::OML { name, df, tp } => Term::OML {
+            name: name.clone(),
+            df: df.as_ref().map(|t| Box::new(translate(t, f))),
+            tp: tp.as_ref().map(|t| Box::new(translate(t, f))),
+        },
+        Term::Informal { tag, attributes, children, terms } => Term::Informal {
+            tag: tag.clone(),
+            attributes: attributes.clone(),
+            children: children.clone(),
+            terms: terms.iter().map(|t| translate(t, f)).collect(),
+        },
+    }
+}
+
 crate::serde_impl!{
-    struct Morphism[uri,domain,total,elements]
+    struct Morphism[uri,domain,total,assignments,morphism_assignments,elements]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::{declarations::symbols::ArgSpec, modules::{Module, OpenModule}, ModuleLike};
+    use crate::uris::{ArchiveURI, BaseURI};
+    use crate::{oma, oms, MaybeResolved};
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref ARCHIVE: ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        static ref DOMAIN: ModuleURI = (ARCHIVE.clone() | "Domain").expect("is valid");
+        static ref LEFT: ModuleURI = (ARCHIVE.clone() | "Left").expect("is valid");
+        static ref RIGHT: ModuleURI = (ARCHIVE.clone() | "Right").expect("is valid");
+        static ref BASE: ModuleURI = (ARCHIVE.clone() | "Base").expect("is valid");
+        static ref CODOMAIN: ModuleURI = (ARCHIVE.clone() | "Codomain").expect("is valid");
+        static ref MORPHISM: SymbolURI = (CODOMAIN.clone() | "phi").expect("is valid");
+        static ref INDUCED: ModuleURI = MORPHISM.clone().into_module();
+        // a morphism declared in Base, and the one it is assigned to in Codomain
+        static ref FROM: SymbolURI = (BASE.clone() | "inner").expect("is valid");
+        static ref TO: SymbolURI = (CODOMAIN.clone() | "outer").expect("is valid");
+    }
+
+    fn sym(module: &ModuleURI, name: &str) -> SymbolURI {
+        (module.clone() | name).expect("is valid")
+    }
+
+    fn symbol(uri: SymbolURI, tp: Option<Term>, df: Option<Term>) -> Declaration {
+        Declaration::Symbol(Symbol {
+            uri,
+            arity: ArgSpec::default(),
+            macroname: None,
+            role: Box::default(),
+            tp,
+            df,
+            assoctype: None,
+            reordering: None,
+        })
+    }
+
+    fn module(uri: &ModuleURI, elements: Vec<Declaration>) -> Module {
+        Module(triomphe::Arc::new(OpenModule {
+            uri: uri.clone(),
+            meta: None,
+            signature: None,
+            elements: elements.into_boxed_slice(),
+        }))
+    }
+
+    fn import(m: &Module) -> Declaration {
+        Declaration::Import(MaybeResolved::resolved(ModuleLike::Module(m.clone())))
+    }
+
+    /// `Domain` imports `Left` and `Right`, which both import `Base`; `Base` imports
+    /// `Domain` back (unresolved), closing a cycle.
+    fn domain() -> Module {
+        let base = module(&BASE, vec![
+            symbol(sym(&BASE, "set"), None, None),
+            Declaration::Import(MaybeResolved::unresolved(DOMAIN.clone())),
+        ]);
+        let left = module(&LEFT, vec![
+            import(&base),
+            symbol(sym(&LEFT, "op"), Some(oms!(sym(&BASE, "set"))), None),
+        ]);
+        let right = module(&RIGHT, vec![
+            import(&base),
+            symbol(
+                sym(&RIGHT, "unit"),
+                Some(oms!(sym(&BASE, "set"))),
+                Some(oms!(sym(&FROM.clone().into_module(), "zero"))),
+            ),
+        ]);
+        module(&DOMAIN, vec![
+            import(&left),
+            import(&right),
+            symbol(
+                sym(&DOMAIN, "square"),
+                Some(oms!(sym(&BASE, "set"))),
+                Some(oma!(oms!(sym(&LEFT, "op")), [{N:oms!(sym(&RIGHT, "unit"))}])),
+            ),
+        ])
+    }
+
+    fn morphism(assignments: Vec<Assignment>, elements: Vec<Declaration>) -> Morphism<Checked> {
+        Morphism {
+            uri: MORPHISM.clone(),
+            domain: MaybeResolved::resolved(ModuleLike::Module(domain())),
+            total: false,
+            assignments: assignments.into_boxed_slice(),
+            morphism_assignments: vec![MorphismAssignment { from: FROM.clone(), to: TO.clone() }].into_boxed_slice(),
+            elements: elements.into_boxed_slice(),
+        }
+    }
+
+    fn find<'a>(symbols: &'a [Symbol], name: &str) -> &'a Symbol {
+        symbols
+            .iter()
+            .find(|s| s.uri == sym(&INDUCED, name))
+            .unwrap_or_else(|| panic!("{name} not induced"))
+    }
+
+    #[test]
+    fn transitive_imports_once() {
+        let induced = morphism(Vec::new(), Vec::new()).induced_symbols();
+        let mut names: Vec<_> = induced.iter().map(|s| s.uri.name().to_string()).collect();
+        names.sort();
+        assert_eq!(names, ["op", "set", "square", "unit"]);
+        assert!(induced.iter().all(|s| s.uri.module() == &*INDUCED));
+    }
+
+    #[test]
+    fn unresolved_domain() {
+        let mut m = morphism(Vec::new(), Vec::new());
+        m.domain = MaybeResolved::unresolved(DOMAIN.clone());
+        assert!(m.induced_symbols().is_empty());
+    }
+
+    #[test]
+    fn renaming() {
+        let induced = morphism(vec![Assignment {
+            original: sym(&BASE, "set"),
+            definiens: None,
+            new_name: Some("carrier".parse().expect("is valid")),
+            macroname: Some("carrier".into()),
+        }], Vec::new()).induced_symbols();
+        assert!(!induced.iter().any(|s| s.uri == sym(&INDUCED, "set")));
+        let carrier = find(&induced, "carrier");
+        assert_eq!(carrier.uri, sym(&INDUCED, "carrier"));
+        assert_eq!(carrier.macroname.as_deref(), Some("carrier"));
+        // references to the renamed symbol are translated as well
+        assert_eq!(find(&induced, "op").tp, Some(oms!(sym(&INDUCED, "carrier"))));
+        assert_eq!(find(&induced, "op").macroname, None);
+    }
+
+    #[test]
+    fn translation() {
+        let induced = morphism(Vec::new(), Vec::new()).induced_symbols();
+        let square = find(&induced, "square");
+        assert_eq!(square.tp, Some(oms!(sym(&INDUCED, "set"))));
+        assert_eq!(
+            square.df,
+            Some(oma!(oms!(sym(&INDUCED, "op")), [{N:oms!(sym(&INDUCED, "unit"))}]))
+        );
+        // symbols accessed through an assigned morphism are translated along the assignment
+        assert_eq!(find(&induced, "unit").df, Some(oms!(sym(&TO.clone().into_module(), "zero"))));
+    }
+
+    #[test]
+    fn definiens_precedence() {
+        let df = oms!(sym(&CODOMAIN, "one"));
+        let induced = morphism(vec![Assignment {
+            original: sym(&RIGHT, "unit"),
+            definiens: Some(df.clone()),
+            new_name: None,
+            macroname: None,
+        }], Vec::new()).induced_symbols();
+        let unit = find(&induced, "unit");
+        assert_eq!(unit.df, Some(df));
+        assert_eq!(unit.tp, Some(oms!(sym(&INDUCED, "set"))));
+    }
+
+    #[test]
+    fn declared_elements_skipped() {
+        let induced = morphism(Vec::new(), vec![
+            symbol(sym(&INDUCED, "op"), None, None),
+        ]).induced_symbols();
+        assert_eq!(induced.len(), 3);
+        assert!(!induced.iter().any(|s| s.uri == sym(&INDUCED, "op")));
+    }
+
+    #[test]
+    fn add_induced_symbols_idempotent() {
+        let mut m = morphism(Vec::new(), Vec::new());
+        m.add_induced_symbols();
+        assert_eq!(m.elements.len(), 4);
+        assert!(m.induced_symbols().is_empty());
+        m.add_induced_symbols();
+        assert_eq!(m.elements.len(), 4);
+    }
 }
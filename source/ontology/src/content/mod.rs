@@ -81,7 +81,7 @@ impl Resolvable for ModuleLike {
             Self::NestedModule(m) => Cow::Owned(m.as_ref().uri.clone().into_module()),
             Self::Structure(s) => Cow::Owned(s.as_ref().uri.clone().into_module()),
             Self::Extension(e) => Cow::Owned(e.as_ref().uri.clone().into_module()),
-            Self::Morphism(_) => todo!()//Cow::Owned(m.0.as_ref().uri.into_module()),
+            Self::Morphism(m) => Cow::Owned(m.as_ref().uri.clone().into_module()),
         }
     }
 }
@@ -140,9 +140,7 @@ pub trait ModuleTrait {
                         }
                         curr = m.declarations().iter();
                     }
-                    Declaration::Morphism(m)
-                        if m.uri.as_ref().map(|u| u.name().last_name()) == Some(step) =>
-                    {
+                    Declaration::Morphism(m) if m.uri.name().last_name() == step => {
                         if steps.is_empty() {
                             return T::from_declaration(c);
                         }
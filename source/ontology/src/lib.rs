@@ -25,6 +25,7 @@ pub mod content;
 pub mod languages;
 pub mod narration;
 pub mod file_states;
+pub mod search;
 #[cfg(feature = "rdf")]
 pub mod rdf;
 pub mod uris;
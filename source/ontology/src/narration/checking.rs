@@ -7,6 +7,7 @@ use crate::{
 use super::{
     exercises::{CognitiveDimension, Exercise, GradingNote, Solutions},
     paragraphs::{LogicalParagraph, ParagraphKind},
+    proofs::ProofStructure,
     sections::{Section, SectionLevel},
     DocumentElement, LazyDocRef
 };
@@ -48,6 +49,7 @@ enum Elem {
         range: DocumentRange,
         styles: Box<[Box<str>]>,
         fors: VecMap<SymbolURI, Option<Term>>,
+        proof: Option<ProofStructure>,
     },
     Exercise {
         sub_exercise: bool,
@@ -109,6 +111,7 @@ impl Elem {
                 fors,
                 range,
                 styles,
+                proof,
             } => DocumentElement::Paragraph(LogicalParagraph {
                 kind,
                 uri,
@@ -117,6 +120,7 @@ impl Elem {
                 range,
                 styles,
                 fors,
+                proof,
                 children: v.into_boxed_slice(),
             }),
             Self::Exercise {
@@ -329,6 +333,7 @@ impl<Check: DocumentChecker> DocumentCheckIter<'_, Check> {
                 fors,
                 range,
                 styles,
+                proof,
                 children,
             }) => {
                 let old_in = std::mem::replace(&mut self.curr_in, children.into_iter());
@@ -342,6 +347,7 @@ impl<Check: DocumentChecker> DocumentCheckIter<'_, Check> {
                         fors,
                         range,
                         styles,
+                        proof,
                     },
                     old_in,
                     old_out,
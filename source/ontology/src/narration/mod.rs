@@ -5,6 +5,7 @@ pub mod documents;
 pub mod exercises;
 pub mod notations;
 pub mod paragraphs;
+pub mod proofs;
 pub mod sections;
 pub mod variables;
 
@@ -6,7 +6,7 @@ use crate::{
     content::terms::Term, shtml::SHTMLKey, uris::{DocumentElementURI, SymbolURI}, Checked, CheckingState, DocumentRange
 };
 
-use super::{DocumentElement, NarrationTrait};
+use super::{proofs::ProofStructure, DocumentElement, NarrationTrait};
 
 #[derive(Debug)]
 pub struct LogicalParagraph<State:CheckingState> {
@@ -18,10 +18,12 @@ pub struct LogicalParagraph<State:CheckingState> {
     pub styles: Box<[Box<str>]>,
     pub children: State::Seq<DocumentElement<State>>,
     pub fors: VecMap<SymbolURI, Option<Term>>,
+    /// only for [`Proof`](ParagraphKind::Proof)s and [`SubProof`](ParagraphKind::SubProof)s
+    pub proof: Option<ProofStructure>,
 }
 
 crate::serde_impl!{
-    struct LogicalParagraph[kind,uri,inline,title,range,styles,children,fors]
+    struct LogicalParagraph[kind,uri,inline,title,range,styles,children,fors,proof]
 }
 
 impl NarrationTrait for LogicalParagraph<Checked> {
@@ -0,0 +1,121 @@
+use crate::{
+    content::terms::Term, uris::{DocumentElementURI, SymbolURI}, DocumentRange
+};
+
+/// The structure of a [`Proof`](super::paragraphs::ParagraphKind::Proof) or
+/// [`SubProof`](super::paragraphs::ParagraphKind::SubProof) paragraph
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofStructure {
+    pub method: Option<Box<str>>,
+    /// whether the proof body should initially be collapsed
+    pub hide: bool,
+    pub sketch: Option<DocumentRange>,
+    pub term: Option<Term>,
+    pub body: Option<DocumentRange>,
+    pub premises: Box<[ProofPremise]>,
+    pub conclusion: Option<DocumentRange>,
+    pub steps: Box<[ProofStep]>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofStep {
+    pub uri: DocumentElementURI,
+    pub kind: ProofStepKind,
+    pub range: DocumentRange,
+    pub name: Option<DocumentRange>,
+    pub premises: Box<[ProofPremise]>,
+    pub conclusion: Option<DocumentRange>,
+    /// the subproofs justifying this step; these are [`Paragraph`](super::DocumentElement::Paragraph)
+    /// children of the proof
+    pub subproofs: Box<[DocumentElementURI]>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofPremise {
+    pub range: DocumentRange,
+    /// the statement used, if given
+    pub uri: Option<SymbolURI>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProofStepKind {
+    Step,
+    EqStep,
+    Assumption,
+}
+
+impl ProofStepKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Step => "step",
+            Self::EqStep => "eqstep",
+            Self::Assumption => "assumption",
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::{ProofPremise, ProofStep, ProofStepKind, ProofStructure};
+    use crate::uris::{ArchiveURI, BaseURI, DocumentURI};
+    use crate::{oms, DocumentRange};
+
+    #[test]
+    fn serde_roundtrip() {
+        let archive: ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        let doc = DocumentURI::from_archive_relpath(archive.clone(), "proofs.en.tex");
+        let lemma = ((archive | "some/module").expect("is valid") | "lemma").expect("is valid");
+        let uri = |s: &str| (doc.clone() & s).expect("is valid");
+        let range = |start, end| DocumentRange { start, end };
+        let proof = ProofStructure {
+            method: Some("induction".into()),
+            hide: true,
+            sketch: Some(range(0, 5)),
+            term: Some(oms!(lemma.clone())),
+            body: Some(range(5, 50)),
+            premises: Box::new([ProofPremise { range: range(6, 10), uri: None }]),
+            conclusion: Some(range(45, 50)),
+            steps: Box::new([
+                ProofStep {
+                    uri: uri("step"),
+                    kind: ProofStepKind::Step,
+                    range: range(10, 30),
+                    name: Some(range(10, 12)),
+                    premises: Box::new([ProofPremise { range: range(12, 15), uri: Some(lemma.clone()) }]),
+                    conclusion: Some(range(20, 25)),
+                    subproofs: Box::new([uri("subproof")]),
+                },
+                ProofStep {
+                    uri: uri("eqstep"),
+                    kind: ProofStepKind::EqStep,
+                    range: range(30, 40),
+                    name: None,
+                    premises: Box::default(),
+                    conclusion: None,
+                    subproofs: Box::default(),
+                },
+            ]),
+        };
+        let json = serde_json::to_string(&proof).expect("is serializable");
+        let decoded: ProofStructure = serde_json::from_str(&json).expect("is deserializable");
+        assert_eq!(serde_json::to_string(&decoded).expect("is serializable"), json);
+
+        assert_eq!(decoded.method.as_deref(), Some("induction"));
+        assert!(decoded.hide);
+        assert_eq!(decoded.term, Some(oms!(lemma.clone())));
+        assert_eq!(decoded.premises[0].uri, None);
+        let [step, eqstep] = &*decoded.steps else { panic!("expected two steps") };
+        assert_eq!(step.uri, uri("step"));
+        assert_eq!(step.kind, ProofStepKind::Step);
+        assert_eq!(step.name, Some(range(10, 12)));
+        assert_eq!(step.premises[0].uri, Some(lemma));
+        assert_eq!(&*step.subproofs, &[uri("subproof")]);
+        assert_eq!(eqstep.kind, ProofStepKind::EqStep);
+        assert!(eqstep.premises.is_empty() && eqstep.subproofs.is_empty());
+    }
+}
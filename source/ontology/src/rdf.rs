@@ -416,6 +416,7 @@ pub mod ontologies {
         CLASS PROOF = "proof" <: PARA @ "A logical paragraph that serves as a justification of a proposition.";
         CLASS SUBPROOF = "subproof" <: PARA @ "A logical paragraph that serves as a justification of an\
          intermediate proposition within a proof.";
+        CLASS PROOF_STEP = "proof-step" <: PARA @ "A single step in a structured proof or subproof.";
         CLASS PROPOSITION = "proposition" <: PARA @ "A statement of a mathematical object or some relation between some." ;
         CLASS PROBLEM = "problem" <: PARA @ "A logical paragraph posing a problem/question/exercise for the reader.";
         CLASS SUBPROBLEM = "subproblem" <: PARA @ "A logical paragraph posing a subexercise in some problem/question/exercise for the reader.";
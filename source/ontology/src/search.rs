@@ -0,0 +1,31 @@
+/// The kinds of items in the search index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SearchKind {
+    Symbol,
+    Paragraph,
+    Document,
+}
+impl SearchKind {
+    pub const ALL: [Self; 3] = [Self::Symbol, Self::Paragraph, Self::Document];
+
+    #[must_use]
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::Symbol => "symbol",
+            Self::Paragraph => "paragraph",
+            Self::Document => "document",
+        }
+    }
+
+    #[must_use]
+    pub fn from_key(s: &str) -> Option<Self> {
+        match s {
+            "symbol" => Some(Self::Symbol),
+            "paragraph" => Some(Self::Paragraph),
+            "document" => Some(Self::Document),
+            _ => None,
+        }
+    }
+}
@@ -17,6 +17,8 @@ pub enum SHTMLError {
     NotInNarrative,
     NotInParagraph,
     NotInExercise(&'static str),
+    NotInProof,
+    NotInMorphism,
     InvalidKey,
     InvalidURI(String),
     IncompleteArgs
@@ -41,6 +43,8 @@ impl Display for SHTMLError {
             Self::NotInNarrative => f.write_str("unbalanced narrative element"),
             Self::NotInParagraph => f.write_str("unbalanced logical paragraph"),
             Self::NotInExercise(s) => write!(f,"unbalanced exercise element: {s}"),
+            Self::NotInProof => f.write_str("proof element outside of a proof"),
+            Self::NotInMorphism => f.write_str("assignment outside of a morphism"),
             Self::InvalidKey => f.write_str("invalid key in shtml element"),
             Self::IncompleteArgs => f.write_str("incomplete argument list"),
             Self::InvalidURI(s) => write!(f,"invalid URI: {s}"),
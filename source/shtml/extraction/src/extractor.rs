@@ -1,12 +1,14 @@
 #![allow(clippy::result_large_err)]
 
 use std::borrow::Cow;
+use immt_ontology::content::declarations::morphisms::{Assignment, MorphismAssignment};
 use immt_ontology::content::declarations::OpenDeclaration;
 use immt_ontology::content::modules::OpenModule;
 use immt_ontology::content::terms::{Arg, ArgMode, Term, Var};
 use immt_ontology::languages::Language;
 use immt_ontology::narration::exercises::{AnswerClass, AnswerKind, Choice, CognitiveDimension, FillInSol, FillInSolOption, GradingNote, SolutionData};
 use immt_ontology::narration::notations::{NotationComponent, OpNotation};
+use immt_ontology::narration::proofs::{ProofPremise, ProofStep, ProofStepKind, ProofStructure};
 use immt_ontology::narration::sections::SectionLevel;
 use immt_ontology::narration::variables::Variable;
 use immt_ontology::narration::{DocumentElement, LazyDocRef};
@@ -47,7 +49,111 @@ pub struct ParagraphState {
     pub uri:DocumentElementURI,
     pub children:Vec<DocumentElement<Unchecked>>,
     pub fors:VecMap<SymbolURI,Option<Term>>,
-    pub title:Option<DocumentRange>
+    pub title:Option<DocumentRange>,
+    pub proof:Option<ProofState>
+}
+
+#[derive(Debug)]
+pub struct ProofState {
+    pub method:Option<Box<str>>,
+    pub hide:bool,
+    pub sketch:Option<DocumentRange>,
+    pub term:Option<Term>,
+    pub body:Option<DocumentRange>,
+    pub premises:Vec<ProofPremise>,
+    pub conclusion:Option<DocumentRange>,
+    pub steps:Vec<ProofStep>,
+    pub open_steps:Vec<ProofStepState>
+}
+impl ProofState {
+    #[must_use]
+    pub const fn new(method:Option<Box<str>>,hide:bool) -> Self {
+        Self {
+            method,hide,sketch:None,term:None,body:None,
+            premises:Vec::new(),conclusion:None,
+            steps:Vec::new(),open_steps:Vec::new()
+        }
+    }
+    pub fn close_step(&mut self,range:DocumentRange) -> bool {
+        let Some(ProofStepState{uri,kind,name,premises,conclusion,subproofs}) = self.open_steps.pop() else {
+            return false
+        };
+        self.steps.push(ProofStep {
+            uri,kind,range,name,conclusion,
+            premises:premises.into_boxed_slice(),
+            subproofs:subproofs.into_boxed_slice()
+        });
+        true
+    }
+    /// adds a premise to the current step, or the proof itself if no step is open
+    pub fn add_premise(&mut self,premise:ProofPremise) {
+        if let Some(s) = self.open_steps.last_mut() {
+            s.premises.push(premise);
+        } else {
+            self.premises.push(premise);
+        }
+    }
+    /// sets the conclusion of the current step, or the proof itself if no step is open
+    pub fn set_conclusion(&mut self,range:DocumentRange) {
+        if let Some(s) = self.open_steps.last_mut() {
+            s.conclusion = Some(range);
+        } else {
+            self.conclusion = Some(range);
+        }
+    }
+    /// attaches a subproof to the current step; subproofs outside of a step are ordinary children
+    pub fn add_subproof(&mut self,uri:DocumentElementURI) {
+        if let Some(s) = self.open_steps.last_mut() {
+            s.subproofs.push(uri);
+        }
+    }
+    #[must_use]
+    pub fn close(self) -> ProofStructure {
+        ProofStructure {
+            method:self.method,hide:self.hide,sketch:self.sketch,
+            term:self.term,body:self.body,conclusion:self.conclusion,
+            premises:self.premises.into_boxed_slice(),
+            steps:self.steps.into_boxed_slice()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProofStepState {
+    pub uri:DocumentElementURI,
+    pub kind:ProofStepKind,
+    pub name:Option<DocumentRange>,
+    pub premises:Vec<ProofPremise>,
+    pub conclusion:Option<DocumentRange>,
+    pub subproofs:Vec<DocumentElementURI>
+}
+impl ProofStepState {
+    #[must_use]
+    pub const fn new(uri:DocumentElementURI,kind:ProofStepKind) -> Self {
+        Self { uri,kind,name:None,premises:Vec::new(),conclusion:None,subproofs:Vec::new() }
+    }
+}
+
+#[derive(Debug)]
+pub struct MorphismState {
+    pub uri:ModuleURI,
+    pub elements:Vec<OpenDeclaration<Unchecked>>,
+    pub assignments:Vec<Assignment>,
+    pub morphism_assignments:Vec<MorphismAssignment>
+}
+impl MorphismState {
+    #[must_use]
+    pub const fn new(uri:ModuleURI) -> Self {
+        Self { uri,elements:Vec::new(),assignments:Vec::new(),morphism_assignments:Vec::new() }
+    }
+    /// the assignment for `original`; assignments and renamings of the same symbol are merged
+    pub fn assignment(&mut self,original:SymbolURI) -> &mut Assignment {
+        if let Some(i) = self.assignments.iter().position(|a| a.original == original) {
+            return &mut self.assignments[i]
+        }
+        self.assignments.push(Assignment { original,definiens:None,new_name:None,macroname:None });
+        self.assignments.last_mut().unwrap_or_else(|| unreachable!())
+    }
 }
 
 #[derive(Clone,Debug)]
@@ -126,6 +232,7 @@ pub enum Narrative {
 #[allow(clippy::large_enum_variant)]
 pub enum Content {
     Container(ModuleURI,Vec<OpenDeclaration<Unchecked>>),
+    Morphism(MorphismState),
     SingleTerm(Option<Term>),
     Symdecl{
         tp:Option<Term>,
@@ -250,6 +357,9 @@ impl<E:StatefulExtractor> SHTMLExtractor for E {
     fn open_narrative(&mut self,uri:Option<NarrativeURI>) {
         self.state_mut().push_narr(uri);
     }
+    fn open_morphism(&mut self,uri:ModuleURI) {
+        self.state_mut().content.push(Content::Morphism(MorphismState::new(uri)));
+    }
     fn open_complex_term(&mut self) {
         self.state_mut().content.push(Content::SingleTerm(None));
     }
@@ -261,6 +371,23 @@ impl<E:StatefulExtractor> SHTMLExtractor for E {
         }
         None
     }
+    fn close_morphism(&mut self) -> Option<MorphismState> {
+        match self.state_mut().content.pop() {
+            Some(Content::Morphism(state)) => return Some(state),
+            Some(o) => self.state_mut().content.push(o),
+            None => {}
+        }
+        None
+    }
+    fn with_morphism<R>(&mut self,then:impl FnOnce(&mut MorphismState) -> R) -> Option<R> {
+        let state = self.state_mut();
+        for c in state.content.iter_mut().rev() {
+            if let Content::Morphism(m) = c {
+                return Some(then(m));
+            }
+        }
+        None
+    }
     fn close_narrative(&mut self) -> Option<(NarrativeURI,Vec<DocumentElement<Unchecked>>)> {
         let state = self.state_mut();
         let r =state.narrative.pop().unwrap_or_else(|| unreachable!());
@@ -345,9 +472,25 @@ impl<E:StatefulExtractor> SHTMLExtractor for E {
     fn open_paragraph(&mut self,uri:DocumentElementURI,fors:VecSet<SymbolURI>) {
         let fors = fors.into_iter().map(|s| (s,None)).collect();
         self.state_mut().narrative.push(Narrative::Paragraph(ParagraphState {
-            uri, children:Vec::new(), fors, title: None 
+            uri, children:Vec::new(), fors, title: None, proof: None
         }));
     }
+    fn open_proof(&mut self,method:Option<Box<str>>,hide:bool) {
+        if let Some(Narrative::Paragraph(p)) = self.state_mut().narrative.last_mut() {
+            p.proof = Some(ProofState::new(method,hide));
+        } else {
+            self.add_error(SHTMLError::NotInParagraph);
+        }
+    }
+    fn with_proof<R>(&mut self,then:impl FnOnce(&mut ProofState) -> R) -> Option<R> {
+        let state = self.state_mut();
+        for e in state.narrative.iter_mut().rev() {
+            if let Narrative::Paragraph(ParagraphState{proof:Some(p),..}) = e {
+                return Some(then(p));
+            }
+        }
+        None
+    }
     fn close_paragraph(&mut self) -> Option<ParagraphState> {
         match self.state_mut().narrative.pop() {
             Some(Narrative::Paragraph(state)) => return Some(state),
@@ -514,7 +657,8 @@ impl<E:StatefulExtractor> SHTMLExtractor for E {
 
     fn get_content_uri(&self) -> Option<&ModuleURI> {
         self.state().content.iter().rev().find_map(|t| match t {
-            Content::Container(uri,_) => Some(uri),
+            Content::Container(uri,_) |
+            Content::Morphism(MorphismState{uri,..}) => Some(uri),
             _ => None
         })
     }
@@ -568,7 +712,7 @@ impl<E:StatefulExtractor> SHTMLExtractor for E {
     /// ### Errors
     fn add_content_element(&mut self,elem:OpenDeclaration<Unchecked>) -> Result<(),OpenDeclaration<Unchecked>> {
         for cont in self.state_mut().content.iter_mut().rev() {
-            if let Content::Container(_,c) = cont {
+            if let Content::Container(_,c) | Content::Morphism(MorphismState{elements:c,..}) = cont {
                 c.push(elem); return Ok(())
             }
         }
@@ -696,14 +840,19 @@ pub trait SHTMLExtractor {
 
     fn open_content(&mut self,uri:ModuleURI);
     fn open_narrative(&mut self,uri:Option<NarrativeURI>);
+    fn open_morphism(&mut self,uri:ModuleURI);
     fn open_complex_term(&mut self);
     fn close_content(&mut self) -> Option<(ModuleURI,Vec<OpenDeclaration<Unchecked>>)>;
+    fn close_morphism(&mut self) -> Option<MorphismState>;
+    fn with_morphism<R>(&mut self,then:impl FnOnce(&mut MorphismState) -> R) -> Option<R>;
     fn close_narrative(&mut self) -> Option<(NarrativeURI,Vec<DocumentElement<Unchecked>>)>;
     fn close_complex_term(&mut self) -> Option<Term>;
     fn open_section(&mut self,uri:DocumentElementURI);
     fn close_section(&mut self) -> Option<(DocumentElementURI,Option<DocumentRange>,Vec<DocumentElement<Unchecked>>)>;
     fn open_paragraph(&mut self,uri:DocumentElementURI,fors:VecSet<SymbolURI>);
     fn close_paragraph(&mut self) -> Option<ParagraphState>;
+    fn open_proof(&mut self,method:Option<Box<str>>,hide:bool);
+    fn with_proof<R>(&mut self,then:impl FnOnce(&mut ProofState) -> R) -> Option<R>;
     fn open_exercise(&mut self,uri:DocumentElementURI);
     fn close_exercise(&mut self) -> Option<ExerciseState>;
     fn open_gnote(&mut self);
@@ -920,3 +1069,46 @@ pub trait Attributes {
             .unwrap_or_default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use immt_ontology::{narration::proofs::{ProofPremise, ProofStepKind}, uris::{ArchiveURI, BaseURI, DocumentURI, SymbolURI}, DocumentRange};
+    use super::{ProofState, ProofStepState};
+
+    const fn range(start:usize,end:usize) -> DocumentRange { DocumentRange { start, end } }
+
+    #[test]
+    fn proof_with_steps() {
+        let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        let doc = DocumentURI::from_archive_relpath(archive.clone(), "proofs.en.tex");
+        let lemma : SymbolURI = ((archive | "some/module").expect("is valid") | "lemma").expect("is valid");
+        let uri = |s:&str| (doc.clone() & s).expect("is valid");
+
+        let mut proof = ProofState::new(Some("induction".into()),false);
+        proof.add_premise(ProofPremise { range:range(0,5), uri:None });
+        proof.open_steps.push(ProofStepState::new(uri("step"),ProofStepKind::Step));
+        proof.add_premise(ProofPremise { range:range(10,15), uri:Some(lemma.clone()) });
+        proof.add_subproof(uri("subproof"));
+        proof.set_conclusion(range(20,25));
+        assert!(proof.close_step(range(10,30)));
+        proof.open_steps.push(ProofStepState::new(uri("eqstep"),ProofStepKind::EqStep));
+        assert!(proof.close_step(range(30,40)));
+        assert!(!proof.close_step(range(40,50)));
+        proof.set_conclusion(range(40,50));
+        // not in a step
+        proof.add_subproof(uri("other"));
+
+        let proof = proof.close();
+        assert_eq!(proof.method.as_deref(),Some("induction"));
+        assert_eq!(proof.premises.len(),1);
+        assert_eq!(proof.conclusion,Some(range(40,50)));
+        let [step,eqstep] = &*proof.steps else { panic!("expected two steps") };
+        assert_eq!(step.kind,ProofStepKind::Step);
+        assert_eq!(step.range,range(10,30));
+        assert_eq!(step.premises.iter().map(|p| p.uri.clone()).collect::<Vec<_>>(),vec![Some(lemma)]);
+        assert_eq!(step.conclusion,Some(range(20,25)));
+        assert_eq!(&*step.subproofs,&[uri("subproof")]);
+        assert_eq!(eqstep.kind,ProofStepKind::EqStep);
+        assert!(eqstep.premises.is_empty() && eqstep.subproofs.is_empty() && eqstep.conclusion.is_none());
+    }
+}
@@ -6,6 +6,8 @@ mod rules;
 mod tags;
 pub mod errors;
 pub mod open;
+#[cfg(all(test,feature="full"))]
+mod tests;
 
 pub mod prelude {
     pub use crate::rules::{SHTMLElements,SHTMLExtractionRule,RuleSet};
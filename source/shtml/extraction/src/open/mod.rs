@@ -1,14 +1,14 @@
 use std::borrow::Cow;
 
 use either::Either;
-use immt_ontology::{content::{declarations::{morphisms::Morphism, structures::{Extension, MathStructure}, symbols::{ArgSpec, AssocType, Symbol}, OpenDeclaration}, modules::{NestedModule, OpenModule}, terms::{Term, Var}}, languages::Language, narration::{exercises::{ChoiceBlock, Exercise, FillInSol, FillInSolOption, GradingNote, SolutionData, Solutions}, notations::Notation, paragraphs::{LogicalParagraph, ParagraphKind}, sections::{Section, SectionLevel}, variables::Variable, DocumentElement}, uris::{ContentURI, DocumentElementURI, DocumentURI, ModuleURI, SymbolURI, URIOrRefTrait}};
+use immt_ontology::{content::{declarations::{morphisms::Morphism, structures::{Extension, MathStructure}, symbols::{ArgSpec, AssocType, Symbol}, OpenDeclaration}, modules::{NestedModule, OpenModule}, terms::{Term, Var}}, languages::Language, narration::{exercises::{ChoiceBlock, Exercise, FillInSol, FillInSolOption, GradingNote, SolutionData, Solutions}, notations::Notation, paragraphs::{LogicalParagraph, ParagraphKind}, proofs::ProofPremise, sections::{Section, SectionLevel}, variables::Variable, DocumentElement}, uris::{ContentURI, DocumentElementURI, DocumentURI, ModuleURI, SymbolURI, URIOrRefTrait}};
 use smallvec::SmallVec;
 use terms::{OpenArg, PreVar, VarOrSym};
 
 #[cfg(feature="rdf")]
 use immt_ontology::triple;
 
-use crate::{errors::SHTMLError, prelude::{ExerciseState, NotationState, ParagraphState, SHTMLExtractor, SHTMLNode}, rules::SHTMLElements};
+use crate::{errors::SHTMLError, prelude::{ExerciseState, MorphismState, NotationState, ParagraphState, ProofState, SHTMLExtractor, SHTMLNode}, rules::SHTMLElements};
 
 pub mod terms;
 #[allow(clippy::large_enum_variant)]
@@ -28,7 +28,7 @@ pub enum OpenSHTMLElement {
         macroname: Option<Box<str>>,
     },
     Morphism {
-        uri: Option<SymbolURI>,
+        uri: SymbolURI,
         domain: ModuleURI,
         total: bool
     },
@@ -43,6 +43,13 @@ pub enum OpenSHTMLElement {
         inline: bool,
         styles: Box<[Box<str>]>,
     },
+    Proof {
+        uri:DocumentElementURI,
+        kind: ParagraphKind,
+        inline: bool,
+        styles: Box<[Box<str>]>,
+        hide: bool,
+    },
     Exercise {
         uri:DocumentElementURI,
         styles: Box<[Box<str>]>,
@@ -100,6 +107,13 @@ pub enum OpenSHTMLElement {
     ProblemChoiceFeedback,
     Fillinsol(Option<f32>),
     FillinsolCase,
+    ProofSketch,
+    ProofTerm,
+    ProofBody,
+    ProofStep,
+    ProofStepName,
+    ProofPremise(Option<SymbolURI>),
+    ProofConclusion,
 
 
     Inputref{uri:DocumentURI,id:Box<str>},
@@ -133,15 +147,16 @@ impl OpenSHTMLElement {
             Self::MathStructure { uri,macroname} => Self::close_structure(extractor, node, uri, macroname),
             Self::Morphism { uri,domain,total } => Self::close_morphism(extractor, node, uri, domain, total),
 
-            Self::Assign(_sym) => {
-                if let Some(tm) = extractor.close_complex_term() {
-
+            Self::Assign(sym) => {
+                let tm = extractor.close_complex_term();
+                if extractor.with_morphism(|m| m.assignment(sym).definiens = tm).is_none() {
+                    extractor.add_error(SHTMLError::NotInMorphism);
                 }
-                // TODO
             }
 
             Self::Section { lvl,  uri } => Self::close_section(extractor, node, lvl, uri),
-            Self::Paragraph { kind, inline, styles, uri } => Self::close_paragraph(extractor, node, kind, inline, styles, uri),
+            Self::Paragraph { kind, inline, styles, uri } |
+            Self::Proof { kind, inline, styles, uri, .. } => Self::close_paragraph(extractor, node, kind, inline, styles, uri),
             Self::Exercise { uri, styles, autogradable, points, sub_exercise } => Self::close_exercise(extractor, node, uri, styles, autogradable, points, sub_exercise),
 
             Self::Doctitle => {
@@ -285,6 +300,54 @@ impl OpenSHTMLElement {
                 ).is_none() {
                     extractor.add_error(SHTMLError::NotInExercise("a"));
                 }
+            Self::ProofSketch => {
+                let range = node.inner_range();
+                if extractor.with_proof(|p| p.sketch = Some(range)).is_none() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
+            Self::ProofTerm => {
+                extractor.set_in_term(false);
+                let tm = Self::as_term(next,node);
+                if extractor.with_proof(|p| p.term = Some(tm)).is_none() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
+            Self::ProofBody => {
+                let range = node.inner_range();
+                if extractor.with_proof(|p| p.body = Some(range)).is_none() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
+            Self::ProofStep => {
+                let range = node.range();
+                if !extractor.with_proof(|p| p.close_step(range)).unwrap_or_default() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
+            Self::ProofStepName => {
+                let range = node.inner_range();
+                if !extractor.with_proof(|p|
+                    if let Some(s) = p.open_steps.last_mut() {
+                        s.name = Some(range);
+                        true
+                    } else { false }
+                ).unwrap_or_default() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
+            Self::ProofPremise(uri) => {
+                let range = node.inner_range();
+                if extractor.with_proof(|p| p.add_premise(ProofPremise { range, uri })).is_none() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
+            Self::ProofConclusion => {
+                let range = node.inner_range();
+                if extractor.with_proof(|p| p.set_conclusion(range)).is_none() {
+                    extractor.add_error(SHTMLError::NotInProof);
+                }
+            }
             Self::ExerciseSolution(id) => {
                 let s = node.inner_string().into_boxed_str();
                 node.delete_children();
@@ -550,12 +613,12 @@ impl OpenSHTMLElement {
         }
     }
 
-    fn close_morphism<E:SHTMLExtractor,N:SHTMLNode>(extractor:&mut E,node:&N,uri:Option<SymbolURI>,domain:ModuleURI,total:bool) {
+    fn close_morphism<E:SHTMLExtractor,N:SHTMLNode>(extractor:&mut E,node:&N,uri:SymbolURI,domain:ModuleURI,total:bool) {
         let Some((_,narrative)) = extractor.close_narrative() else {
             extractor.add_error(SHTMLError::NotInNarrative);
             return
         };
-        let Some((_,content)) = extractor.close_content() else {
+        let Some(MorphismState{elements,assignments,morphism_assignments,..}) = extractor.close_morphism() else {
             extractor.add_error(SHTMLError::NotInContent);
             return
         };
@@ -563,7 +626,7 @@ impl OpenSHTMLElement {
         #[cfg(feature="rdf")]
         if E::RDF {
             if let Some(cont) = extractor.get_content_iri() {
-                let iri = uri.as_ref().expect("TODO").to_iri(); // TODO
+                let iri = uri.to_iri();
                 extractor.add_triples([
                     triple!(<(iri.clone())> : ulo:MORPHISM),
                     triple!(<(iri.clone())> rdfs:DOMAIN <(domain.to_iri())>),
@@ -573,10 +636,10 @@ impl OpenSHTMLElement {
         }
         
         extractor.add_document_element(DocumentElement::Morphism { 
-            range: node.range(), morphism: uri.clone().expect("TODO") /* TODO */, children: narrative
+            range: node.range(), morphism: uri.clone(), children: narrative
         });
         if extractor.add_content_element(OpenDeclaration::Morphism(Morphism {
-            uri,domain,total,elements:content
+            uri,domain,total,assignments,morphism_assignments,elements
         })).is_err() {
             extractor.add_error(SHTMLError::NotInContent);
         }
@@ -599,10 +662,15 @@ impl OpenSHTMLElement {
     }
 
     fn close_paragraph<E:SHTMLExtractor,N:SHTMLNode>(extractor:&mut E,node:&N,kind:ParagraphKind,inline:bool,styles:Box<[Box<str>]>,uri:DocumentElementURI) {
-        let Some(ParagraphState{children,fors,title,..}) = extractor.close_paragraph() else {
+        let Some(ParagraphState{children,fors,title,proof,..}) = extractor.close_paragraph() else {
             extractor.add_error(SHTMLError::NotInParagraph);
             return
         };
+        let proof = proof.map(ProofState::close);
+        if kind == ParagraphKind::SubProof {
+            // a subproof justifies the current step of the surrounding proof
+            let _ = extractor.with_proof(|p| p.add_subproof(uri.clone()));
+        }
 
         #[cfg(feature="rdf")]
         if E::RDF {
@@ -622,6 +690,36 @@ impl OpenSHTMLElement {
                     ]);
 
                 }
+            } else if matches!(kind,ParagraphKind::Proof|ParagraphKind::SubProof) {
+                for (f,_) in fors.iter() {
+                    extractor.add_triples([
+                        triple!(<(iri.clone())> ulo:JUSTIFIES <(f.to_iri())>)
+                    ]);
+                }
+            }
+            if let Some(proof) = &proof {
+                for p in proof.premises.iter().filter_map(|p| p.uri.as_ref()) {
+                    extractor.add_triples([
+                        triple!(<(iri.clone())> ulo:USES <(p.to_iri())>)
+                    ]);
+                }
+                for step in &proof.steps {
+                    let s = step.uri.to_iri();
+                    extractor.add_triples([
+                        triple!(<(s.clone())> : ulo:PROOF_STEP),
+                        triple!(<(iri.clone())> ulo:CONTAINS <(s.clone())>)
+                    ]);
+                    for p in step.premises.iter().filter_map(|p| p.uri.as_ref()) {
+                        extractor.add_triples([
+                            triple!(<(s.clone())> ulo:USES <(p.to_iri())>)
+                        ]);
+                    }
+                    for sp in &step.subproofs {
+                        extractor.add_triples([
+                            triple!(<(sp.to_iri())> ulo:JUSTIFIES <(s.clone())>)
+                        ]);
+                    }
+                }
             }
             extractor.add_triples([
                 triple!(<(iri.clone())> : <(kind.rdf_type().into_owned())>),
@@ -632,7 +730,7 @@ impl OpenSHTMLElement {
         extractor.add_document_element(DocumentElement::Paragraph(
             LogicalParagraph {
                 range: node.range(),kind,inline,styles,
-                fors,uri,children,title
+                fors,uri,children,title,proof
             }
         ));
     }
@@ -788,4 +886,56 @@ impl OpenSHTMLElement {
         }
     }
 
-}
\ No newline at end of file
+}
+#[cfg(all(test,feature="full",feature="rdf"))]
+mod tests {
+    use immt_ontology::narration::{paragraphs::ParagraphKind, proofs::{ProofPremise, ProofStepKind}};
+    use immt_ontology::uris::{ArchiveURI, BaseURI, DocumentURI, SymbolURI, URIOrRefTrait};
+    use immt_ontology::{triple, DocumentRange};
+    use immt_utils::vecmap::VecSet;
+    use crate::extractor::{ProofStepState, SHTMLExtractor};
+    use crate::tests::{Extractor, Node};
+    use super::OpenSHTMLElement;
+
+    #[test]
+    fn proof_triples() {
+        let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        let doc = DocumentURI::from_archive_relpath(archive.clone(), "proofs.en.tex");
+        let module = (archive | "some/module").expect("is valid");
+        let (lemma,axiom) : (SymbolURI,SymbolURI) = (
+            (module.clone() | "lemma").expect("is valid"),
+            (module | "axiom").expect("is valid")
+        );
+        let uri = |s:&str| (doc.clone() & s).expect("is valid");
+        let (proof,step,subproof) = (uri("proof"),uri("step"),uri("subproof"));
+        let range = DocumentRange { start:0, end:0 };
+
+        let mut extractor = Extractor::new(doc.clone());
+        extractor.open_paragraph(proof.clone(),VecSet::new());
+        extractor.open_proof(None,false);
+        extractor.with_proof(|p| {
+            p.add_premise(ProofPremise { range, uri:Some(lemma.clone()) });
+            p.open_steps.push(ProofStepState::new(step.clone(),ProofStepKind::Step));
+            p.add_premise(ProofPremise { range, uri:Some(axiom.clone()) });
+        });
+        extractor.open_paragraph(subproof.clone(),VecSet::new());
+        extractor.open_proof(None,false);
+        OpenSHTMLElement::close_paragraph(&mut extractor,&Node,ParagraphKind::SubProof,false,Box::default(),subproof.clone());
+        assert_eq!(extractor.with_proof(|p| p.close_step(range)),Some(true));
+        OpenSHTMLElement::close_paragraph(&mut extractor,&Node,ParagraphKind::Proof,false,Box::default(),proof.clone());
+        assert!(extractor.errors.is_empty());
+
+        let (proof,step,subproof) = (proof.to_iri(),step.to_iri(),subproof.to_iri());
+        for t in [
+            triple!(<(step.clone())> : ulo:PROOF_STEP),
+            triple!(<(proof.clone())> ulo:CONTAINS <(step.clone())>),
+            triple!(<(proof.clone())> ulo:USES <(lemma.to_iri())>),
+            triple!(<(step.clone())> ulo:USES <(axiom.to_iri())>),
+            triple!(<(subproof.clone())> ulo:JUSTIFIES <(step.clone())>)
+        ] {
+            assert_eq!(extractor.triples.iter().filter(|e| **e == t).count(),1,"{t} expected once in {:?}",extractor.triples);
+        }
+        // premises of the step are not premises of the proof
+        assert!(!extractor.triples.contains(&triple!(<(proof)> ulo:USES <(axiom.to_iri())>)));
+    }
+}
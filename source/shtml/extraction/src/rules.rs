@@ -132,16 +132,18 @@ impl<const L:usize,E:SHTMLExtractor> RuleSet<E> for [SHTMLExtractionRule<E>;L] {
 #[allow(clippy::needless_pass_by_value)]
 #[allow(clippy::unnecessary_wraps)]
 pub mod rules {
+    use immt_ontology::content::declarations::morphisms::MorphismAssignment;
     use immt_ontology::content::declarations::symbols::{ArgSpec, AssocType};
     use immt_ontology::narration::exercises::{AnswerClass, AnswerKind, Choice, FillInSolOption, SolutionData};
     use immt_ontology::narration::paragraphs::ParagraphKind;
+    use immt_ontology::narration::proofs::ProofStepKind;
     use immt_ontology::shtml::SHTMLKey;
     use immt_ontology::uris::{DocumentElementURI, ModuleURI, Name, SymbolURI};
     use immt_utils::vecmap::VecSet;
     use smallvec::SmallVec;
     use crate::errors::SHTMLError;
     use crate::open::OpenSHTMLElement;
-    use crate::prelude::{Attributes, SHTMLExtractor};
+    use crate::prelude::{Attributes, ProofStepState, SHTMLExtractor};
     use crate::rules::SHTMLExtractionRule;
     use crate::open::terms::{OpenArg, OpenTerm, OpenTermKind, PreVar, VarOrSym};
     use std::borrow::Cow;
@@ -242,11 +244,10 @@ pub mod rules {
             let uri = err!(extractor,attrs.take_new_symbol_uri(SHTMLKey::Morphism,extractor));
             let domain = err!(extractor,attrs.take_module_uri(SHTMLKey::MorphismDomain, extractor));
             let total = attrs.take_bool(SHTMLKey::MorphismTotal);
-            extractor.open_content(uri.clone().into_module());
+            extractor.open_morphism(uri.clone().into_module());
             extractor.open_narrative(None);
             Some(OpenSHTMLElement::Morphism {
-                uri:Some(uri), // TODO
-                domain,total,//content:Vec::new(),narrative:Vec::new()
+                uri,domain,total,//content:Vec::new(),narrative:Vec::new()
             })
         }
 
@@ -256,6 +257,29 @@ pub mod rules {
             Some(OpenSHTMLElement::Assign(symbol))
         }
 
+        pub fn rename<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            let symbol = err!(extractor,attrs.get_symbol_uri(SHTMLKey::Rename,extractor));
+            let new_name = opt!(extractor,attrs.get_typed(SHTMLKey::RenameTo,Name::from_str));
+            let macroname = attrs.get(SHTMLKey::Macroname).map(|s| Into::<String>::into(s).into_boxed_str());
+            if extractor.with_morphism(|m| {
+                let a = m.assignment(symbol);
+                if new_name.is_some() { a.new_name = new_name; }
+                if macroname.is_some() { a.macroname = macroname; }
+            }).is_none() {
+                extractor.add_error(SHTMLError::NotInMorphism);
+            }
+            None
+        }
+
+        pub fn assignmorphism<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            let from = err!(extractor,attrs.get_symbol_uri(SHTMLKey::AssignMorphismFrom,extractor));
+            let to = err!(extractor,attrs.get_symbol_uri(SHTMLKey::AssignMorphismTo,extractor));
+            if extractor.with_morphism(|m| m.morphism_assignments.push(MorphismAssignment { from,to })).is_none() {
+                extractor.add_error(SHTMLError::NotInMorphism);
+            }
+            None
+        }
+
         pub fn section<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
             let lvl = err!(extractor,attrs.get_section_level(SHTMLKey::Section));
             let id = attrs.get_id(extractor,Cow::Borrowed("section"));
@@ -283,10 +307,80 @@ pub mod rules {
             do_paragraph(extractor, attrs, nexts, ParagraphKind::Example)
         }
         pub fn proof<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
-            do_paragraph(extractor, attrs, nexts, ParagraphKind::Proof)
+            do_proof(extractor, attrs, nexts, ParagraphKind::Proof)
         }
         pub fn subproof<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
-            do_paragraph(extractor, attrs, nexts, ParagraphKind::SubProof)
+            do_proof(extractor, attrs, nexts, ParagraphKind::SubProof)
+        }
+
+        fn do_proof<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,nexts:&mut SV<E>,kind:ParagraphKind) -> Option<OpenSHTMLElement> {
+            // method and hide are properties of the proof itself
+            let hide = attrs.get_bool(SHTMLKey::ProofHide);
+            let method = attrs.get(SHTMLKey::ProofMethod)
+                .map(|s| s.as_ref().trim().to_string().into_boxed_str())
+                .filter(|s| !s.is_empty());
+            nexts.retain(|r| !matches!(r.tag,SHTMLKey::ProofHide|SHTMLKey::ProofMethod));
+            let Some(OpenSHTMLElement::Paragraph { uri, kind, inline, styles }) = do_paragraph(extractor, attrs, nexts, kind) else {
+                return None
+            };
+            extractor.open_proof(method, hide);
+            Some(OpenSHTMLElement::Proof { uri, kind, inline, styles, hide })
+        }
+
+        pub fn proofsketch<E:SHTMLExtractor>(_extractor:&mut E,_attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            Some(OpenSHTMLElement::ProofSketch)
+        }
+
+        pub fn proofterm<E:SHTMLExtractor>(extractor:&mut E,_attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            if extractor.in_term() {
+                extractor.add_error(SHTMLError::InvalidKey);
+                return None
+            }
+            extractor.set_in_term(true);
+            Some(OpenSHTMLElement::ProofTerm)
+        }
+
+        pub fn proofbody<E:SHTMLExtractor>(_extractor:&mut E,_attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            Some(OpenSHTMLElement::ProofBody)
+        }
+
+        pub fn proofstep<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            do_proofstep(extractor, attrs, nexts, ProofStepKind::Step)
+        }
+        pub fn proofeqstep<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            do_proofstep(extractor, attrs, nexts, ProofStepKind::EqStep)
+        }
+        pub fn proofassumption<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            do_proofstep(extractor, attrs, nexts, ProofStepKind::Assumption)
+        }
+
+        fn do_proofstep<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>,kind:ProofStepKind) -> Option<OpenSHTMLElement> {
+            let id = attrs.get_id(extractor,Cow::Borrowed(kind.as_str()));
+            let uri = match extractor.get_narrative_uri() & &*id {
+                Ok(uri) => uri,
+                Err(e) => {
+                    extractor.add_error(SHTMLError::InvalidURI(id.to_string()));
+                    return None
+                }
+            };
+            if extractor.with_proof(|p| p.open_steps.push(ProofStepState::new(uri, kind))).is_none() {
+                extractor.add_error(SHTMLError::NotInProof);
+                return None
+            }
+            Some(OpenSHTMLElement::ProofStep)
+        }
+
+        pub fn proofstepname<E:SHTMLExtractor>(_extractor:&mut E,_attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            Some(OpenSHTMLElement::ProofStepName)
+        }
+
+        pub fn proofpremise<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            let uri = opt!(extractor,attrs.get_symbol_uri(SHTMLKey::ProofPremise,extractor));
+            Some(OpenSHTMLElement::ProofPremise(uri))
+        }
+
+        pub fn proofconclusion<E:SHTMLExtractor>(_extractor:&mut E,_attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>) -> Option<OpenSHTMLElement> {
+            Some(OpenSHTMLElement::ProofConclusion)
         }
 
         fn do_paragraph<E:SHTMLExtractor>(extractor:&mut E,attrs:&mut E::Attr<'_>,_nexts:&mut SV<E>,kind:ParagraphKind) -> Option<OpenSHTMLElement> {
@@ -738,3 +832,91 @@ pub mod rules {
 
     //}
 }
+
+#[cfg(all(test,feature="full"))]
+mod tests {
+    use immt_ontology::content::terms::Term;
+    use immt_ontology::shtml::SHTMLKey;
+    use immt_ontology::uris::{ArchiveURI, BaseURI, DocumentURI, ModuleURI, SymbolURI};
+    use immt_ontology::oms;
+    use smallvec::SmallVec;
+    use crate::errors::SHTMLError;
+    use crate::extractor::{Content, MorphismState, SHTMLExtractor};
+    use crate::open::OpenSHTMLElement;
+    use crate::tests::{attrs, Extractor, Node};
+    use super::{rules, SHTMLElements};
+
+    fn setup() -> (Extractor,ModuleURI,ModuleURI) {
+        let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        let doc = DocumentURI::from_archive_relpath(archive.clone(), "morphisms.en.tex");
+        let domain = (archive.clone() | "Domain").expect("is valid");
+        let module = (archive | "Codomain").expect("is valid");
+        (Extractor::new(doc),domain,module)
+    }
+    fn sym(module:&ModuleURI,name:&str) -> SymbolURI {
+        (module.clone() | name).expect("is valid")
+    }
+
+    /// runs the [`rules::assign`] rule on `symbol`, and closes it with `definiens` as the assigned term
+    fn assign(extractor:&mut Extractor,symbol:&SymbolURI,definiens:Term) {
+        let mut attrs = attrs([(SHTMLKey::Assign,symbol.to_string())]);
+        let open = rules::assign(extractor,&mut attrs,&mut SmallVec::new());
+        let Some(open @ OpenSHTMLElement::Assign(_)) = open else { panic!("expected an assignment") };
+        let Some(Content::SingleTerm(t)) = extractor.state.content.last_mut() else { panic!("expected a term") };
+        *t = Some(definiens);
+        let mut previous = SHTMLElements { elems:SmallVec::new() };
+        let mut next = SHTMLElements { elems:SmallVec::new() };
+        assert!(open.close(&mut previous,&mut next,extractor,&Node).is_none());
+    }
+    fn rename(extractor:&mut Extractor,symbol:&SymbolURI,to:&str,macroname:Option<&str>) {
+        let mut attrs = attrs([(SHTMLKey::Rename,symbol.to_string()),(SHTMLKey::RenameTo,to.to_string())]);
+        if let Some(m) = macroname {
+            attrs.0.push((SHTMLKey::Macroname.attr_name(),m.to_string()));
+        }
+        assert!(rules::rename(extractor,&mut attrs,&mut SmallVec::new()).is_none());
+    }
+
+    #[test]
+    fn assign_and_rename() {
+        let (mut extractor,domain,module) = setup();
+        let (set,op) = (sym(&domain,"set"),sym(&domain,"op"));
+        let df = oms!(sym(&module,"naturals"));
+
+        extractor.open_morphism(sym(&module,"phi").into_module());
+        rename(&mut extractor,&set,"carrier",Some("carrier"));
+        assign(&mut extractor,&set,df.clone());
+        rename(&mut extractor,&op,"plus",None);
+        let mut attrs = attrs([
+            (SHTMLKey::AssignMorphismFrom,sym(&domain,"inner").to_string()),
+            (SHTMLKey::AssignMorphismTo,sym(&module,"outer").to_string())
+        ]);
+        assert!(rules::assignmorphism(&mut extractor,&mut attrs,&mut SmallVec::new()).is_none());
+
+        assert!(extractor.errors.is_empty());
+        let Some(MorphismState { assignments,morphism_assignments,.. }) = extractor.close_morphism() else {
+            panic!("expected a morphism")
+        };
+        // the renaming and the assignment of `set` are merged
+        let [set_a,op_a] = &*assignments else { panic!("expected two assignments") };
+        assert_eq!(set_a.original,set);
+        assert_eq!(set_a.definiens,Some(df));
+        assert_eq!(set_a.new_name.as_ref().map(ToString::to_string).as_deref(),Some("carrier"));
+        assert_eq!(set_a.macroname.as_deref(),Some("carrier"));
+        assert_eq!(op_a.original,op);
+        assert_eq!(op_a.definiens,None);
+        assert_eq!(op_a.new_name.as_ref().map(ToString::to_string).as_deref(),Some("plus"));
+        assert_eq!(op_a.macroname,None);
+        let [ma] = &*morphism_assignments else { panic!("expected one morphism assignment") };
+        assert_eq!(ma.from,sym(&domain,"inner"));
+        assert_eq!(ma.to,sym(&module,"outer"));
+    }
+
+    #[test]
+    fn outside_of_morphism() {
+        let (mut extractor,domain,module) = setup();
+        let set = sym(&domain,"set");
+        rename(&mut extractor,&set,"carrier",None);
+        assign(&mut extractor,&set,oms!(sym(&module,"naturals")));
+        assert!(matches!(&*extractor.errors,[SHTMLError::NotInMorphism,SHTMLError::NotInMorphism]));
+    }
+}
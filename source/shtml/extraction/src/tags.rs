@@ -79,17 +79,17 @@ do_tags!{
 
     Proof                       @ proof,
     SubProof                    @ subproof,
-    ProofMethod                 @ no_op /* TODO */,
-    ProofSketch                 @ no_op /* TODO */,
-    ProofTerm                   @ no_op /* TODO */,
-    ProofBody                   @ no_op /* TODO */,
-    ProofAssumption             @ no_op /* TODO */,
-    ProofHide                   @ no_op /* TODO */,
-    ProofStep                   @ no_op /* TODO */,
-    ProofStepName               @ no_op /* TODO */,
-    ProofEqStep                 @ no_op /* TODO */,
-    ProofPremise                @ no_op /* TODO */,
-    ProofConclusion             @ no_op /* TODO */,
+    ProofMethod                 @ no_op,
+    ProofSketch                 @ proofsketch,
+    ProofTerm                   @ proofterm,
+    ProofBody                   @ proofbody,
+    ProofAssumption             @ proofassumption,
+    ProofHide                   @ no_op,
+    ProofStep                   @ proofstep,
+    ProofStepName               @ proofstepname,
+    ProofEqStep                 @ proofeqstep,
+    ProofPremise                @ proofpremise,
+    ProofConclusion             @ proofconclusion,
 
     PreconditionDimension       @ precondition,
     PreconditionSymbol          @ no_op,
@@ -139,10 +139,10 @@ do_tags!{
     Capitalize                  @ no_op /* TODO */,
     
     Assign                      @ assign,
-    Rename                      @ no_op /* TODO */,
-    RenameTo                    @ no_op /* TODO */,
-    AssignMorphismFrom          @ no_op /* TODO */,
-    AssignMorphismTo            @ no_op /* TODO */,
+    Rename                      @ rename,
+    RenameTo                    @ no_op,
+    AssignMorphismFrom          @ assignmorphism,
+    AssignMorphismTo            @ no_op,
 
     AssocType                   @ no_op,
     ArgumentReordering          @ no_op,
@@ -0,0 +1,83 @@
+//! Test doubles for running extraction rules without an actual HTML document
+
+use immt_ontology::content::terms::Term;
+use immt_ontology::narration::{notations::OpNotation, LazyDocRef};
+use immt_ontology::shtml::SHTMLKey;
+use immt_ontology::uris::DocumentURI;
+use immt_ontology::{DocumentRange, Resourcable};
+use crate::errors::SHTMLError;
+use crate::extractor::{Attributes, ExtractorState, NotationSpec, SHTMLNode, StatefulExtractor};
+use crate::rules::SHTMLElements;
+
+pub(crate) struct Attrs(pub(crate) Vec<(&'static str,String)>);
+impl Attributes for Attrs {
+    type KeyIter<'a> = std::iter::Map<std::slice::Iter<'a,(&'static str,String)>,fn(&(&'static str,String)) -> &str>;
+    type Value<'a> = &'a str;
+    fn keys(&self) -> Self::KeyIter<'_> {
+        self.0.iter().map(|(k,_)| *k)
+    }
+    fn value(&self,key:&str) -> Option<Self::Value<'_>> {
+        self.0.iter().find(|(k,_)| *k == key).map(|(_,v)| v.as_str())
+    }
+    fn set(&mut self,key:&str,value:&str) {
+        if let Some((_,v)) = self.0.iter_mut().find(|(k,_)| *k == key) {
+            *v = value.to_string();
+        }
+    }
+    fn take(&mut self,key:&str) -> Option<String> {
+        let i = self.0.iter().position(|(k,_)| *k == key)?;
+        Some(self.0.remove(i).1)
+    }
+}
+pub(crate) fn attrs<const N:usize>(kvs:[(SHTMLKey,String);N]) -> Attrs {
+    Attrs(kvs.into_iter().map(|(k,v)| (k.attr_name(),v)).collect())
+}
+
+/// Collects errors (and triples) instead of reporting them
+pub(crate) struct Extractor {
+    pub(crate) state:ExtractorState,
+    pub(crate) errors:Vec<SHTMLError>,
+    #[cfg(feature="rdf")]
+    pub(crate) triples:Vec<immt_ontology::rdf::Triple>
+}
+impl Extractor {
+    pub(crate) fn new(document:DocumentURI) -> Self {
+        Self {
+            state:ExtractorState::new(document),
+            errors:Vec::new(),
+            #[cfg(feature="rdf")]
+            triples:Vec::new()
+        }
+    }
+}
+impl StatefulExtractor for Extractor {
+    type Attr<'a> = Attrs;
+    #[cfg(feature="rdf")]
+    const RDF: bool = true;
+    #[cfg(feature="rdf")]
+    fn add_triples<const N:usize>(&mut self, triples:[immt_ontology::rdf::Triple;N]) {
+        self.triples.extend(triples);
+    }
+    fn state_mut(&mut self) -> &mut ExtractorState { &mut self.state }
+    fn state(&self) -> &ExtractorState { &self.state }
+    fn add_error(&mut self,err:SHTMLError) { self.errors.push(err); }
+    fn set_document_title(&mut self,_title:Box<str>) {}
+    fn add_resource<T:Resourcable>(&mut self,_t:&T) -> LazyDocRef<T> { unreachable!() }
+}
+
+/// A node without content or children
+pub(crate) struct Node;
+impl SHTMLNode for Node {
+    type Ancestors<'a> = std::iter::Empty<Self>;
+    fn ancestors(&self) -> Self::Ancestors<'_> { std::iter::empty() }
+    fn with_elements<R>(&mut self,mut f:impl FnMut(Option<&mut SHTMLElements>) -> R) -> R { f(None) }
+    fn delete(&self) {}
+    fn delete_children(&self) {}
+    fn range(&self) -> DocumentRange { DocumentRange { start:0, end:0 } }
+    fn inner_range(&self) -> DocumentRange { DocumentRange { start:0, end:0 } }
+    fn string(&self) -> String { String::new() }
+    fn inner_string(&self) -> String { String::new() }
+    fn as_notation(&self) -> Option<NotationSpec> { None }
+    fn as_op_notation(&self) -> Option<OpNotation> { None }
+    fn as_term(&self) -> Term { unreachable!() }
+}
@@ -3,19 +3,22 @@
 
 mod parser;
 
+use std::{cell::RefCell, str::FromStr};
+
 use either::Either;
-use immt_ontology::uris::{ArchiveURITrait, DocumentURI};
-use immt_system::{backend::{AnyBackend, Backend}, build_result, build_target, building::{BuildArtifact, BuildResult, BuildResultArtifact, BuildTask}, formats::{BuildArtifactTypeId, OMDocResult, CHECK, UNCHECKED_OMDOC}, source_format};
+use html5ever::tokenizer::{BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts};
+use immt_ontology::{shtml::SHTMLKey, uris::{ArchiveURITrait, DocumentURI, ModuleURI}};
+use immt_system::{backend::{AnyBackend, Backend}, build_result, build_target, building::{BuildArtifact, BuildResult, BuildResultArtifact, BuildTask, Dependency, TaskRef}, formats::{BuildArtifactTypeId, OMDocResult, CHECK, UNCHECKED_OMDOC}, source_format};
 
 source_format!(shtml ["html","xhtml","htm"] [SHTML_IMPORT => SHTML_OMDOC => CHECK] @
   "Semantically annotated HTML"
-  = |_,_| todo!()
+  = get_deps
 );
 
 build_target!(
   shtml_import [] => [SHTML_DOC] 
   @ "Import existing sHTML"
-  = |_,_| todo!()
+  = import
 );
 
 build_target!(
@@ -29,7 +32,7 @@ build_result!(shtml_doc @ "Semantically annotated HTML");
 fn extract(backend:&AnyBackend,task:&BuildTask) -> BuildResult {
   let html:Result<HTMLString,_> = backend.with_archive(task.archive().archive_id(), |a| {
     let Some(a) = a else {return Err(BuildResult::err())};
-    a.load(task.rel_path()).map_err(|e| BuildResult {
+    a.load(task.rel_path(),backend).map_err(|e| BuildResult {
       log:Either::Left(format!("Error loading html data for {}/{}: {e}",task.archive().archive_id(),task.rel_path())),
       result:Err(Vec::new())
     })
@@ -50,6 +53,116 @@ fn extract(backend:&AnyBackend,task:&BuildTask) -> BuildResult {
   }
 }
 
+fn import(_:&AnyBackend,task:&BuildTask) -> BuildResult {
+  let Either::Left(path) = task.source() else {
+    return BuildResult {
+      log:Either::Left("Needs a physical file".to_string()),
+      result:Err(Vec::new())
+    }
+  };
+  match std::fs::read(path) {
+    Err(e) => BuildResult {
+      log:Either::Left(format!("Error reading {}: {e}",path.display())),
+      result:Err(Vec::new())
+    },
+    Ok(bytes) => BuildResult {
+      log:Either::Left(String::new()),
+      result:Ok(HTMLString::create(normalize(String::from_utf8_lossy_owned(bytes))))
+    }
+  }
+}
+
+/// Strips byte order marks and XML declarations and makes sure the
+/// document starts with an HTML doctype, so that (X)HTML produced by
+/// other tools is parsed as a full HTML document by [`build_shtml`].
+fn normalize(html:String) -> String {
+  let mut s = html.trim_start_matches('\u{feff}').trim_start();
+  if s.starts_with("<?xml") {
+    if let Some(i) = s.find("?>") {
+      s = s[i+2..].trim_start();
+    }
+  }
+  if s.get(..9).is_some_and(|p| p.eq_ignore_ascii_case("<!doctype")) {
+    s.to_string()
+  } else {
+    format!("<!DOCTYPE html>\n{s}")
+  }
+}
+
+/// Collects the values of the attributes `attrs` of all start tags, as tokenized (and
+/// unescaped) by html5ever.
+struct AttributeValues<const N:usize> {
+  attrs:[&'static str;N],
+  values:RefCell<[Vec<String>;N]>
+}
+impl<const N:usize> TokenSink for AttributeValues<N> {
+  type Handle = ();
+  fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+    if let Token::TagToken(Tag { kind:TagKind::StartTag, attrs, .. }) = token {
+      let mut values = self.values.borrow_mut();
+      for a in attrs {
+        if let Some(i) = self.attrs.iter().position(|n| *n == &*a.name.local) {
+          values[i].push(a.value.to_string());
+        }
+      }
+    }
+    TokenSinkResult::Continue
+  }
+}
+
+/// The values of the attributes `attrs` (in order) in `html`.
+fn attribute_values<const N:usize>(html:&str,attrs:[&'static str;N]) -> [Vec<String>;N] {
+  let tokenizer = Tokenizer::new(AttributeValues {
+    attrs, values:RefCell::new(std::array::from_fn(|_| Vec::new()))
+  }, TokenizerOpts::default());
+  let input = BufferQueue::default();
+  input.push_back(html.into());
+  let _ = tokenizer.feed(&input);
+  tokenizer.end();
+  tokenizer.sink.values.into_inner()
+}
+
+fn get_deps(backend:&AnyBackend,task:&BuildTask) {
+  let Either::Left(path) = task.source() else {return};
+  let Ok(bytes) = std::fs::read(path) else {return};
+  let html = String::from_utf8_lossy(&bytes);
+  let (check,omdoc) = dependencies(&html, |uri| backend.with_archive(uri.archive_id(), |a|
+    a.and_then(|a| a.find_source(uri))
+  ));
+  if let Some(step) = task.get_step(CHECK) {
+    for d in check { step.add_dependency(d); }
+  }
+  if let Some(step) = task.get_step(SHTML_OMDOC) {
+    for d in omdoc { step.add_dependency(d); }
+  }
+}
+
+/// The dependencies of the [`CHECK`] step (on the imported and used modules) and of the
+/// [`SHTML_OMDOC`] step (on the documents referenced via inputref) of an sHTML document;
+/// `find_source` yields the path of a document's source file relative to its archive.
+fn dependencies(html:&str,mut find_source:impl FnMut(&DocumentURI) -> Option<std::sync::Arc<str>>) -> (Vec<Dependency>,Vec<Dependency>) {
+  let [imports,uses,inputrefs] = attribute_values(html, [
+    SHTMLKey::ImportModule.attr_name(),SHTMLKey::UseModule.attr_name(),SHTMLKey::InputRef.attr_name()
+  ]);
+  let check = imports.into_iter().chain(uses)
+    .filter_map(|uri| ModuleURI::from_str(&uri).ok())
+    .map(|uri| Dependency::Logical { uri:!uri, strict: true })
+    .collect();
+  let omdoc = inputrefs.into_iter().filter_map(|uri| {
+    let uri = DocumentURI::from_str(&uri).ok()?;
+    let rel_path = find_source(&uri)?;
+    Some(Dependency::Physical {
+      strict: false,
+      task: TaskRef {
+        archive: uri.archive_id().clone(),
+        rel_path,
+        target: SHTML_OMDOC
+      }
+    })
+  }).collect();
+  (check,omdoc)
+}
+
 /// #### Errors
 #[inline]
 pub fn build_shtml(backend:&AnyBackend,html:&str,uri:DocumentURI,rel_path:&str) -> Result<(OMDocResult,String),String> {
@@ -69,7 +182,7 @@ impl BuildArtifact for HTMLString {
   fn write(&self,path:&std::path::Path) -> Result<(),std::io::Error> {
     std::fs::write(path, &self.0)
   }
-  fn load(p:&std::path::Path) -> Result<Self,std::io::Error> where Self:Sized {
+  fn load(p:&std::path::Path,_backend:&AnyBackend) -> Result<Self,std::io::Error> where Self:Sized {
     let s = std::fs::read_to_string(p)?;
     Ok(Self(s))
   }
@@ -82,4 +195,71 @@ impl HTMLString {
   pub fn create(s:String) -> BuildResultArtifact {
     BuildResultArtifact::Data(Box::new(Self(s)))
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use immt_ontology::uris::{ArchiveURI, ArchiveURITrait, BaseURI, DocumentURI, ModuleURI};
+  use immt_system::building::{Dependency, TaskRef};
+
+  use super::{dependencies, normalize, SHTML_OMDOC};
+
+  #[test]
+  fn normalization() {
+    assert_eq!(normalize("\u{feff}<!DOCTYPE html><html></html>".to_string()),"<!DOCTYPE html><html></html>");
+    assert_eq!(
+      normalize("\u{feff}<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!doctype html>\n<html></html>".to_string()),
+      "<!doctype html>\n<html></html>"
+    );
+    assert_eq!(
+      normalize("<?xml version=\"1.0\"?>\n  <html xmlns=\"http://www.w3.org/1999/xhtml\"></html>".to_string()),
+      "<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\"></html>"
+    );
+    assert_eq!(normalize("<html></html>".to_string()),"<!DOCTYPE html>\n<html></html>");
+  }
+
+  fn archive() -> ArchiveURI {
+    BaseURI::new_unchecked("http://example.com/") & "some/archive"
+  }
+
+  #[test]
+  fn module_and_document_dependencies() {
+    let imported : ModuleURI = (archive() | "Top/Nested").expect("is valid");
+    let used : ModuleURI = (archive() | "Used").expect("is valid");
+    let known = DocumentURI::from_archive_relpath(archive(), "sub/known.en.tex");
+    let unknown = DocumentURI::from_archive_relpath(archive(), "unknown.en.tex");
+    // attribute values are unescaped
+    let attr = |uri:String| uri.replace('&',"&amp;");
+    let html = format!(
+      r#"<!DOCTYPE html><html><body>
+        <div data-shtml-import="{}"><span data-shtml-usemodule="{}"></span></div>
+        <div data-shtml-inputref="{}"></div><div data-shtml-inputref="{}"></div>
+      </body></html>"#,
+      attr(imported.to_string()),attr(used.to_string()),attr(known.to_string()),attr(unknown.to_string())
+    );
+    let (check,omdoc) = dependencies(&html,|uri| (*uri == known).then(|| "sub/known.en.tex".into()));
+    assert_eq!(check,vec![
+      Dependency::Logical { uri:(archive() | "Top").expect("is valid"), strict:true },
+      Dependency::Logical { uri:used, strict:true }
+    ]);
+    assert_eq!(omdoc,vec![Dependency::Physical {
+      strict:false,
+      task:TaskRef { archive:archive().archive_id().clone(), rel_path:"sub/known.en.tex".into(), target:SHTML_OMDOC }
+    }]);
+  }
+
+  #[test]
+  fn no_or_malformed_dependencies() {
+    let none = |html:&str| {
+      let (check,omdoc) = dependencies(html,|_| Some("doc.en.tex".into()));
+      assert!(check.is_empty() && omdoc.is_empty(),"{html}: {check:?}, {omdoc:?}");
+    };
+    none("<!DOCTYPE html><html><body><p>no dependencies</p></body></html>");
+    none("");
+    none(r#"<div data-shtml-import="not a module uri" data-shtml-inputref="not a document uri"></div>"#);
+    none("<div data-shtml-import data-shtml-usemodule= data-shtml-inputref=''></div>");
+    // only start tags carry attributes; an unfinished tag is dropped at the end of the input
+    let uri = (archive() | "Foo").expect("is valid").to_string().replace('&',"&amp;");
+    none(&format!(r#"<div></div data-shtml-import="{uri}"><div data-shtml-import="{uri}"#));
+  }
+}
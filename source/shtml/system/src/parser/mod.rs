@@ -421,9 +421,7 @@ fn update_attributes(elements:&SHTMLElements,child:&ElementData) {
     }
     OpenSHTMLElement::Morphism { uri,domain,..} => {
       attrs.update(SHTMLTag::MorphismDomain, domain);
-      if let Some(uri) = uri {
-        attrs.update(SHTMLTag::Morphism, &uri.clone().into_module());
-      }
+      attrs.update(SHTMLTag::Morphism, &uri.clone().into_module());
     }
     OpenSHTMLElement::Assign(uri) => {
       attrs.update(SHTMLTag::Assign, uri);
@@ -2,6 +2,7 @@ pub(crate) mod inputref;
 pub(crate) mod sections;
 pub(crate) mod terms;
 pub(crate) mod exercise;
+pub(crate) mod proofs;
 pub mod documents;
 mod toc;
 pub(crate) mod navigation;
@@ -87,6 +88,16 @@ fn do_components<const MATH:bool>(skip:usize,elements:SHTMLElements,orig:Origina
       OpenSHTMLElement::Fillinsol(wd) => {
         exercise::fillinsol(*wd).into_any()
       }
+      OpenSHTMLElement::Proof { kind, hide, .. } => {
+        proofs::proof(*kind,*hide,
+          move || do_components::<MATH>(skip+1,elements,orig)
+        ).into_any()
+      }
+      OpenSHTMLElement::ProofBody => {
+        proofs::proof_body(
+          move || do_components::<MATH>(skip+1,elements,orig)
+        ).into_any()
+      }
       _ => todo!()
     }
   } else {
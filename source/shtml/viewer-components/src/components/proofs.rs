@@ -0,0 +1,28 @@
+use immt_ontology::narration::paragraphs::ParagraphKind;
+use leptos::{context::Provider, prelude::*};
+
+#[derive(Copy,Clone,Debug)]
+struct CurrentProof {
+  kind:ParagraphKind,
+  hide:bool
+}
+
+pub(super) fn proof<V:IntoView+'static>(kind:ParagraphKind,hide:bool,children:impl FnOnce() -> V + Send + 'static) -> impl IntoView {
+  view!{
+    <Provider value=CurrentProof{kind,hide}>{children()}</Provider>
+  }
+}
+
+/// The body of the innermost (sub)proof; collapsed initially if the proof is marked as hidden,
+/// so nested subproofs yield a collapsible proof tree
+pub(super) fn proof_body<V:IntoView+'static>(children:impl FnOnce() -> V + Send + 'static) -> impl IntoView {
+  use immt_web_utils::components::{Collapsible,Header};
+  let (kind,hide) = with_context::<CurrentProof,_>(|p| (p.kind,p.hide))
+    .unwrap_or((ParagraphKind::Proof,false));
+  view!{
+    <Collapsible expanded=!hide>
+      <Header slot><span style="font-style:italic;color:gray">{kind.as_display_str()}</span></Header>
+      {children()}
+    </Collapsible>
+  }
+}
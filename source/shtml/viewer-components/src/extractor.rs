@@ -5,6 +5,13 @@ use smallvec::SmallVec;
 use shtml_extraction::prelude::{Attributes, GnoteState, SHTMLExtractor};
 use leptos::{prelude::{expect_context, UpdateValue}, web_sys::Element};
 
+/// Extracts the sHTML elements relevant for rendering from the DOM.
+///
+/// Unlike the extractor used when building, this one does not collect content: proof
+/// structures and morphisms are only rendered here (their structure is available in the
+/// OMDoc extracted at build time), so [`with_proof`](SHTMLExtractor::with_proof),
+/// [`with_morphism`](SHTMLExtractor::with_morphism) and
+/// [`close_morphism`](SHTMLExtractor::close_morphism) always return `None`.
 #[derive(Default)]
 pub struct DOMExtractor {
     in_notation:bool,
@@ -112,6 +119,10 @@ impl SHTMLExtractor for DOMExtractor {
     fn close_content(&mut self) -> Option<(immt_ontology::uris::ModuleURI,Vec<immt_ontology::content::declarations::OpenDeclaration<Unchecked>>)> {
         todo!()
     }
+    fn close_morphism(&mut self) -> Option<shtml_extraction::prelude::MorphismState> {
+        // morphisms are not collected; see the type documentation
+        None
+    }
     fn close_decl(&mut self) -> Option<(Option<immt_ontology::content::terms::Term>,Option<immt_ontology::content::terms::Term>)> {
         todo!()
     }
@@ -139,6 +150,14 @@ impl SHTMLExtractor for DOMExtractor {
     fn with_exercise<R>(&mut self,then:impl FnOnce(&mut shtml_extraction::prelude::ExerciseState) -> R) -> Option<R> {
         todo!()
     }
+    fn with_proof<R>(&mut self,_then:impl FnOnce(&mut shtml_extraction::prelude::ProofState) -> R) -> Option<R> {
+        // proof structures are not collected; see the type documentation
+        None
+    }
+    fn with_morphism<R>(&mut self,_then:impl FnOnce(&mut shtml_extraction::prelude::MorphismState) -> R) -> Option<R> {
+        // morphisms are not collected; see the type documentation
+        None
+    }
     fn close_gnote(&mut self) -> Option<GnoteState> {
         todo!()   
     }
@@ -159,10 +178,12 @@ impl SHTMLExtractor for DOMExtractor {
     fn open_complex_term(&mut self) {}
     fn open_content(&mut self,_uri:immt_ontology::uris::ModuleURI) {}
     fn open_decl(&mut self) {}
+    fn open_morphism(&mut self,_uri:immt_ontology::uris::ModuleURI) {}
     fn open_exercise(&mut self,_uri:immt_ontology::uris::DocumentElementURI) {}
     fn open_narrative(&mut self,_uri:Option<immt_ontology::uris::NarrativeURI>) {}
     fn open_notation(&mut self) {}
     fn open_paragraph(&mut self,_uri:immt_ontology::uris::DocumentElementURI,_fors:VecSet<immt_ontology::uris::SymbolURI>) {}
+    fn open_proof(&mut self,_method:Option<Box<str>>,_hide:bool) {}
     fn open_section(&mut self,_uri:immt_ontology::uris::DocumentElementURI) {}
     fn set_document_title(&mut self,_title:Box<str>) {}
 }
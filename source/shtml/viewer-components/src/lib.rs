@@ -87,7 +87,7 @@ pub fn SHTMLStringMath(html:String) -> impl IntoView {
     view!(<math><DomStringContMath html cont=iterate/></math>)
 }
 
-pub static RULES:[SHTMLExtractionRule<DOMExtractor>;34] = [
+pub static RULES:[SHTMLExtractionRule<DOMExtractor>;39] = [
     rule(SHTMLTag::Section),
     rule(SHTMLTag::Term),
     rule(SHTMLTag::Arg),
@@ -111,6 +111,10 @@ pub static RULES:[SHTMLExtractionRule<DOMExtractor>;34] = [
     rule(SHTMLTag::ProblemChoice),
     rule(SHTMLTag::ProblemFillinsol),
 
+    rule(SHTMLTag::Proof),
+    rule(SHTMLTag::SubProof),
+    rule(SHTMLTag::ProofBody),
+
     // ---- no-ops --------
     rule(SHTMLTag::ArgMode),
     rule(SHTMLTag::NotationId),
@@ -129,6 +133,8 @@ pub static RULES:[SHTMLExtractionRule<DOMExtractor>;34] = [
     rule(SHTMLTag::Argprecs),
     rule(SHTMLTag::Autogradable),
     rule(SHTMLTag::AnswerClassPts),
+    rule(SHTMLTag::ProofHide),
+    rule(SHTMLTag::ProofMethod),
 ];
 
 #[cfg_attr(all(feature="csr",not(feature="ts")),wasm_bindgen::prelude::wasm_bindgen)]
@@ -1,9 +1,9 @@
 use crate::{
-    quickparse::{latex::LaTeXParser, stex::{structs::{ModuleReference, STeXParseState, STeXToken}, DiagnosticLevel}},
+    quickparse::{latex::LaTeXParser, stex::{structs::{ModuleReference, STeXParseState, STeXToken}, DiagnosticCode, DiagnosticLevel}},
     PDFLATEX_FIRST,
 };
 use either::Either;
-use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, DocumentURI}};
+use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, DocumentURI, ModuleURI}};
 use immt_system::{
     backend::AnyBackend,
     building::{BuildTask, Dependency, TaskRef},
@@ -26,22 +26,28 @@ pub enum STeXDependency {
         archive: Option<ArchiveId>,
         filepath: std::sync::Arc<str>
     },
+    /// The domain of a morphism or the module in a `\realize`
+    Logical {
+        uri: ModuleURI
+    },
     Module{
         //uri:ModuleURI,
         sig:Option<Language>,
-        meta:Option<(ArchiveId,std::sync::Arc<str>)>
+        meta:Option<(ArchiveId,std::sync::Arc<str>)>,
+        /// A metatheory given explicitly via `meta=`
+        custom_meta:Option<ModuleURI>
     }
 }
 
 #[allow(clippy::type_complexity)]
 pub struct DepParser<'a> {
-    parser: LaTeXParser<'a, ParseStr<'a,()>,STeXToken<()>,fn(String,SourceRange<()>,DiagnosticLevel),STeXParseState<'a,(),()>>,
+    parser: LaTeXParser<'a, ParseStr<'a,()>,STeXToken<()>,fn(String,SourceRange<()>,DiagnosticLevel,Option<DiagnosticCode>),STeXParseState<'a,(),()>>,
     stack: Vec<std::vec::IntoIter<STeXToken<()>>>,
     curr: Option<std::vec::IntoIter<STeXToken<()>>>,
 }
 
 fn parse_deps<'a>(source: &'a str, path: &'a Path,archive:ArchiveURIRef<'a>,doc:&'a DocumentURI,backend:&'a AnyBackend) -> impl Iterator<Item = STeXDependency> + use<'a> {
-    const NOERR: fn(String,SourceRange<()>,DiagnosticLevel) = |_,_,_| {};
+    const NOERR: fn(String,SourceRange<()>,DiagnosticLevel,Option<DiagnosticCode>) = |_,_,_,_| {};
     let parser = LaTeXParser::with_rules(
         ParseStr::new(source),
         STeXParseState::<(),()>::new(Some(archive),Some(path),doc,backend,()),
@@ -50,18 +56,35 @@ fn parse_deps<'a>(source: &'a str, path: &'a Path,archive:ArchiveURIRef<'a>,doc:
             ("importmodule", rules::importmodule_deps as _),
             ("setmetatheory", rules::setmetatheory as _),
             ("usemodule", rules::usemodule_deps as _),
+            ("realize", rules::realize_deps as _),
             ("inputref", rules::inputref as _),
             ("stexstyleassertion",rules::stexstyleassertion as _),
             ("stexstyledefinition",rules::stexstyledefinition as _),
             ("stexstyleparagraph",rules::stexstyleparagraph as _),
         ]),
-        LaTeXParser::default_env_rules().into_iter().chain([(
-            "smodule",
+        LaTeXParser::default_env_rules().into_iter().chain([
+            (
+                "smodule",
+                (
+                    rules::smodule_deps_open as _,
+                    rules::smodule_deps_close as _,
+                ),
+            ),
+            (
+                "copymodule",
+                (
+                    rules::copymodule_deps_open as _,
+                    rules::copymodule_deps_close as _,
+                ),
+            ),
             (
-                rules::smodule_deps_open as _,
-                rules::smodule_deps_close as _,
+                "interpretmodule",
+                (
+                    rules::interpretmodule_deps_open as _,
+                    rules::interpretmodule_deps_close as _,
+                ),
             ),
-        )]),
+        ]),
     );
     DepParser {
         parser,
@@ -85,7 +108,18 @@ impl DepParser<'_> {
                 if let Some(old) = old {
                     self.stack.push(old);
                 }
-                Some(STeXDependency::Module{/*uri,*/sig:sig.map(|(l,_)| l),meta:meta_theory.and_then(|(m,_)| m.rel_path.map(|p| (m.uri.archive_id().clone(),p)) )})
+                let custom_meta = meta_theory.as_ref().and_then(|(m,r)| r.is_some().then(|| m.uri.clone()));
+                Some(STeXDependency::Module{/*uri,*/sig:sig.map(|(l,_)| l),meta:meta_theory.and_then(|(m,_)| m.rel_path.map(|p| (m.uri.archive_id().clone(),p)) ),custom_meta})
+            }
+            STeXToken::Morphism { domain, children, .. } => {
+                let old = std::mem::replace(&mut self.curr, Some(children.into_iter()));
+                if let Some(old) = old {
+                    self.stack.push(old);
+                }
+                Some(STeXDependency::Logical { uri:domain.uri })
+            }
+            STeXToken::Realize { module, .. } => {
+                Some(STeXDependency::Logical { uri:module.uri })
             }
             STeXToken::Inputref {
                 archive,
@@ -175,7 +209,12 @@ pub fn get_deps(backend: &AnyBackend, task: &BuildTask) {
                         });
                     }
                 }
-                STeXDependency::Module { /*uri:_,*/ sig,meta } => {
+                STeXDependency::Logical { uri } => {
+                    if let Some(step) = task.get_step(CHECK) {
+                        step.add_dependency(Dependency::Logical { uri:!uri, strict: true });
+                    }
+                }
+                STeXDependency::Module { /*uri:_,*/ sig,meta,custom_meta } => {
                     //yields.push(uri);
                     if let Some(lang) = sig {
                         let archive = task.archive().id().clone();
@@ -228,6 +267,11 @@ pub fn get_deps(backend: &AnyBackend, task: &BuildTask) {
                             });
                         }
                     }
+                    if let Some(uri) = custom_meta {
+                        if let Some(step) = task.get_step(CHECK) {
+                            step.add_dependency(Dependency::Logical { uri:!uri, strict: true });
+                        }
+                    }
                 }
             }
         }
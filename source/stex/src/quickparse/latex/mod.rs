@@ -13,7 +13,7 @@ use std::convert::Into;
 use std::marker::PhantomData;
 use tex_engine::utils::HMap;
 
-use super::stex::DiagnosticLevel;
+use super::stex::{DiagnosticCode, DiagnosticLevel};
 
 
 pub trait FromLaTeXToken<'a, Pos:SourcePos, Str:StringOrStr<'a>>: Sized + std::fmt::Debug {
@@ -192,7 +192,7 @@ pub struct OptMap<'a,
 pub struct Group<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > {
     previous_letters: Option<String>,
@@ -205,7 +205,7 @@ pub struct Group<'a,
 pub trait GroupState<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > {
     fn new(parent:Option<&mut Self>) -> Self;
@@ -220,7 +220,7 @@ pub trait GroupState<'a,
 impl<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > GroupState<'a,Pa,T,Err,State> for Group<'a, Pa, T, Err,State> {
     fn new(_:Option<&mut Self>) -> Self {
@@ -271,7 +271,7 @@ impl<'a,
 pub trait ParserState<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel)
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 >:Sized {
     type Group:GroupState<'a,Pa,T,Err,Self>;
     type MacroArg:Clone;
@@ -280,7 +280,7 @@ pub trait ParserState<'a,
 impl<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel)
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > ParserState<'a,Pa,T,Err> for () {
     type Group=Group<'a,Pa,T,Err,Self>;
     type MacroArg = ();
@@ -289,7 +289,7 @@ impl<'a,
 impl<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > Group<'a, Pa, T, Err, State> {
 
@@ -299,7 +299,7 @@ pub struct LaTeXParser<
     'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > {
     pub tokenizer: super::tokenizer::TeXTokenizer<'a, Pa,Err>,
@@ -340,7 +340,7 @@ macro_rules! default_envs {
 pub struct Groups<'a,'b,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > {
     pub groups:&'b mut Vec<State::Group>,
@@ -352,7 +352,7 @@ pub struct Groups<'a,'b,
 impl<'a,'b,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > Groups<'a,'b,Pa,T,Err,State> {
     pub fn add_macro_rule(&mut self, name: Cow<'a,str>, rule: Option<AnyMacro<'a, Pa, T, Err, State>>) {
@@ -384,7 +384,7 @@ impl<'a,'b,
 impl<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > LaTeXParser<'a, Pa, T, Err,State> {
     pub fn new(input: Pa, state:State,err:Err) -> Self {
@@ -956,7 +956,7 @@ impl<'a,
 impl<'a,
     Pos:SourcePos,
     T: FromLaTeXToken<'a, Pos, &'a str>,
-    Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,ParseStr<'a,Pos>,T,Err>
 > LaTeXParser<'a, ParseStr<'a,Pos>,T,Err,State> {
     pub fn read_opt_map(
@@ -1053,7 +1053,7 @@ impl<'a,
 impl<'a,
     Pa: ParseSource<'a>,
     T: FromLaTeXToken<'a, Pa::Pos, Pa::Str>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel),
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>),
     State: ParserState<'a,Pa,T,Err>
 > Iterator
     for LaTeXParser<'a, Pa, T, Err, State>
@@ -4,12 +4,12 @@ pub mod structs;
 use std::path::Path;
 
 use chrono::format::parse;
-use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURITrait, DocumentURI, ModuleURI, SymbolURI}};
+use immt_ontology::{languages::Language, narration::paragraphs::ParagraphKind, uris::{ArchiveId, ArchiveURITrait, DocumentURI, ModuleURI, Name, SymbolURI}};
 use immt_system::backend::AnyBackend;
 use immt_utils::{parsing::ParseStr, prelude::{TreeChild, TreeChildIter, TreeLike}, sourcerefs::{LSPLineCol, SourceRange}, vecmap::VecSet};
 use rules::{NotationArgs, SymdeclArgs};
 use smallvec::SmallVec;
-use structs::{ModuleReference, ModuleRules, STeXModuleStore, STeXParseState, STeXToken, SymbolReference, SymnameMod};
+use structs::{ModuleReference, ModuleRules, MorphismKind, STeXModuleStore, STeXParseState, STeXToken, SymbolReference, SymnameMod, VariableReference};
 
 use super::latex::LaTeXParser;
 
@@ -103,12 +103,116 @@ pub enum STeXAnnot {
     notation:(SourceRange<LSPLineCol>,Vec<Self>),
     full_range: SourceRange<LSPLineCol>
   },
+  #[allow(clippy::type_complexity)]
+  Vardef {
+    name:Name,
+    main_name_range:SourceRange<LSPLineCol>,
+    name_ranges:Option<(SourceRange<LSPLineCol>,SourceRange<LSPLineCol>)>,
+    parsed_args:Box<SymdeclArgs<LSPLineCol,Self>>,
+    notation_args:NotationArgs<LSPLineCol,Self>,
+    token_range: SourceRange<LSPLineCol>,
+    sequence:Option<(SourceRange<LSPLineCol>,Vec<Self>)>,
+    notation:(SourceRange<LSPLineCol>,Vec<Self>),
+    full_range: SourceRange<LSPLineCol>
+  },
+  VariableMacro {
+    var:VariableReference<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>
+  },
   SymName {
     uri:SymbolReference<LSPLineCol>,
     full_range: SourceRange<LSPLineCol>,
     token_range: SourceRange<LSPLineCol>,
     name_range: SourceRange<LSPLineCol>,
     mod_:SymnameMod<LSPLineCol>
+  },
+  Symref {
+    uri:SymbolReference<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    name_range: SourceRange<LSPLineCol>
+  },
+  Paragraph {
+    kind:ParagraphKind,
+    full_range: SourceRange<LSPLineCol>,
+    name_range: SourceRange<LSPLineCol>,
+    fors:Vec<(SymbolReference<LSPLineCol>,SourceRange<LSPLineCol>)>,
+    children:Vec<Self>
+  },
+  Definiendum {
+    uri:SymbolReference<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    name_range: SourceRange<LSPLineCol>
+  },
+  Definame {
+    uri:SymbolReference<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    name_range: SourceRange<LSPLineCol>,
+    mod_:SymnameMod<LSPLineCol>
+  },
+  Definiens {
+    uri:Option<(SymbolReference<LSPLineCol>,SourceRange<LSPLineCol>)>,
+    full_range: SourceRange<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    children:Vec<Self>
+  },
+  MathStructure {
+    uri:SymbolReference<LSPLineCol>,
+    macroname:Option<String>,
+    name_range:SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    mathstructure_range: SourceRange<LSPLineCol>,
+    children:Vec<Self>
+  },
+  Extension {
+    uri:SymbolReference<LSPLineCol>,
+    targets:Vec<(SymbolReference<LSPLineCol>,SourceRange<LSPLineCol>)>,
+    name_range:SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    extstructure_range: SourceRange<LSPLineCol>,
+    children:Vec<Self>
+  },
+  UseStructure {
+    structure:SymbolReference<LSPLineCol>,
+    structure_range:SourceRange<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>
+  },
+  Morphism {
+    kind:MorphismKind,
+    uri:Option<SymbolURI>,
+    domain:ModuleReference,
+    archive_range: Option<SourceRange<LSPLineCol>>,
+    domain_range:SourceRange<LSPLineCol>,
+    name_range:SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    morphism_range: SourceRange<LSPLineCol>,
+    children:Vec<Self>
+  },
+  Realize {
+    archive_range: Option<SourceRange<LSPLineCol>>,
+    path_range: SourceRange<LSPLineCol>,
+    module: ModuleReference,
+    token_range: SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>
+  },
+  Assign {
+    uri:SymbolReference<LSPLineCol>,
+    symbol_range:SourceRange<LSPLineCol>,
+    token_range: SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>,
+    definiens:(SourceRange<LSPLineCol>,Vec<Self>)
+  },
+  RenameDecl {
+    uri:SymbolReference<LSPLineCol>,
+    symbol_range:SourceRange<LSPLineCol>,
+    name:Option<(Name,SourceRange<LSPLineCol>)>,
+    macroname:(String,SourceRange<LSPLineCol>),
+    token_range: SourceRange<LSPLineCol>,
+    full_range: SourceRange<LSPLineCol>
   }
 }
 impl STeXAnnot {
@@ -118,7 +222,10 @@ impl STeXAnnot {
       match t {
         STeXToken::Module { uri, name_range, sig, meta_theory, full_range, smodule_range, children,rules } => {
           if let Some(ref mut m) = modules { m.push((uri.clone(),rules)) };
-          v.push(STeXAnnot::Module { uri, name_range, sig, meta_theory, full_range, smodule_range, children:Self::from_tokens(children,None) });
+          v.push(STeXAnnot::Module { uri, name_range, sig, meta_theory, full_range, smodule_range, 
+            // nested modules and structures
+            children:Self::from_tokens(children,if let Some(m) = modules.as_mut() { Some(*m) } else { None } )
+          });
         }
         STeXToken::SemanticMacro { uri, argnum, token_range, full_range } => 
           v.push(STeXAnnot::SemanticMacro { uri, argnum, token_range, full_range }),
@@ -140,8 +247,55 @@ impl STeXAnnot {
           notation_args:notation_args.into_other(|v| Self::from_tokens(v,if let Some(m) = modules.as_mut() { Some(*m) } else { None } )),
           notation:(notation.0,Self::from_tokens(notation.1,None))
         }),
+        STeXToken::Vardef { name, main_name_range, name_ranges, token_range, full_range, parsed_args, notation_args, sequence, notation } =>
+        v.push(STeXAnnot::Vardef { name, main_name_range, name_ranges, token_range, full_range, 
+          parsed_args:Box::new(parsed_args.into_other(|v| Self::from_tokens(v,None))),
+          notation_args:notation_args.into_other(|v| Self::from_tokens(v,None)),
+          sequence:sequence.map(|(r,v)| (r,Self::from_tokens(v,None))),
+          notation:(notation.0,Self::from_tokens(notation.1,None))
+        }),
+        STeXToken::VariableMacro { var, full_range, token_range } =>
+          v.push(STeXAnnot::VariableMacro { var, full_range, token_range }),
         STeXToken::SymName { uri, full_range, token_range, name_range, mod_ } =>
           v.push(STeXAnnot::SymName { uri, full_range, token_range, name_range, mod_ }),
+        STeXToken::Symref { uri, full_range, token_range, name_range } =>
+          v.push(STeXAnnot::Symref { uri, full_range, token_range, name_range }),
+        STeXToken::Paragraph { kind, full_range, name_range, fors, children } =>
+          v.push(STeXAnnot::Paragraph { kind, full_range, name_range, fors,
+            children:Self::from_tokens(children,if let Some(m) = modules.as_mut() { Some(*m) } else { None } )
+          }),
+        STeXToken::Definiendum { uri, full_range, token_range, name_range } =>
+          v.push(STeXAnnot::Definiendum { uri, full_range, token_range, name_range }),
+        STeXToken::Definame { uri, full_range, token_range, name_range, mod_ } =>
+          v.push(STeXAnnot::Definame { uri, full_range, token_range, name_range, mod_ }),
+        STeXToken::Definiens { uri, full_range, token_range, children } =>
+          v.push(STeXAnnot::Definiens { uri, full_range, token_range, children:Self::from_tokens(children,None) }),
+        STeXToken::MathStructure { uri, macroname, rules, name_range, full_range, children, mathstructure_range } => {
+          if let Some(ref mut m) = modules { m.push((uri.uri.clone().into_module(),rules)) };
+          v.push(STeXAnnot::MathStructure { uri, macroname, name_range, full_range, mathstructure_range,
+            children:Self::from_tokens(children,None)
+          });
+        }
+        STeXToken::Extension { uri, targets, rules, name_range, full_range, children, extstructure_range } => {
+          if let Some(ref mut m) = modules { m.push((uri.uri.clone().into_module(),rules)) };
+          v.push(STeXAnnot::Extension { uri, targets, name_range, full_range, extstructure_range,
+            children:Self::from_tokens(children,None)
+          });
+        }
+        STeXToken::UseStructure { structure, structure_range, full_range, token_range } =>
+          v.push(STeXAnnot::UseStructure { structure, structure_range, full_range, token_range }),
+        STeXToken::Morphism { kind, uri, domain, archive_range, domain_range, name_range, full_range, children, morphism_range } =>
+          v.push(STeXAnnot::Morphism { kind, uri, domain, archive_range, domain_range, name_range, full_range, morphism_range,
+            children:Self::from_tokens(children,None)
+          }),
+        STeXToken::Realize { archive_range, path_range, module, full_range, token_range } =>
+          v.push(STeXAnnot::Realize { archive_range, path_range, module, token_range, full_range }),
+        STeXToken::Assign { uri, symbol_range, full_range, token_range, definiens } =>
+          v.push(STeXAnnot::Assign { uri, symbol_range, token_range, full_range,
+            definiens:(definiens.0,Self::from_tokens(definiens.1,None))
+          }),
+        STeXToken::RenameDecl { uri, symbol_range, name, macroname, full_range, token_range } =>
+          v.push(STeXAnnot::RenameDecl { uri, symbol_range, name, macroname, token_range, full_range }),
         STeXToken::Vec(vi) => v.extend(Self::from_tokens(vi,if let Some(m) = modules.as_mut() { Some(*m) } else { None } )),
       }
     }
@@ -158,7 +312,21 @@ impl STeXAnnot {
       Self::SetMetatheory { full_range, .. } |
       Self::Symdecl { full_range, .. } |
       Self::Symdef  { full_range, .. } |
-      Self::SymName { full_range, .. } => *full_range,
+      Self::Vardef { full_range, .. } |
+      Self::VariableMacro { full_range, .. } |
+      Self::SymName { full_range, .. } |
+      Self::Symref { full_range, .. } |
+      Self::Paragraph { full_range, .. } |
+      Self::Definiendum { full_range, .. } |
+      Self::Definame { full_range, .. } |
+      Self::Definiens { full_range, .. } |
+      Self::MathStructure { full_range, .. } |
+      Self::Extension { full_range, .. } |
+      Self::UseStructure { full_range, .. } |
+      Self::Morphism { full_range, .. } |
+      Self::Realize { full_range, .. } |
+      Self::Assign { full_range, .. } |
+      Self::RenameDecl { full_range, .. } => *full_range,
       Self::Inputref { range, .. } => *range,
     }
   }
@@ -194,7 +362,13 @@ impl TreeLike for STeXAnnot {
     #[inline]
     fn ident(o:Option<std::slice::Iter<STeXAnnot>>) -> Option<std::slice::Iter<STeXAnnot>> { o }
     match self {
-      Self::Module { children, .. }  => Some(AnnotIter::Slice(children.iter())),
+      Self::Module { children, .. } |
+      Self::Paragraph { children, .. } |
+      Self::Definiens { children, .. } |
+      Self::MathStructure { children, .. } |
+      Self::Extension { children, .. } |
+      Self::Morphism { children, .. } |
+      Self::Assign { definiens:(_,children), .. } => Some(AnnotIter::Slice(children.iter())),
       Self::Symdecl { parsed_args,.. } => {
         let arr = [
           parsed_args.argtypes.as_ref().map(|(_,_,tps)| tps.iter() ),
@@ -205,7 +379,8 @@ impl TreeLike for STeXAnnot {
         let r  = arr.into_iter().filter_map(ident as _).flatten();
         Some(AnnotIter::Multiple4(r))
       }
-      Self::Symdef { parsed_args,notation_args,.. } => {
+      Self::Symdef { parsed_args,notation_args,.. } |
+      Self::Vardef { parsed_args,notation_args,.. } => {
         let arr = [
           parsed_args.argtypes.as_ref().map(|(_,_,tps)| tps.iter() ),
           parsed_args.tp.as_ref().map(|(_,_,tp)| tp.iter()),
@@ -235,20 +410,60 @@ pub enum DiagnosticLevel {
   Error,Warning,Info,Hint
 }
 
+/// Identifies the kinds of diagnostics that quick fixes can be offered for,
+/// together with the name they are about
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum DiagnosticCode {
+  /// A symbol name (in `\symname`, `\symref` etc.) that does not resolve
+  UnknownSymbol(Box<str>),
+  /// A module (in `\importmodule`, `\usemodule` etc.) that could not be found
+  ModuleNotFound(Box<str>),
+  /// The semantic macro (without backslash) of a known symbol that is not in scope
+  UnknownMacro(Box<str>)
+}
+impl DiagnosticCode {
+  #[must_use]
+  pub const fn code(&self) -> &'static str {
+    match self {
+      Self::UnknownSymbol(_) => "unknown-symbol",
+      Self::ModuleNotFound(_) => "module-not-found",
+      Self::UnknownMacro(_) => "unknown-macro"
+    }
+  }
+  #[must_use]
+  pub fn name(&self) -> &str {
+    match self {
+      Self::UnknownSymbol(n) | Self::ModuleNotFound(n) | Self::UnknownMacro(n) => n
+    }
+  }
+  /// Inverse of [`code`](Self::code) and [`name`](Self::name)
+  #[must_use]
+  pub fn parse(code:&str,name:&str) -> Option<Self> {
+    let name = name.into();
+    match code {
+      "unknown-symbol" => Some(Self::UnknownSymbol(name)),
+      "module-not-found" => Some(Self::ModuleNotFound(name)),
+      "unknown-macro" => Some(Self::UnknownMacro(name)),
+      _ => None
+    }
+  }
+}
+
 #[derive(PartialEq,Eq)]
 pub struct STeXDiagnostic {
   pub level: DiagnosticLevel,
   pub message: String,
-  pub range: SourceRange<LSPLineCol>
+  pub range: SourceRange<LSPLineCol>,
+  pub code: Option<DiagnosticCode>
 }
 
 #[must_use]
 pub fn quickparse<'a,S:STeXModuleStore>(uri:&'a DocumentURI,source: &'a str,path:&'a Path,backend:&'a AnyBackend,store:S) -> STeXParseDataI {
   let mut diagnostics = VecSet::new();
   let mut modules = SmallVec::new();
-  let err = |message,range,level| diagnostics.insert(STeXDiagnostic {
+  let err = |message,range,level,code| diagnostics.insert(STeXDiagnostic {
     level,
-    message, range
+    message, range, code
   });
   let mut parser = if S::FULL  { 
     LaTeXParser::with_rules(
@@ -278,4 +493,222 @@ pub fn quickparse<'a,S:STeXModuleStore>(uri:&'a DocumentURI,source: &'a str,path
 
   let annotations = STeXAnnot::from_tokens(parser, Some(&mut modules));
   STeXParseDataI { annotations, diagnostics, modules }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use immt_ontology::{narration::paragraphs::ParagraphKind, uris::{ArchiveURI, BaseURI, DocumentURI}};
+  use immt_system::backend::{Backend, GlobalBackend};
+  use immt_utils::prelude::TreeChildIter;
+
+  use super::{quickparse, structs::{ModuleReference, MorphismKind, STeXModuleStore}, AnnotIter, DiagnosticCode, DiagnosticLevel, STeXAnnot, STeXParseData, STeXParseDataI};
+
+  /// Enables all rules, but knows no modules other than the ones in the parsed document
+  struct NoModules;
+  impl STeXModuleStore for NoModules {
+    const FULL:bool = true;
+    fn get_module(&mut self,_:&ModuleReference) -> Option<STeXParseData> { None }
+  }
+
+  fn parse(source:&str) -> STeXParseDataI {
+    let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+    let uri = DocumentURI::from_archive_relpath(archive, "test.en.tex");
+    let path = Path::new("/some/archive/source/test.en.tex");
+    quickparse(&uri,source,path,&GlobalBackend::get().to_any(),NoModules)
+  }
+
+  fn annotations(data:&STeXParseDataI) -> Vec<&STeXAnnot> {
+    let iter : AnnotIter = data.annotations.iter().into();
+    <AnnotIter as TreeChildIter<STeXAnnot>>::dfs(iter).collect()
+  }
+
+  fn errors(data:&STeXParseDataI) -> Vec<&str> {
+    data.diagnostics.iter().filter(|d| d.level == DiagnosticLevel::Error).map(|d| d.message.as_str()).collect()
+  }
+
+  #[test]
+  fn definitions() {
+    let data = parse(r"
+\begin{smodule}{Test}
+  \symdef{plus}[args=2]{+}
+  \symdef{zero}{0}
+  \begin{sdefinition}[for={plus,zero}]
+    \definiendum{plus}{Addition} with neutral element \definame{zero} is
+    $\definiens[plus]{\plus{a}{b}}$; \definiendum{minus}{subtraction} is unknown.
+  \end{sdefinition}
+\end{smodule}
+");
+    assert_eq!(errors(&data),vec!["Unknown symbol minus"]);
+    let annots = annotations(&data);
+    let Some(STeXAnnot::Paragraph { kind, fors, .. }) = annots.iter().find(|a| matches!(a,STeXAnnot::Paragraph { .. })) else {
+      panic!("no paragraph in {annots:?}")
+    };
+    assert_eq!(*kind,ParagraphKind::Definition);
+    let fors : Vec<_> = fors.iter().map(|(s,_)| s.uri.name().last_name().as_ref()).collect();
+    assert_eq!(fors,vec!["plus","zero"]);
+
+    let definienda : Vec<_> = annots.iter().filter_map(|a| match a {
+      STeXAnnot::Definiendum { uri, .. } => Some(uri.uri.name().last_name().as_ref()),
+      _ => None
+    }).collect();
+    assert_eq!(definienda,vec!["plus"]);
+    assert!(annots.iter().any(|a| matches!(a,STeXAnnot::Definame { uri, .. } if uri.uri.name().last_name().as_ref() == "zero")));
+    let Some(STeXAnnot::Definiens { uri:Some((uri,_)), children, .. }) = annots.iter().find(|a| matches!(a,STeXAnnot::Definiens { .. })) else {
+      panic!("no definiens for plus in {annots:?}")
+    };
+    assert_eq!(uri.uri.name().last_name().as_ref(),"plus");
+    assert!(matches!(children.as_slice(),[STeXAnnot::SemanticMacro { argnum:2, .. }]));
+  }
+
+  #[test]
+  fn variables() {
+    let data = parse(r"
+\begin{smodule}{Test}
+  \vardef{vx}[name=x,args=1,type=\mathbb{N}]{x}
+  $\vx{a}$
+  {\vardef{vy}{y} $\vy$}
+  $\vy$
+  \varseq{vs}{1,n}{s}
+  $\vs$
+\end{smodule}
+");
+    assert!(errors(&data).is_empty(),"{:?}",errors(&data));
+    let annots = annotations(&data);
+    let vardefs : Vec<_> = annots.iter().filter_map(|a| match a {
+      STeXAnnot::Vardef { name, sequence, .. } => Some((name.to_string(),sequence.is_some())),
+      _ => None
+    }).collect();
+    assert_eq!(vardefs,vec![("x".to_string(),false),("vy".to_string(),false),("vs".to_string(),true)]);
+
+    // \vy is out of scope after the group
+    let vars : Vec<_> = annots.iter().filter_map(|a| match a {
+      STeXAnnot::VariableMacro { var, .. } => Some(var),
+      _ => None
+    }).collect();
+    let names : Vec<_> = vars.iter().map(|v| v.name.to_string()).collect();
+    assert_eq!(names,vec!["x","vy","vs"]);
+    assert_eq!(vars[0].argnum,1);
+    assert!(vars[0].tp.as_deref().is_some_and(|tp| tp.contains("mathbb")));
+    assert!(!vars[0].is_seq && vars[2].is_seq);
+  }
+
+  #[test]
+  fn structures() {
+    let data = parse(r"
+\begin{smodule}{Test}
+  \begin{mathstructure}{monoid}
+    \symdef{op}[args=2]{\circ}
+    \symdef{unit}{e}
+    $\op{\unit}{\unit}$
+  \end{mathstructure}
+  \begin{extstructure}{group}{monoid}
+    \symdef{inv}[args=1]{i}
+    $\op{\inv{\unit}}{\unit}$
+  \end{extstructure}
+  $\op{a}{b}$
+  \usestructure{monoid}
+  $\op{a}{b}$
+\end{smodule}
+");
+    assert!(errors(&data).is_empty(),"{:?}",errors(&data));
+    let annots = annotations(&data);
+    let Some(STeXAnnot::MathStructure { uri, macroname, .. }) = annots.iter().find(|a| matches!(a,STeXAnnot::MathStructure { .. })) else {
+      panic!("no structure in {annots:?}")
+    };
+    assert_eq!(uri.uri.name().last_name().as_ref(),"monoid");
+    assert_eq!(macroname.as_deref(),Some("monoid"));
+    let Some(STeXAnnot::Extension { uri, targets, .. }) = annots.iter().find(|a| matches!(a,STeXAnnot::Extension { .. })) else {
+      panic!("no extension in {annots:?}")
+    };
+    assert_eq!(uri.uri.name().last_name().as_ref(),"group");
+    assert!(matches!(targets.as_slice(),[(t,_)] if t.uri.name().last_name().as_ref() == "monoid"));
+    assert!(annots.iter().any(|a| matches!(a,STeXAnnot::UseStructure { structure, .. } if structure.uri.name().last_name().as_ref() == "monoid")));
+
+    // the fields are only in scope within the structure, its extensions and after \usestructure
+    let ops : Vec<_> = annots.iter().filter_map(|a| match a {
+      STeXAnnot::SemanticMacro { uri, .. } if uri.uri.name().last_name().as_ref() == "op" => Some(uri),
+      _ => None
+    }).collect();
+    assert_eq!(ops.len(),3);
+    assert!(ops.iter().all(|uri| uri.uri.module().name().last_name().as_ref() == "monoid"));
+  }
+
+  #[test]
+  fn morphisms() {
+    let data = parse(r"
+\begin{smodule}{Dom}
+  \symdef{plus}[args=2]{+}
+  \symdef{zero}{0}
+\end{smodule}
+\begin{smodule}{Test}
+  \begin{copymodule}{Dom}{copy}
+    \assign{zero}{\comp{0}}
+    \renamedecl[sum]{plus}{mysum}
+  \end{copymodule}
+  $\mysum{a}{b}$
+  \begin{interpretmodule}{Dom}{interp}
+    \assign{zero}{0}
+  \end{interpretmodule}
+  \realize{Dom}
+  \assign{plus}{x}
+\end{smodule}
+");
+    let errs = errors(&data);
+    assert_eq!(errs.len(),2,"{errs:?}");
+    assert!(errs.iter().any(|e| e.starts_with("Missing assignment for") && e.ends_with("=plus")),"{errs:?}");
+    assert!(errs.contains(&"\\assign is only allowed in a morphism"),"{errs:?}");
+
+    let annots = annotations(&data);
+    let morphisms : Vec<_> = annots.iter().filter_map(|a| match a {
+      STeXAnnot::Morphism { kind, uri, domain, children, .. } => Some((*kind,uri,domain,children)),
+      _ => None
+    }).collect();
+    let [(MorphismKind::Copy,Some(copy),domain,children),(MorphismKind::Interpret,Some(interp),_,_)] = morphisms.as_slice() else {
+      panic!("unexpected morphisms in {annots:?}")
+    };
+    assert_eq!(copy.name().last_name().as_ref(),"copy");
+    assert_eq!(interp.name().last_name().as_ref(),"interp");
+    assert_eq!(domain.uri.name().last_name().as_ref(),"Dom");
+    let [STeXAnnot::Assign { uri:assigned, .. },STeXAnnot::RenameDecl { uri:renamed, name:Some((name,_)), macroname, .. }] = children.as_slice() else {
+      panic!("unexpected children of the copy: {children:?}")
+    };
+    assert_eq!(assigned.uri.name().last_name().as_ref(),"zero");
+    assert_eq!(renamed.uri.name().last_name().as_ref(),"plus");
+    assert_eq!(name.last_name().as_ref(),"sum");
+    assert_eq!(macroname.0,"mysum");
+
+    // the renamed symbol of the copy is available via its new macro
+    assert!(annots.iter().any(|a| matches!(a,
+      STeXAnnot::SemanticMacro { uri, argnum:2, .. } if uri.uri.name().last_name().as_ref() == "sum"
+        && uri.uri.module().name().last_name().as_ref() == "copy"
+    )));
+    assert!(annots.iter().any(|a| matches!(a,STeXAnnot::Realize { module, .. } if module.uri == domain.uri)));
+  }
+
+  #[test]
+  fn meta_theories() {
+    let data = parse(r"
+\begin{smodule}{Meta}
+  \symdef{foo}{f}
+\end{smodule}
+\begin{smodule}[meta=Meta]{A}\end{smodule}
+\begin{smodule}[meta={}]{B}\end{smodule}
+\begin{smodule}{C}\end{smodule}
+\begin{smodule}[meta=Unknown]{D}\end{smodule}
+");
+    assert_eq!(errors(&data),vec!["Module Unknown not found"]);
+    assert!(data.diagnostics.iter().any(|d| d.level == DiagnosticLevel::Error && d.code == Some(DiagnosticCode::ModuleNotFound("Unknown".into()))));
+    let modules : Vec<_> = data.annotations.iter().filter_map(|a| match a {
+      STeXAnnot::Module { uri, meta_theory, .. } => Some((uri,meta_theory)),
+      _ => None
+    }).collect();
+    let [(meta,_),(_,a),(_,b),(_,c),(_,d)] = modules.as_slice() else {
+      panic!("unexpected modules in {:?}",data.annotations)
+    };
+    assert!(matches!(a,Some((m,Some(_))) if m.uri == **meta));
+    assert!(b.is_none());
+    assert!(matches!(c,Some((m,None)) if m.uri == *immt_ontology::metatheory::URI));
+    assert!(d.is_none());
+  }
+}
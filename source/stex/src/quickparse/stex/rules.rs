@@ -4,7 +4,7 @@
 
 use std::{borrow::Cow, collections::hash_map::Entry, num::NonZeroU8, path::{Path, PathBuf}};
 
-use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, DocumentURI, ModuleURI, Name, PathURI, PathURITrait, SymbolURI, URIRefTrait, URIWithLanguage}};
+use immt_ontology::{content::declarations::symbols::ArgSpec, languages::Language, narration::paragraphs::ParagraphKind, uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, DocumentURI, ModuleURI, Name, PathURI, PathURITrait, SymbolURI, URIRefTrait, URIWithLanguage}};
 use immt_system::backend::{AnyBackend, Backend, GlobalBackend};
 use immt_utils::{parsing::ParseStr, prelude::HMap, sourcerefs::{LSPLineCol, SourcePos, SourceRange}, vecmap::VecSet};
 use smallvec::SmallVec;
@@ -12,19 +12,19 @@ use smallvec::SmallVec;
 use crate::{quickparse::latex::{rules::{AnyEnv, AnyMacro, DynMacro, EnvironmentResult, EnvironmentRule, MacroResult, MacroRule}, Environment, FromLaTeXToken, Group, GroupState, Groups, LaTeXParser, Macro, OptMap, ParserState}, tex};
 use immt_utils::parsing::ParseSource;
 
-use super::{structs::{GroupKind, ModuleReference, ModuleRule, ModuleRules, STeXModuleStore, STeXParseState, STeXToken, SymbolReference, SymbolRule}, DiagnosticLevel, STeXParseData};
+use super::{structs::{GroupKind, MacroArg, ModuleReference, ModuleRule, MorphismKind, MorphismSymbol, ModuleRules, STeXModuleStore, STeXParseState, STeXToken, SymbolReference, SymbolRule, VariableReference}, DiagnosticCode, DiagnosticLevel, STeXParseData};
 
 #[must_use]
 #[allow(clippy::type_complexity)]
 pub fn all_rules<'a,
   MS:STeXModuleStore,
-  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
 >() -> [(&'static str,MacroRule<'a,
   ParseStr<'a,LSPLineCol>,
   STeXToken<LSPLineCol>,
   Err,
   STeXParseState<'a,LSPLineCol,MS>,
->);17] {[
+>);29] {[
   ("importmodule",importmodule as _),
   ("setmetatheory",setmetatheory as _),
   ("usemodule",usemodule as _),
@@ -42,19 +42,31 @@ pub fn all_rules<'a,
   ("sns",symnames as _),
   ("Symnames",Symnames as _),
   ("Sns",Symnames as _),
+  ("symref",symref as _),
+  ("sr",symref as _),
+  ("definiendum",definiendum as _),
+  ("definame",definame as _),
+  ("Definame",Definame as _),
+  ("definiens",definiens as _),
+  ("vardef",vardef as _),
+  ("varseq",varseq as _),
+  ("usestructure",usestructure as _),
+  ("realize",realize as _),
+  ("assign",assign as _),
+  ("renamedecl",renamedecl as _),
 ]}
 
 #[must_use]
 #[allow(clippy::type_complexity)]
 pub fn declarative_rules<'a,
   MS:STeXModuleStore,
-  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
 >() -> [(&'static str,MacroRule<'a,
   ParseStr<'a,LSPLineCol>,
   STeXToken<LSPLineCol>,
   Err,
   STeXParseState<'a,LSPLineCol,MS>
->);7] {[
+>);10] {[
   ("importmodule",importmodule as _),
   ("setmetatheory",setmetatheory as _),
   ("stexstyleassertion",stexstyleassertion as _),
@@ -62,47 +74,62 @@ pub fn declarative_rules<'a,
   ("stexstyleparagraph",stexstyleparagraph as _),
   ("symdecl",symdecl as _),
   ("symdef",symdef as _),
+  ("realize",realize as _),
+  ("assign",assign as _),
+  ("renamedecl",renamedecl as _),
 ]}
 
 #[must_use]
 #[allow(clippy::type_complexity)]
 pub fn all_env_rules<'a,
   MS:STeXModuleStore,
-  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
 >() -> [(&'static str,
   EnvironmentRule<'a,
     ParseStr<'a,LSPLineCol>,
     STeXToken<LSPLineCol>,
     Err,
     STeXParseState<'a,LSPLineCol,MS>
->);1] {[
-  ("smodule",(smodule_open as _, smodule_close as _))
+>);9] {[
+  ("smodule",(smodule_open as _, smodule_close as _)),
+  ("sdefinition",(sdefinition_open as _, sdefinition_close as _)),
+  ("sassertion",(sassertion_open as _, sassertion_close as _)),
+  ("sexample",(sexample_open as _, sexample_close as _)),
+  ("sparagraph",(sparagraph_open as _, sparagraph_close as _)),
+  ("mathstructure",(mathstructure_open as _, mathstructure_close as _)),
+  ("extstructure",(extstructure_open as _, extstructure_close as _)),
+  ("copymodule",(copymodule_open as _, copymodule_close as _)),
+  ("interpretmodule",(interpretmodule_open as _, interpretmodule_close as _))
 ]}
 
 #[must_use]
 #[allow(clippy::type_complexity)]
-pub fn declarative_env_rules<'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>() -> [(&'static str,EnvironmentRule<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>);1] {[
-  ("smodule",(smodule_open as _, smodule_close as _))
+pub fn declarative_env_rules<'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>() -> [(&'static str,EnvironmentRule<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>);5] {[
+  ("smodule",(smodule_open as _, smodule_close as _)),
+  ("mathstructure",(mathstructure_open as _, mathstructure_close as _)),
+  ("extstructure",(extstructure_open as _, extstructure_close as _)),
+  ("copymodule",(copymodule_open as _, copymodule_close as _)),
+  ("interpretmodule",(interpretmodule_open as _, interpretmodule_close as _))
 ]}
 
 macro_rules! stex {
   ($p:ident => @begin $($stuff:tt)+) => {
-    tex!(<{'a,Pos:SourcePos,MS:STeXModuleStore,Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)} E{'a,Pos,&'a str,STeXToken<Pos>} P{'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>} R{'a,Pos,&'a str,STeXToken<Pos>}>
+    tex!(<{'a,Pos:SourcePos,MS:STeXModuleStore,Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)} E{'a,Pos,&'a str,STeXToken<Pos>} P{'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>} R{'a,Pos,&'a str,STeXToken<Pos>}>
       $p => @begin $($stuff)*
     );
   };
   ($p:ident => $($stuff:tt)+) => {
-    tex!(<{'a,Pos:SourcePos,MS:STeXModuleStore,Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)} M{'a,Pos,&'a str} P{'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>} R{'a,Pos,&'a str,STeXToken<Pos>}>
+    tex!(<{'a,Pos:SourcePos,MS:STeXModuleStore,Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)} M{'a,Pos,&'a str} P{'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>} R{'a,Pos,&'a str,STeXToken<Pos>}>
       $p => $($stuff)*
     );
   };
   (LSP: $p:ident => @begin $($stuff:tt)+) => {
-    tex!(<{'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)} E{'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>} P{'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>} R{'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>}>
+    tex!(<{'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)} E{'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>} P{'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>} R{'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>}>
       $p => @begin $($stuff)*
     );
   };
   (LSP: $p:ident => $($stuff:tt)+) => {
-    tex!(<{'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)} M{'a,LSPLineCol,&'a str} P{'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>} R{'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>}>
+    tex!(<{'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)} M{'a,LSPLineCol,&'a str} P{'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>} R{'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>}>
       $p => $($stuff)*
     );
   };
@@ -118,7 +145,7 @@ stex!(LSP: p => importmodule[archive:str]{module:name} => {
             full_range:importmodule.range, token_range:importmodule.token_range
           })
         } else {
-          p.tokenizer.problem(importmodule.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error);
+          p.tokenizer.coded_problem(importmodule.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
           MacroResult::Simple(importmodule)
         }
     }
@@ -132,7 +159,7 @@ stex!(p => importmodule_deps[archive:str]{module:name} => {
             full_range:importmodule_deps.range, token_range:importmodule_deps.token_range
           })
         } else {
-          p.tokenizer.problem(importmodule_deps.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error);
+          p.tokenizer.coded_problem(importmodule_deps.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
           MacroResult::Simple(importmodule_deps)
         }
     }
@@ -148,7 +175,7 @@ stex!(LSP:p => usemodule[archive:str]{module:name} => {
           full_range:usemodule.range, token_range:usemodule.token_range
         })
       } else {
-        p.tokenizer.problem(usemodule.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error);
+        p.tokenizer.coded_problem(usemodule.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
         MacroResult::Simple(usemodule)
       }
   }
@@ -162,7 +189,7 @@ stex!(p => usemodule_deps[archive:str]{module:name} => {
           full_range:usemodule_deps.range,token_range:usemodule_deps.token_range
         })
       } else {
-        p.tokenizer.problem(usemodule_deps.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error);
+        p.tokenizer.coded_problem(usemodule_deps.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
         MacroResult::Simple(usemodule_deps)
       }
   }
@@ -176,7 +203,7 @@ stex!(p => setmetatheory[archive:str]{module:name} => {
           full_range:setmetatheory.range, token_range:setmetatheory.token_range
         })
       } else {
-        p.tokenizer.problem(setmetatheory.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error);
+        p.tokenizer.coded_problem(setmetatheory.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
         MacroResult::Simple(setmetatheory)
       }
   }
@@ -204,12 +231,12 @@ stex!(p => inputref('*'?_s)[archive:str]{filepath:name} => {
 fn get_module<'a,'b,
   Pos:SourcePos+'a,
   MS:STeXModuleStore,
-  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 >(p:&'b mut LaTeXParser<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>>)
   -> Option<(&'b ModuleURI,&'b mut Vec<ModuleRule<Pos>>)> {
     p.groups.iter_mut().rev().find_map(|g| match &mut g.kind {
       GroupKind::Module { uri, rules } => Some((&*uri,rules)),
-      GroupKind::None => None
+      GroupKind::None | GroupKind::Morphism { .. } => None
   })
 }
 
@@ -234,7 +261,7 @@ fn strip_comments(s:&str) -> Cow<'_,str> {
 #[derive(Debug,Clone)]
 pub struct SymdeclArgs<Pos:SourcePos,Tk> {
   pub name:Option<(String,SourceRange<Pos>,SourceRange<Pos>)>,
-  pub args:Option<(u8,SourceRange<Pos>,SourceRange<Pos>)>,
+  pub args:Option<(ArgSpec,SourceRange<Pos>,SourceRange<Pos>)>,
   pub tp:Option<(SourceRange<Pos>,SourceRange<Pos>,Vec<Tk>)>,
   pub df:Option<(SourceRange<Pos>,SourceRange<Pos>,Vec<Tk>)>,
   pub return_:Option<(SourceRange<Pos>,SourceRange<Pos>,Vec<Tk>)>,
@@ -265,13 +292,14 @@ fn symdecl_args<'a,Pos:SourcePos>(args:&mut OptMap<'a,Pos,&'a str,STeXToken<Pos>
     let str = strip_comments(val.str);
     let str = str.trim();
     if str.bytes().all(|b| b.is_ascii_digit()) && str.len() == 1 { 
-      let arg:u8 = str.parse().unwrap_or_else(|_| unreachable!());
+      let arg:ArgSpec = str.parse().unwrap_or_else(|_| unreachable!());
       ret.args = Some((arg,val.key_range,val.val_range));
     } else if str.bytes().all(|b| b == b'i' || b == b'a' || b == b'b' || b == b'B') {
       if str.len() > 9 {
         err(val.val_range.start,"Too many arguments".to_string());
       } else {
-        ret.args = Some((str.len() as u8,val.key_range,val.val_range));
+        let arg:ArgSpec = str.parse().unwrap_or_else(|_| unreachable!());
+        ret.args = Some((arg,val.key_range,val.val_range));
       }
     } else {
       err(val.val_range.start,format!("Invalid args value: >{}<",str));
@@ -334,7 +362,7 @@ stex!(p => symdecl('*'?star){name:name}[mut args:Map] => {
       };
       SymbolRule {
         uri,macroname:mn.map(|s| s.clone().into()),
-        has_df,has_tp,argnum:parsed_args.args.as_ref().map(|(a,_,_)| *a).unwrap_or_default()
+        has_df,has_tp,argnum:parsed_args.args.as_ref().map(|(a,_,_)| a.num()).unwrap_or_default()
       }
     },groups,symdecl.range) {
       let name_ranges = name.0.map(|r| (r,name.2));
@@ -413,7 +441,7 @@ stex!(p => symdef{name:name}[mut args:Map]{notation:M} => {
     };
     SymbolRule {
       uri,macroname:mn.map(|s| s.clone().into()),
-      has_df,has_tp,argnum:parsed_args.args.as_ref().map(|(a,_,_)| *a).unwrap_or_default()
+      has_df,has_tp,argnum:parsed_args.args.as_ref().map(|(a,_,_)| a.num()).unwrap_or_default()
     }
   },groups,symdef.range) {
     let name_ranges = name.0.map(|r| (r,name.2));
@@ -429,11 +457,70 @@ stex!(p => symdef{name:name}[mut args:Map]{notation:M} => {
 }
 );
 
+#[allow(clippy::type_complexity)]
+fn vardef_i<'a,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  m:Macro<'a,LSPLineCol,&'a str>,
+  name:(&'a str,SourceRange<LSPLineCol>),
+  mut args:OptMap<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  sequence:Option<(SourceRange<LSPLineCol>,Vec<STeXToken<LSPLineCol>>)>,
+  notation:(SourceRange<LSPLineCol>,Vec<STeXToken<LSPLineCol>>),
+  p:&mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) -> MacroResult<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>> {
+  let macroname = name.0;
+  let main_name_range = name.1;
+  let mut name = (None,name.0.to_string(),name.1);
+  let tp = args.inner.get(&"type").map(|v| strip_comments(v.str).trim().into());
+  let _ = args.inner.remove(&"bind");
+  let parsed_args = symdecl_args(&mut args,|a,b| p.tokenizer.problem(a,b,DiagnosticLevel::Error));
+  if let Some((n,k,v)) = &parsed_args.name {
+    let n = n.strip_prefix('{').unwrap_or(n.as_str());
+    let n = n.strip_suffix('}').unwrap_or(n);
+    name = (Some(*k),n.to_string(),*v);
+  }
+  let notation_args = notation_args(&mut args,|a,b| p.tokenizer.problem(a,b,DiagnosticLevel::Error));
+  for (k,v) in args.inner.iter() {
+    p.tokenizer.problem(v.key_range.start, format!("Unknown argument {}",k),DiagnosticLevel::Error);
+  }
+  let Ok(vname) : Result<Name,_> = name.1.parse() else {
+    p.tokenizer.problem(name.2.start, format!("Invalid uri segment {}",name.1),DiagnosticLevel::Error);
+    return MacroResult::Simple(m)
+  };
+  let var = VariableReference {
+    name:vname.clone(),
+    range:m.range,
+    argnum:parsed_args.args.as_ref().map(|(a,_,_)| a.num()).unwrap_or_default(),
+    is_seq:sequence.is_some(),
+    tp
+  };
+  // scoped to the current group, like the variable itself
+  p.add_macro_rule(Cow::Borrowed(macroname),Some(AnyMacro::Ext(DynMacro {
+    ptr:semantic_macro as _,
+    arg:MacroArg::Variable(var)
+  })));
+  let name_ranges = name.0.map(|r| (r,name.2));
+  MacroResult::Success(STeXToken::Vardef {
+    name:vname, main_name_range, name_ranges,
+    full_range:m.range,parsed_args:Box::new(parsed_args),
+    notation_args,sequence,notation,
+    token_range:m.token_range
+  })
+}
+
+stex!(LSP: p => vardef{name:name}[args:Map]{notation:M} => {
+  vardef_i(vardef,name,args,None,notation,p)
+});
+
+stex!(LSP: p => varseq{name:name}[args:Map]{seq:M}{notation:M} => {
+  vardef_i(varseq,name,args,Some(seq),notation,p)
+});
 
 stex!(LSP: p => symname[mut args:Map]{name:name} => {
   let (state,groups) = p.split();
   let Some(s) = state.get_symbol(groups,name.0) else {
-    p.tokenizer.problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error);
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
     return MacroResult::Simple(symname);
   };
   let pre = if let Some(val) = args.inner.remove(&"pre") {
@@ -452,10 +539,22 @@ stex!(LSP: p => symname[mut args:Map]{name:name} => {
   })
 });
 
+stex!(LSP: p => symref{name:name}{_:T} => {
+  let (state,groups) = p.split();
+  let Some(s) = state.get_symbol(groups,name.0) else {
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
+    return MacroResult::Simple(symref);
+  };
+  MacroResult::Success(STeXToken::Symref {
+    uri:s, full_range: symref.range, token_range: symref.token_range,
+    name_range: name.1
+  })
+});
+
 stex!(LSP: p => Symname[mut args:Map]{name:name} => {
   let (state,groups) = p.split();
   let Some(s) = state.get_symbol(groups,name.0) else {
-    p.tokenizer.problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error);
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
     return MacroResult::Simple(Symname);
   };
   let post = if let Some(val) = args.inner.remove(&"post") {
@@ -474,7 +573,7 @@ stex!(LSP: p => Symname[mut args:Map]{name:name} => {
 stex!(LSP: p => symnames[mut args:Map]{name:name} => {
   let (state,groups) = p.split();
   let Some(s) = state.get_symbol(groups,name.0) else {
-    p.tokenizer.problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error);
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
     return MacroResult::Simple(symnames);
   };
   let pre = if let Some(val) = args.inner.remove(&"pre") {
@@ -493,7 +592,7 @@ stex!(LSP: p => symnames[mut args:Map]{name:name} => {
 stex!(LSP: p => Symnames{name:name} => {
   let (state,groups) = p.split();
   let Some(s) = state.get_symbol(groups,name.0) else {
-    p.tokenizer.problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error);
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
     return MacroResult::Simple(Symnames);
   };
   MacroResult::Success(STeXToken::SymName { 
@@ -503,6 +602,526 @@ stex!(LSP: p => Symnames{name:name} => {
   })
 });
 
+stex!(LSP: p => definiendum{name:name}{_:T} => {
+  let (state,groups) = p.split();
+  let Some(s) = state.get_symbol(groups,name.0) else {
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
+    return MacroResult::Simple(definiendum);
+  };
+  MacroResult::Success(STeXToken::Definiendum {
+    uri:s, full_range: definiendum.range, token_range: definiendum.token_range,
+    name_range: name.1
+  })
+});
+
+stex!(LSP: p => definame[mut args:Map]{name:name} => {
+  let (state,groups) = p.split();
+  let Some(s) = state.get_symbol(groups,name.0) else {
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
+    return MacroResult::Simple(definame);
+  };
+  let pre = if let Some(val) = args.inner.remove(&"pre") {
+    Some((val.key_range,val.val_range,strip_comments(val.str).trim().to_string()))
+  } else { None };
+  let post = if let Some(val) = args.inner.remove(&"post") {
+    Some((val.key_range,val.val_range,strip_comments(val.str).trim().to_string()))
+  } else { None };
+  for (k,v) in args.inner.iter() {
+    p.tokenizer.problem(v.key_range.start, format!("Unknown argument {}",k),DiagnosticLevel::Error);
+  }
+  MacroResult::Success(STeXToken::Definame { 
+    uri:s, full_range: definame.range, token_range: definame.token_range,
+    name_range: name.1, 
+    mod_: super::structs::SymnameMod::PrePost{ pre, post }
+  })
+});
+
+stex!(LSP: p => Definame[mut args:Map]{name:name} => {
+  let (state,groups) = p.split();
+  let Some(s) = state.get_symbol(groups,name.0) else {
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
+    return MacroResult::Simple(Definame);
+  };
+  let post = if let Some(val) = args.inner.remove(&"post") {
+    Some((val.key_range,val.val_range,strip_comments(val.str).trim().to_string()))
+  } else { None };
+  for (k,v) in args.inner.iter() {
+    p.tokenizer.problem(v.key_range.start, format!("Unknown argument {}",k),DiagnosticLevel::Error);
+  }
+  MacroResult::Success(STeXToken::Definame { 
+    uri:s, full_range: Definame.range, token_range: Definame.token_range,
+    name_range: name.1, 
+    mod_: super::structs::SymnameMod::Cap{ post }
+  })
+});
+
+stex!(LSP: p => definiens[name:str] => {
+  let uri = name.and_then(|(name,range)| {
+    let (state,groups) = p.split();
+    let s = state.get_symbol(groups,name);
+    if s.is_none() {
+      p.tokenizer.coded_problem(range.start, format!("Unknown symbol {name}"),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.to_string().into()));
+    }
+    s.map(|s| (s,range))
+  });
+  let mode = p.tokenizer.mode;
+  p.open_group();
+  p.tokenizer.mode = crate::quickparse::tokenizer::Mode::Text;
+  let (_,children) = p.get_argument(&mut definiens);
+  p.tokenizer.mode = mode;
+  p.close_group();
+  MacroResult::Success(STeXToken::Definiens {
+    uri, full_range: definiens.range, token_range: definiens.token_range,
+    children
+  })
+});
+
+/// The comma-separated names in the value of a key like `for={a,b}`, with their ranges
+fn names_in<Pos:SourcePos>(val:&str,start:Pos) -> Vec<(&str,SourceRange<Pos>)> {
+  let mut ret = Vec::new();
+  let mut pos = start;
+  let mut current : Option<(usize,Pos,usize,Pos)> = None;
+  for (i,c) in val.char_indices() {
+    let before = pos;
+    pos.update(c);
+    match c {
+      ',' | '{' | '}' => if let Some((s,start,e,end)) = current.take() {
+        ret.push((&val[s..e],SourceRange { start, end }));
+      },
+      c if c.is_whitespace() => (),
+      c => {
+        let (s,start) = current.map_or((i,before),|(s,start,_,_)| (s,start));
+        current = Some((s,start,i + c.len_utf8(),pos));
+      }
+    }
+  }
+  if let Some((s,start,e,end)) = current {
+    ret.push((&val[s..e],SourceRange { start, end }));
+  }
+  ret
+}
+
+#[allow(clippy::type_complexity)]
+fn paragraph_open<'a,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  kind:ParagraphKind,
+  env:&mut Environment<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  mut opt:OptMap<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  p:&mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) {
+  let mut fors = Vec::new();
+  if let Some(val) = opt.inner.remove(&"for") {
+    for (name,range) in names_in(val.str,val.val_range.start) {
+      let (state,groups) = p.split();
+      if let Some(s) = state.get_symbol(groups,name) {
+        fors.push((s,range));
+      } else {
+        p.tokenizer.coded_problem(range.start, format!("Unknown symbol {name}"),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.to_string().into()));
+      }
+    }
+  }
+  env.children.push(STeXToken::Paragraph {
+    kind,full_range:env.begin.range,name_range:env.name_range,
+    fors,children:Vec::new()
+  });
+}
+
+fn paragraph_close<'a>(
+  mut env:Environment<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>
+) -> EnvironmentResult<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>> {
+  match env.children.first() {
+    Some(STeXToken::Paragraph { .. }) => {
+      let mut ch = env.children.drain(..);
+      let Some(STeXToken::Paragraph { kind,mut full_range,name_range,fors,mut children }) = ch.next() else {
+        unreachable!()
+      };
+      children.extend(ch);
+      if let Some(end) = env.end {
+        full_range.end = end.range.end;
+      }
+      EnvironmentResult::Success(STeXToken::Paragraph { kind,full_range,name_range,fors,children })
+    }
+    _ => EnvironmentResult::Simple(env)
+  }
+}
+
+stex!(LSP: p => @begin{sdefinition}([mut opt:Map]){
+  paragraph_open(ParagraphKind::Definition,sdefinition,opt,p);
+}{ paragraph_close(sdefinition) });
+
+stex!(LSP: p => @begin{sassertion}([mut opt:Map]){
+  paragraph_open(ParagraphKind::Assertion,sassertion,opt,p);
+}{ paragraph_close(sassertion) });
+
+stex!(LSP: p => @begin{sexample}([mut opt:Map]){
+  paragraph_open(ParagraphKind::Example,sexample,opt,p);
+}{ paragraph_close(sexample) });
+
+stex!(LSP: p => @begin{sparagraph}([mut opt:Map]){
+  paragraph_open(ParagraphKind::Paragraph,sparagraph,opt,p);
+}{ paragraph_close(sparagraph) });
+
+/// Declares the structure (or extension) `name` in the current module and turns the group
+/// of `env` into the module of the structure, so that its fields are only in scope within
+/// the environment; the fields of the `targets` of an extension are in scope as well.
+#[allow(clippy::type_complexity)]
+fn structure_open<'a,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  env:&mut Environment<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  name:(&'a str,SourceRange<LSPLineCol>),
+  mut opt:OptMap<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  targets:Option<(&'a str,SourceRange<LSPLineCol>)>,
+  p:&mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) {
+  if get_module(p).is_none() {
+    p.tokenizer.problem(env.begin.range.start, format!("{} is only allowed in a module",env.name),DiagnosticLevel::Error);
+    return
+  }
+  let macroname = if targets.is_none() { Some(name.0.to_string()) } else { None };
+  let mut sname = (name.0.to_string(),name.1);
+  if let Some(val) = opt.inner.remove(&"name") {
+    let n = strip_comments(val.str);
+    let n = n.trim();
+    let n = n.strip_prefix('{').unwrap_or(n);
+    sname = (n.strip_suffix('}').unwrap_or(n).to_string(),val.val_range);
+  }
+  for (k,v) in opt.inner.iter() {
+    p.tokenizer.problem(v.key_range.start, format!("Unknown argument {}",k),DiagnosticLevel::Error);
+  }
+  let mut resolved = Vec::new();
+  if let Some((targets,range)) = targets {
+    for (name,range) in names_in(targets,range.start) {
+      let (state,groups) = p.split();
+      if let Some(s) = state.get_symbol(groups,name) {
+        resolved.push((s,range));
+      } else {
+        p.tokenizer.coded_problem(range.start, format!("Unknown symbol {name}"),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.to_string().into()));
+      }
+    }
+  }
+  let Ok(fname) : Result<Name,_> = sname.0.parse() else {
+    p.tokenizer.problem(sname.1.start, format!("Invalid uri segment {}",sname.0),DiagnosticLevel::Error);
+    return
+  };
+  let (state,groups) = p.split();
+  let mn = macroname.as_ref();
+  let filepath = state.in_path.clone();
+  let range = env.begin.range;
+  let Some(uri) = state.add_rule(move |m| SymbolRule {
+    uri:SymbolReference { uri:m.clone() | fname, filepath, range },
+    macroname:mn.map(|s| s.clone().into()),
+    has_df:false,has_tp:false,argnum:0
+  },groups,range) else { return };
+  let mut rules = Vec::new();
+  for (s,_) in &resolved {
+    let module = ModuleReference::of_structure(s);
+    let (state,groups) = p.split();
+    state.add_use(&module,groups,range);
+    rules.push(ModuleRule::Import(module));
+  }
+  p.groups.last_mut().unwrap_or_else(|| unreachable!()).kind = GroupKind::Module{
+    uri:uri.uri.clone().into_module(),rules
+  };
+  env.children.push(if targets.is_some() {
+    STeXToken::Extension {
+      uri,targets:resolved,rules:ModuleRules::default(),
+      name_range:name.1,full_range:range,
+      children:Vec::new(),extstructure_range:env.name_range
+    }
+  } else {
+    STeXToken::MathStructure {
+      uri,macroname,rules:ModuleRules::default(),
+      name_range:name.1,full_range:range,
+      children:Vec::new(),mathstructure_range:env.name_range
+    }
+  });
+}
+
+fn structure_close<'a,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  mut env:Environment<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  p:&mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) -> EnvironmentResult<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>> {
+  let Some(g) = p.groups.last_mut() else {unreachable!()};
+  let GroupKind::Module { uri, rules } = std::mem::take(&mut g.kind) else {
+    return EnvironmentResult::Simple(env);
+  };
+  let new_rules = ModuleRules{ rules:rules.into()};
+  p.state.modules.push((uri,new_rules.clone()));
+  if !matches!(env.children.first(),Some(STeXToken::MathStructure { .. } | STeXToken::Extension { .. })) {
+    return EnvironmentResult::Simple(env)
+  }
+  let end = env.end.as_ref().map(|e| e.range.end);
+  let mut ch = env.children.drain(..);
+  let mut ret = ch.next().unwrap_or_else(|| unreachable!());
+  let (STeXToken::MathStructure { rules,full_range,children,.. } |
+       STeXToken::Extension { rules,full_range,children,.. }) = &mut ret else { unreachable!() };
+  *rules = new_rules;
+  children.extend(ch);
+  if let Some(end) = end {
+    full_range.end = end;
+  }
+  EnvironmentResult::Success(ret)
+}
+
+stex!(LSP: p => @begin{mathstructure}({name:name}[mut opt:Map]){
+  structure_open(mathstructure,name,opt,None,p);
+}{ structure_close(mathstructure,p) });
+
+stex!(LSP: p => @begin{extstructure}({name:name}[mut opt:Map]{targets:name}){
+  structure_open(extstructure,name,opt,Some(targets),p);
+}{ structure_close(extstructure,p) });
+
+stex!(LSP: p => usestructure{name:name} => {
+  let (state,groups) = p.split();
+  let Some(s) = state.get_symbol(groups,name.0) else {
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
+    return MacroResult::Simple(usestructure);
+  };
+  let (state,groups) = p.split();
+  state.add_use(&ModuleReference::of_structure(&s),groups,usestructure.range);
+  MacroResult::Success(STeXToken::UseStructure {
+    structure:s, structure_range:name.1,
+    full_range:usestructure.range, token_range:usestructure.token_range
+  })
+});
+
+/// Makes the symbols of the domain available within the environment and records them in
+/// the group, so that `\assign` and `\renamedecl` can be checked against them
+#[allow(clippy::type_complexity)]
+fn morphism_open<'a,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  kind:MorphismKind,
+  env:&mut Environment<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  archive:Option<(&'a str,SourceRange<LSPLineCol>)>,
+  module:(&'a str,SourceRange<LSPLineCol>),
+  name:(&'a str,SourceRange<LSPLineCol>),
+  p:&mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) {
+  let Some((current,_)) = get_module(p) else {
+    p.tokenizer.problem(env.begin.range.start, format!("{} is only allowed in a module",env.name),DiagnosticLevel::Error);
+    return
+  };
+  let Ok(uri) = current.clone() | name.0.trim() else {
+    p.tokenizer.problem(name.1.start, format!("Invalid uri segment {}",name.0),DiagnosticLevel::Error);
+    return
+  };
+  let (archive,archive_range) = archive.map_or((None,None),|(a,r)| (Some(ArchiveId::new(a)),Some(r)));
+  let Some(domain) = p.state.resolve_module(module.0, archive) else {
+    p.tokenizer.coded_problem(module.1.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
+    return
+  };
+  let symbols = p.state.all_symbols(&domain).into_iter().map(|rule| MorphismSymbol {
+    rule,name:None,macroname:None,assigned:false
+  }).collect();
+  let (state,groups) = p.split();
+  state.add_use(&domain,groups,env.begin.range);
+  p.groups.last_mut().unwrap_or_else(|| unreachable!()).kind = GroupKind::Morphism {
+    uri:uri.clone(),symbols
+  };
+  env.children.push(STeXToken::Morphism {
+    kind,uri:Some(uri),domain,archive_range,domain_range:module.1,
+    name_range:name.1,full_range:env.begin.range,
+    children:Vec::new(),morphism_range:env.name_range
+  });
+}
+
+/// Adds the symbols induced by the morphism (with their new names and macros) to the
+/// current module
+fn morphism_close<'a,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  env:Environment<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>>,
+  p:&mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) -> EnvironmentResult<'a,LSPLineCol,&'a str,STeXToken<LSPLineCol>> {
+  let Some(g) = p.groups.last_mut() else {unreachable!()};
+  let GroupKind::Morphism { uri, symbols } = std::mem::take(&mut g.kind) else {
+    return EnvironmentResult::Simple(env);
+  };
+  let Some(STeXToken::Morphism { kind, .. }) = env.children.first() else {
+    return EnvironmentResult::Simple(env);
+  };
+  let kind = *kind;
+  let range = env.begin.range;
+  let module = uri.into_module();
+  for s in symbols {
+    if kind == MorphismKind::Interpret && !s.assigned && !s.rule.has_df {
+      p.tokenizer.problem(range.start, format!("Missing assignment for {}",s.rule.uri.uri),DiagnosticLevel::Error);
+    }
+    let name = s.name.unwrap_or_else(|| s.rule.uri.uri.name().clone());
+    let filepath = p.state.in_path.clone();
+    let (state,groups) = p.split();
+    let _ = state.add_rule(|_| SymbolRule {
+      uri:SymbolReference { uri:module.clone() | name, filepath, range },
+      macroname:s.macroname,
+      has_tp:s.rule.has_tp,
+      has_df:s.rule.has_df || s.assigned,
+      argnum:s.rule.argnum
+    },groups,range);
+  }
+  morphism_deps_close(env)
+}
+
+stex!(LSP: p => @begin{copymodule}([archive:str]{module:name}{name:name}){
+  morphism_open(MorphismKind::Copy,copymodule,archive,module,name,p);
+}{ morphism_close(copymodule,p) });
+
+stex!(LSP: p => @begin{interpretmodule}([archive:str]{module:name}{name:name}){
+  morphism_open(MorphismKind::Interpret,interpretmodule,archive,module,name,p);
+}{ morphism_close(interpretmodule,p) });
+
+stex!(p => @begin{copymodule_deps}([archive:str]{module:name}{name:name}){
+  morphism_deps_open(MorphismKind::Copy,copymodule_deps,archive,module,name,p);
+}{ morphism_deps_close(copymodule_deps) });
+
+stex!(p => @begin{interpretmodule_deps}([archive:str]{module:name}{name:name}){
+  morphism_deps_open(MorphismKind::Interpret,interpretmodule_deps,archive,module,name,p);
+}{ morphism_deps_close(interpretmodule_deps) });
+
+/// Only resolves the domain; used for extracting dependencies
+#[allow(clippy::type_complexity)]
+fn morphism_deps_open<'a,
+  Pos:SourcePos,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  kind:MorphismKind,
+  env:&mut Environment<'a,Pos,&'a str,STeXToken<Pos>>,
+  archive:Option<(&'a str,SourceRange<Pos>)>,
+  module:(&'a str,SourceRange<Pos>),
+  name:(&'a str,SourceRange<Pos>),
+  p:&mut LaTeXParser<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>>
+) {
+  let (archive,archive_range) = archive.map_or((None,None),|(a,r)| (Some(ArchiveId::new(a)),Some(r)));
+  let Some(domain) = p.state.resolve_module(module.0, archive) else {
+    p.tokenizer.coded_problem(module.1.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
+    return
+  };
+  env.children.push(STeXToken::Morphism {
+    kind,uri:None,domain,archive_range,domain_range:module.1,
+    name_range:name.1,full_range:env.begin.range,
+    children:Vec::new(),morphism_range:env.name_range
+  });
+}
+
+fn morphism_deps_close<'a,Pos:SourcePos>(
+  mut env:Environment<'a,Pos,&'a str,STeXToken<Pos>>
+) -> EnvironmentResult<'a,Pos,&'a str,STeXToken<Pos>> {
+  if !matches!(env.children.first(),Some(STeXToken::Morphism { .. })) {
+    return EnvironmentResult::Simple(env)
+  }
+  let end = env.end.as_ref().map(|e| e.range.end);
+  let mut ch = env.children.drain(..);
+  let mut ret = ch.next().unwrap_or_else(|| unreachable!());
+  let STeXToken::Morphism { full_range,children,.. } = &mut ret else { unreachable!() };
+  children.extend(ch);
+  if let Some(end) = end {
+    full_range.end = end;
+  }
+  EnvironmentResult::Success(ret)
+}
+
+stex!(LSP: p => realize[archive:str]{module:name} => {
+  let (archive,archive_range) = archive.map_or((None,None),|(a,r)| (Some(ArchiveId::new(a)),Some(r)));
+  if let Some(r) = p.state.resolve_module(module.0, archive) {
+    let (state,groups) = p.split();
+    state.add_import(&r, groups,realize.range);
+    MacroResult::Success(STeXToken::Realize {
+      archive_range, path_range:module.1,module:r,
+      full_range:realize.range, token_range:realize.token_range
+    })
+  } else {
+    p.tokenizer.coded_problem(realize.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
+    MacroResult::Simple(realize)
+  }
+});
+
+stex!(p => realize_deps[archive:str]{module:name} => {
+  let (archive,archive_range) = archive.map_or((None,None),|(a,r)| (Some(ArchiveId::new(a)),Some(r)));
+  if let Some(r) = p.state.resolve_module(module.0, archive) {
+    MacroResult::Success(STeXToken::Realize {
+      archive_range, path_range:module.1,module:r,
+      full_range:realize_deps.range, token_range:realize_deps.token_range
+    })
+  } else {
+    p.tokenizer.coded_problem(realize_deps.range.start, format!("Module {} not found",module.0),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(module.0.to_string().into()));
+    MacroResult::Simple(realize_deps)
+  }
+});
+
+/// The symbol `name` refers to, and its entry in the innermost morphism
+#[allow(clippy::type_complexity)]
+fn morphism_symbol<'a,'b,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  macroname:&str,
+  name:(&'a str,SourceRange<LSPLineCol>),
+  p:&'b mut LaTeXParser<'a,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,STeXParseState<'a,LSPLineCol,MS>>
+) -> Option<(SymbolReference<LSPLineCol>,&'b mut MorphismSymbol<LSPLineCol>)> {
+  let (state,groups) = p.split();
+  let Some(s) = state.get_symbol(groups,name.0) else {
+    p.tokenizer.coded_problem(name.1.start, format!("Unknown symbol {}",name.0),DiagnosticLevel::Error,DiagnosticCode::UnknownSymbol(name.0.to_string().into()));
+    return None
+  };
+  let Some(symbols) = p.groups.iter_mut().rev().find_map(|g| match &mut g.kind {
+    GroupKind::Morphism { symbols,.. } => Some(symbols),
+    _ => None
+  }) else {
+    p.tokenizer.problem(name.1.start, format!("\\{macroname} is only allowed in a morphism"),DiagnosticLevel::Error);
+    return None
+  };
+  let Some(ms) = symbols.iter_mut().find(|ms| ms.rule.uri.uri == s.uri) else {
+    p.tokenizer.problem(name.1.start, format!("{} is not in the domain of the morphism",s.uri),DiagnosticLevel::Error);
+    return None
+  };
+  Some((s,ms))
+}
+
+stex!(LSP: p => assign{name:name}{df:M} => {
+  let Some((s,ms)) = morphism_symbol("assign",name,p) else {
+    return MacroResult::Simple(assign)
+  };
+  if std::mem::replace(&mut ms.assigned,true) {
+    p.tokenizer.problem(name.1.start, format!("{} is already assigned",s.uri),DiagnosticLevel::Warning);
+  }
+  MacroResult::Success(STeXToken::Assign {
+    uri:s, symbol_range:name.1,
+    full_range:assign.range, token_range:assign.token_range,
+    definiens:df
+  })
+});
+
+stex!(LSP: p => renamedecl[newname:str]{name:name}{macroname:name} => {
+  let newname = match newname.map(|(n,r)| (n.trim().parse::<Name>(),n,r)) {
+    Some((Ok(n),_,r)) => Some((n,r)),
+    Some((Err(_),n,r)) => {
+      p.tokenizer.problem(r.start, format!("Invalid uri segment {n}"),DiagnosticLevel::Error);
+      return MacroResult::Simple(renamedecl)
+    }
+    None => None
+  };
+  let Some((s,ms)) = morphism_symbol("renamedecl",name,p) else {
+    return MacroResult::Simple(renamedecl)
+  };
+  let macroname = (macroname.0.trim().to_string(),macroname.1);
+  ms.name = newname.as_ref().map(|(n,_)| n.clone());
+  ms.macroname = Some(macroname.0.as_str().into());
+  MacroResult::Success(STeXToken::RenameDecl {
+    uri:s, symbol_range:name.1,
+    name:newname, macroname,
+    full_range:renamedecl.range, token_range:renamedecl.token_range
+  })
+});
 
 lazy_static::lazy_static! {
   static ref META_REL_PATH:std::sync::Arc<str> = "Metatheory.en.tex".into(); 
@@ -512,6 +1131,28 @@ lazy_static::lazy_static! {
   );
 }
 
+/// Resolves the value of `meta=` in an `smodule`, which is of the form `[archive?]path?Module`
+fn resolve_meta<'a,
+  Pos:SourcePos,
+  MS:STeXModuleStore,
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)
+>(
+  val:&str,range:SourceRange<Pos>,
+  p:&mut LaTeXParser<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>>
+) -> Option<(ModuleReference,Option<SourceRange<Pos>>)> {
+  let val = val.strip_prefix('{').and_then(|v| v.strip_suffix('}')).unwrap_or(val).trim();
+  let (archive,module) = match val.split_once('?') {
+    Some((a,rest)) if rest.contains('?') => (Some(ArchiveId::new(a)),rest),
+    _ => (None,val)
+  };
+  if let Some(r) = p.state.resolve_module(module, archive) {
+    Some((r,Some(range)))
+  } else {
+    p.tokenizer.coded_problem(range.start, format!("Module {val} not found"),DiagnosticLevel::Error,DiagnosticCode::ModuleNotFound(val.to_string().into()));
+    None
+  }
+}
+
 stex!(p => @begin{smodule}([opt]{name:name}){
       let opt = opt.as_keyvals();
       let sig = opt.get(&"sig").and_then(|v| v.val.parse().ok().map(|i| (i,v.val_range)));
@@ -524,14 +1165,14 @@ stex!(p => @begin{smodule}([opt]{name:name}){
         p.tokenizer.problem(name.1.start, format!("Invalid uri segment {}",name.1),DiagnosticLevel::Error);
         return ()
       };
-      let meta_theory = match opt.get(&"meta").map(|v| v.val) {
+      let meta_theory = match opt.get(&"meta").map(|v| (v.val,v.val_range)) {
         None => Some((ModuleReference { 
           uri:immt_ontology::metatheory::URI.clone(),
           rel_path:Some(META_REL_PATH.clone()),
           full_path:META_FULL_PATH.clone()
         },None)),
-        Some(""|"{}") => None,
-        Some(o) => todo!()
+        Some((""|"{}",_)) => None,
+        Some((o,range)) => resolve_meta(o,range,p)
       };
       p.groups.last_mut().unwrap_or_else(|| unreachable!()).kind = GroupKind::Module{
         uri:uri.clone(),rules:Vec::new()
@@ -578,14 +1219,14 @@ stex!(p => @begin{smodule_deps}([opt]{name:name}){
         p.tokenizer.problem(name.1.start, format!("Invalid uri segment {}",name.1),DiagnosticLevel::Error);
         return ()
       };
-      let meta_theory = match opt.get(&"meta").map(|v| v.val) {
+      let meta_theory = match opt.get(&"meta").map(|v| (v.val,v.val_range)) {
         None => Some((ModuleReference{ 
           uri:immt_ontology::metatheory::URI.clone(),
           rel_path:Some(META_REL_PATH.clone()),
           full_path:META_FULL_PATH.clone()
         },None)),
-        Some(""|"{}") => None,
-        Some(o) => todo!()
+        Some((""|"{}",_)) => None,
+        Some((o,range)) => resolve_meta(o,range,p)
       };
       //p.state.push_module(uri.clone());
       smodule_deps.children.push(STeXToken::Module{
@@ -616,17 +1257,24 @@ stex!(p => @begin{smodule_deps}([opt]{name:name}){
 pub(super) fn semantic_macro<'a,
   MS:STeXModuleStore,
   Pos:SourcePos + 'a,
-  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel),
->((uri,argnum):&(SymbolReference<Pos>,u8),
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>),
+>(arg:&MacroArg<Pos>,
   m:Macro<'a, Pos, &'a str>,
   _parser: &mut LaTeXParser<'a,ParseStr<'a,Pos>, STeXToken<Pos>, Err, STeXParseState<'a,Pos,MS>>
 ) -> MacroResult<'a, Pos, &'a str, STeXToken<Pos>> {
-  MacroResult::Success(STeXToken::SemanticMacro { 
-    uri:uri.clone(), 
-    argnum: *argnum, 
-    full_range: m.range, 
-    token_range: m.token_range 
-  })
+  match arg {
+    MacroArg::Symbol(uri,argnum) => MacroResult::Success(STeXToken::SemanticMacro { 
+      uri:uri.clone(), 
+      argnum: *argnum, 
+      full_range: m.range, 
+      token_range: m.token_range 
+    }),
+    MacroArg::Variable(var) => MacroResult::Success(STeXToken::VariableMacro {
+      var:var.clone(),
+      full_range: m.range,
+      token_range: m.token_range
+    })
+  }
 }
 
 
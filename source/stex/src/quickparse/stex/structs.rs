@@ -1,13 +1,13 @@
 use std::{borrow::Cow, collections::hash_map::Entry, path::{Path, PathBuf}};
 
-use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, ContentURITrait, DocumentURI, ModuleURI, Name, PathURI, PathURITrait, SymbolURI, URIRefTrait}};
+use immt_ontology::{languages::Language, narration::paragraphs::ParagraphKind, uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, ContentURITrait, DocumentURI, ModuleURI, Name, PathURI, PathURITrait, SymbolURI, URIRefTrait}};
 use immt_system::backend::{AnyBackend, Backend};
 use immt_utils::{parsing::ParseStr, prelude::HMap, sourcerefs::{LSPLineCol, SourcePos, SourceRange}, vecmap::VecSet};
 use smallvec::SmallVec;
 
 use crate::quickparse::latex::{rules::{AnyEnv, AnyMacro, DynMacro}, Environment, FromLaTeXToken, Group, GroupState, Groups, LaTeXParser, Macro, ParserState};
 
-use super::{rules::{NotationArgs, SymdeclArgs}, DiagnosticLevel, STeXParseData};
+use super::{rules::{NotationArgs, SymdeclArgs}, DiagnosticCode, DiagnosticLevel, STeXParseData};
 
 
 #[allow(clippy::large_enum_variant)]
@@ -78,6 +78,24 @@ pub enum STeXToken<Pos:SourcePos> {
     full_range: SourceRange<Pos>,
     token_range: SourceRange<Pos>
   },
+  #[allow(clippy::type_complexity)]
+  Vardef {
+    name:Name,
+    main_name_range:SourceRange<Pos>,
+    name_ranges:Option<(SourceRange<Pos>,SourceRange<Pos>)>,
+    full_range: SourceRange<Pos>,
+    parsed_args:Box<SymdeclArgs<Pos,STeXToken<Pos>>>,
+    notation_args:NotationArgs<Pos,STeXToken<Pos>>,
+    /// The range argument of a `\varseq`
+    sequence:Option<(SourceRange<Pos>,Vec<STeXToken<Pos>>)>,
+    notation:(SourceRange<Pos>,Vec<STeXToken<Pos>>),
+    token_range: SourceRange<Pos>
+  },
+  VariableMacro {
+    var:VariableReference<Pos>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>
+  },
   SymName {
     uri:SymbolReference<Pos>,
     full_range: SourceRange<Pos>,
@@ -85,6 +103,96 @@ pub enum STeXToken<Pos:SourcePos> {
     name_range:SourceRange<Pos>,
     mod_:SymnameMod<Pos>
   },
+  Symref {
+    uri:SymbolReference<Pos>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>,
+    name_range:SourceRange<Pos>
+  },
+  Paragraph {
+    kind:ParagraphKind,
+    full_range: SourceRange<Pos>,
+    name_range: SourceRange<Pos>,
+    fors:Vec<(SymbolReference<Pos>,SourceRange<Pos>)>,
+    children:Vec<STeXToken<Pos>>
+  },
+  Definiendum {
+    uri:SymbolReference<Pos>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>,
+    name_range:SourceRange<Pos>
+  },
+  Definame {
+    uri:SymbolReference<Pos>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>,
+    name_range:SourceRange<Pos>,
+    mod_:SymnameMod<Pos>
+  },
+  Definiens {
+    uri:Option<(SymbolReference<Pos>,SourceRange<Pos>)>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>,
+    children:Vec<STeXToken<Pos>>
+  },
+  MathStructure {
+    uri:SymbolReference<Pos>,
+    macroname:Option<String>,
+    /// The fields, as the rules of the module of the structure
+    rules:ModuleRules<Pos>,
+    name_range:SourceRange<Pos>,
+    full_range: SourceRange<Pos>,
+    children:Vec<STeXToken<Pos>>,
+    mathstructure_range: SourceRange<Pos>
+  },
+  Extension {
+    uri:SymbolReference<Pos>,
+    targets:Vec<(SymbolReference<Pos>,SourceRange<Pos>)>,
+    rules:ModuleRules<Pos>,
+    name_range:SourceRange<Pos>,
+    full_range: SourceRange<Pos>,
+    children:Vec<STeXToken<Pos>>,
+    extstructure_range: SourceRange<Pos>
+  },
+  UseStructure {
+    structure:SymbolReference<Pos>,
+    structure_range:SourceRange<Pos>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>
+  },
+  Morphism {
+    kind:MorphismKind,
+    uri:Option<SymbolURI>,
+    domain:ModuleReference,
+    archive_range: Option<SourceRange<Pos>>,
+    domain_range:SourceRange<Pos>,
+    name_range:SourceRange<Pos>,
+    full_range: SourceRange<Pos>,
+    children:Vec<STeXToken<Pos>>,
+    morphism_range: SourceRange<Pos>
+  },
+  Realize {
+    archive_range: Option<SourceRange<Pos>>,
+    path_range: SourceRange<Pos>,
+    module: ModuleReference,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>
+  },
+  Assign {
+    uri:SymbolReference<Pos>,
+    symbol_range:SourceRange<Pos>,
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>,
+    definiens:(SourceRange<Pos>,Vec<STeXToken<Pos>>)
+  },
+  RenameDecl {
+    uri:SymbolReference<Pos>,
+    symbol_range:SourceRange<Pos>,
+    name:Option<(Name,SourceRange<Pos>)>,
+    macroname:(String,SourceRange<Pos>),
+    full_range: SourceRange<Pos>,
+    token_range: SourceRange<Pos>
+  },
   Vec(Vec<STeXToken<Pos>>),
 }
 #[derive(Debug)]
@@ -133,6 +241,43 @@ pub struct SymbolReference<Pos:SourcePos> {
   pub range: SourceRange<Pos>
 }
 
+/// A variable declared via `\vardef` or `\varseq`; in scope until the end of the
+/// current TeX group
+#[derive(Debug,Clone)]
+pub struct VariableReference<Pos:SourcePos> {
+  pub name: Name,
+  pub range: SourceRange<Pos>,
+  pub argnum: u8,
+  pub is_seq: bool,
+  /// The value of the `type` key, as written in the declaration
+  pub tp: Option<std::sync::Arc<str>>
+}
+
+/// The argument of the macro rules for semantic macros and variables
+#[derive(Debug,Clone)]
+pub enum MacroArg<Pos:SourcePos> {
+  Symbol(SymbolReference<Pos>,u8),
+  Variable(VariableReference<Pos>)
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum MorphismKind {
+  /// `copymodule`
+  Copy,
+  /// `interpretmodule`; every symbol of the domain without definiens needs to be assigned
+  Interpret
+}
+
+/// A symbol in the domain of the morphism currently being parsed, and what has been
+/// assigned to it so far
+#[derive(Debug,Clone)]
+pub struct MorphismSymbol<Pos:SourcePos> {
+  pub rule:SymbolRule<Pos>,
+  pub name:Option<Name>,
+  pub macroname:Option<std::sync::Arc<str>>,
+  pub assigned:bool
+}
+
 #[derive(Debug,Clone)]
 pub struct ModuleReference {
   pub uri:ModuleURI,
@@ -140,6 +285,16 @@ pub struct ModuleReference {
   pub full_path:Option<std::sync::Arc<Path>>
 }
 impl ModuleReference {
+  /// The module containing the fields of a `mathstructure` or `extstructure`
+  #[must_use]
+  pub fn of_structure<Pos:SourcePos>(structure:&SymbolReference<Pos>) -> Self {
+    Self {
+      uri:structure.uri.clone().into_module(),
+      rel_path:None,
+      full_path:structure.filepath.clone()
+    }
+  }
+
   #[must_use]
   pub fn doc_uri(&self) -> Option<DocumentURI> {
     let rel_path = &**self.rel_path.as_ref()?;
@@ -187,11 +342,11 @@ pub struct SymbolRule<Pos:SourcePos> {
   pub argnum:u8
 }
 impl<Pos:SourcePos> SymbolRule<Pos> {
-  fn as_rule<'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)>(&self) -> Option<(Cow<'a,str>,AnyMacro<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>>)> {
+  fn as_rule<'a,MS:STeXModuleStore,Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)>(&self) -> Option<(Cow<'a,str>,AnyMacro<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>>)> {
     self.macroname.as_ref().map(|m|
       (m.to_string().into(),AnyMacro::Ext(DynMacro {
         ptr:super::rules::semantic_macro as _,
-        arg:(self.uri.clone(),self.argnum)
+        arg:MacroArg::Symbol(self.uri.clone(),self.argnum)
       }))
     )
   }
@@ -231,7 +386,7 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     }
     None
   }
-  fn load_rules<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(
+  fn load_rules<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(
     mod_ref:ModuleReference,
     irules:ModuleRules<LSPLineCol>,
     prev:&[STeXGroup<'a,MS,LSPLineCol,Err>],
@@ -262,7 +417,7 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     semantic_rules.push(either::Either::Right((mod_ref,irules)))
   }
 
-  fn has_module<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(
+  fn has_module<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(
     prev:&[STeXGroup<'a,MS,LSPLineCol,Err>],
     current:&Vec<either::Either<SymbolRule<LSPLineCol>,(ModuleReference,ModuleRules<LSPLineCol>)>>,
     mod_ref:&ModuleReference
@@ -278,7 +433,7 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     false
   }
 
-  pub fn add_use<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(&mut self,module:&ModuleReference,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,range:SourceRange<LSPLineCol>) {
+  pub fn add_use<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(&mut self,module:&ModuleReference,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,range:SourceRange<LSPLineCol>) {
     let mut groups_ls = &mut **groups.groups;
     assert!(!groups_ls.is_empty());
     let i = groups_ls.len() -1;
@@ -295,7 +450,7 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     }
   }
 
-  pub fn add_import<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(&mut self,module:&ModuleReference,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,range:SourceRange<LSPLineCol>) {
+  pub fn add_import<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(&mut self,module:&ModuleReference,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,range:SourceRange<LSPLineCol>) {
     let mut groups_ls = &mut **groups.groups;
     let Some(i) = groups_ls.iter().enumerate().rev().find_map(|(i,g)| if let GroupKind::Module { rules,.. } = &g.kind { Some(i) } else { None }) else {
       groups.tokenizer.problem(range.start, "\\importmodule is only allowed in a module".to_string(),DiagnosticLevel::Error);
@@ -321,7 +476,26 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     }
   }
 
-  fn get_symbol_macro_or_name<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(&self,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,namestr:&str) -> Option<SymbolReference<LSPLineCol>> {
+  /// The symbols declared in `module` or any module it (transitively) imports
+  pub fn all_symbols(&mut self,module:&ModuleReference) -> Vec<SymbolRule<LSPLineCol>> {
+    let mut done = Vec::new();
+    let mut ret = Vec::new();
+    self.collect_symbols(module,&mut done,&mut ret);
+    ret
+  }
+  fn collect_symbols(&mut self,module:&ModuleReference,done:&mut Vec<ModuleURI>,ret:&mut Vec<SymbolRule<LSPLineCol>>) {
+    if done.contains(&module.uri) { return }
+    done.push(module.uri.clone());
+    let Some(rules) = self.load_module(module) else { return };
+    for r in rules.rules.iter() {
+      match r {
+        ModuleRule::Import(m) => self.collect_symbols(m,done,ret),
+        ModuleRule::Symbol(s) => ret.push(s.clone())
+      }
+    }
+  }
+
+  fn get_symbol_macro_or_name<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(&self,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,namestr:&str) -> Option<SymbolReference<LSPLineCol>> {
     for g in groups.groups.iter().rev() {
       for r in g.semantic_rules.iter().rev() {
         match r {
@@ -390,7 +564,7 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     return id.ends_with(path);
   }
 
-  fn get_symbol_complex<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(&self,
+  fn get_symbol_complex<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(&self,
     groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,
     symbol:&str,module:&str,path:Option<&str>
   ) -> Option<SymbolReference<LSPLineCol>> {
@@ -417,7 +591,7 @@ impl<'a,MS:STeXModuleStore> STeXParseState<'a,LSPLineCol,MS> {
     None
   }
 
-  pub fn get_symbol<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel)>(&self,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,namestr:&str) -> Option<SymbolReference<LSPLineCol>> {
+  pub fn get_symbol<Err:FnMut(String,SourceRange<LSPLineCol>,DiagnosticLevel,Option<DiagnosticCode>)>(&self,groups:Groups<'a,'_,ParseStr<'a,LSPLineCol>,STeXToken<LSPLineCol>,Err,Self>,namestr:&str) -> Option<SymbolReference<LSPLineCol>> {
     let mut steps = namestr.trim().split('?').rev();
     let name = steps.next()?;
     
@@ -439,7 +613,7 @@ impl<'a,Pos:SourcePos,MS:STeXModuleStore> STeXParseState<'a,Pos,MS> {
     Self { archive, in_path:in_path.map(Into::into), doc_uri:uri, language, backend, modules:SmallVec::new(), module_store: on_module }
   }
 
-  pub fn add_rule<Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)>(&mut self,f:impl FnOnce(&ModuleURI) -> SymbolRule<Pos>,groups:Groups<'a,'_,ParseStr<'a,Pos>,STeXToken<Pos>,Err,Self>,range:SourceRange<Pos>) -> Option<SymbolReference<Pos>> {
+  pub fn add_rule<Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)>(&mut self,f:impl FnOnce(&ModuleURI) -> SymbolRule<Pos>,groups:Groups<'a,'_,ParseStr<'a,Pos>,STeXToken<Pos>,Err,Self>,range:SourceRange<Pos>) -> Option<SymbolReference<Pos>> {
     for g in groups.groups.iter_mut().rev() {
       match &mut g.kind {
         GroupKind::Module { uri, rules,.. } => {
@@ -469,7 +643,7 @@ impl<'a,Pos:SourcePos,MS:STeXModuleStore> STeXParseState<'a,Pos,MS> {
 
   #[allow(clippy::case_sensitive_file_extension_comparisons)]
   #[allow(clippy::needless_pass_by_value)]
-  pub(super) fn resolve_module(&self,module:&'a str,archive:Option<ArchiveId>) -> Option<ModuleReference> {
+  pub(super) fn resolve_module(&self,module:&str,archive:Option<ArchiveId>) -> Option<ModuleReference> {
     if let Some(m) = self.find_module(module) {
       return Some(ModuleReference { uri:m.clone(),rel_path:None,full_path:self.in_path.clone() });
     }
@@ -586,6 +760,10 @@ pub enum GroupKind<Pos:SourcePos> {
   Module{
     uri:ModuleURI,
     rules: Vec<ModuleRule<Pos>>
+  },
+  Morphism{
+    uri:SymbolURI,
+    symbols: Vec<MorphismSymbol<Pos>>
   }
 }
 
@@ -593,7 +771,7 @@ pub enum GroupKind<Pos:SourcePos> {
 pub struct STeXGroup<'a,
   MS:STeXModuleStore,
   Pos:SourcePos+'a,
-  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > {
   pub inner: Group<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>>,
   pub kind:GroupKind<Pos>,
@@ -604,7 +782,7 @@ pub struct STeXGroup<'a,
 impl<'a,
   MS:STeXModuleStore,
   Pos:SourcePos+'a,
-  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > GroupState<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err,STeXParseState<'a,Pos,MS>> for STeXGroup<'a,MS,Pos,Err> {
   #[inline]
   fn new(parent:Option<&mut Self>) -> Self {
@@ -645,8 +823,8 @@ impl<'a,
 impl<'a,
   MS:STeXModuleStore,
   Pos:SourcePos+'a,
-  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel)
+  Err:FnMut(String,SourceRange<Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > ParserState<'a,ParseStr<'a,Pos>,STeXToken<Pos>,Err> for STeXParseState<'a,Pos,MS> {
   type Group = STeXGroup<'a,MS,Pos,Err>;
-  type MacroArg = (SymbolReference<Pos>,u8);
+  type MacroArg = MacroArg<Pos>;
 }
\ No newline at end of file
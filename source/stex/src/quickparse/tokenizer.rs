@@ -5,7 +5,7 @@ use immt_utils::{
 };
 use std::marker::PhantomData;
 
-use super::stex::DiagnosticLevel;
+use super::stex::{DiagnosticCode, DiagnosticLevel};
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Mode {
@@ -15,7 +15,7 @@ pub enum Mode {
 
 pub struct TeXTokenizer<'a, 
     Pa:ParseSource<'a>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel)
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > {
     pub reader: Pa,
     pub letters: String,
@@ -26,7 +26,7 @@ pub struct TeXTokenizer<'a,
 
 impl<'a, 
     Pa:ParseSource<'a>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel)
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > Iterator for TeXTokenizer<'a, Pa,Err> {
     type Item = TeXToken<Pa::Pos, Pa::Str>;
 
@@ -39,7 +39,7 @@ impl<'a,
 
 impl<'a, 
     Pa:ParseSource<'a>,
-    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel)
+    Err:FnMut(String,SourceRange<Pa::Pos>,DiagnosticLevel,Option<DiagnosticCode>)
 > TeXTokenizer<'a, Pa,Err> {
     pub(crate) fn new(reader: Pa,err:Err) -> Self {
         TeXTokenizer {
@@ -136,7 +136,13 @@ impl<'a,
 
     #[inline]
     pub fn problem(&mut self,start:Pa::Pos, msg: impl std::fmt::Display,level:DiagnosticLevel) {
-        (self.err)(msg.to_string(), SourceRange{start,end: self.reader.curr_pos()},level);
+        (self.err)(msg.to_string(), SourceRange{start,end: self.reader.curr_pos()},level,None);
+    }
+
+    /// Like [`problem`](Self::problem), for problems that tools (e.g. quick fixes) can identify by `code`
+    #[inline]
+    pub fn coded_problem(&mut self,start:Pa::Pos, msg: impl std::fmt::Display,level:DiagnosticLevel,code:DiagnosticCode) {
+        (self.err)(msg.to_string(), SourceRange{start,end: self.reader.curr_pos()},level,Some(code));
     }
 
     fn read_comment(&mut self, start: Pa::Pos) -> TeXToken<Pa::Pos, Pa::Str> {
@@ -1,9 +1,9 @@
 use std::{ops::Deref, path::Path};
 
 use immt_ontology::{
-    content::modules::OpenModule, languages::Language, narration::documents::UncheckedDocument, uris::{ArchiveId, ArchiveURITrait, NameStep, PathURIRef, PathURITrait}, Unchecked
+    content::modules::OpenModule, languages::Language, narration::documents::UncheckedDocument, uris::{ArchiveId, ArchiveURI, ArchiveURITrait, NameStep, PathURIRef, PathURITrait, URIRefTrait}, Unchecked
 };
-use immt_utils::change_listener::ChangeSender;
+use immt_utils::change_listener::{ChangeListener, ChangeSender};
 use oxigraph::model::Quad;
 
 use crate::backend::BackendChange;
@@ -42,6 +42,52 @@ impl ArchiveManager {
         r
     }
 
+    /// Re-scans the given paths for archives, notifying listeners about
+    /// new and deleted archives. Returns the URIs of the new and deleted archives.
+    /// The paths are scanned without holding a lock on the archive tree.
+    pub fn rescan<'a>(&self,paths:impl IntoIterator<Item = &'a Path,IntoIter : DoubleEndedIterator>) -> (Vec<ArchiveURI>,Vec<ArchiveURI>) {
+        let mut fresh = ArchiveTree::default();
+        // changes in known archives are reported by the watcher anyway
+        let silent = ChangeSender::new(1);
+        for p in paths.into_iter().rev() {
+            fresh.load(p,&silent,());
+        }
+        let old = std::mem::replace(&mut *self.tree.write(),fresh);
+        let tree = self.tree.read();
+        let mut new = Vec::new();
+        for a in &tree.archives {
+            if !old.archives.iter().any(|o| o.id() == a.id()) {
+                let uri = a.uri().owned();
+                self.change_sender.lazy_send(|| BackendChange::NewArchive(uri.clone()));
+                new.push(uri);
+            }
+        }
+        let mut deleted = Vec::new();
+        for o in &old.archives {
+            if !tree.archives.iter().any(|a| a.id() == o.id()) {
+                let uri = o.uri().owned();
+                self.change_sender.lazy_send(|| BackendChange::ArchiveDeleted(uri.clone()));
+                deleted.push(uri);
+            }
+        }
+        (new,deleted)
+    }
+
+    /// Updates the source files of the given archive from disk.
+    pub fn refresh_sources(&self,id:&ArchiveId) {
+        self.with_archive(id, |a| {
+            if let Some(Archive::Local(a)) = a {
+                a.update_sources(&self.change_sender);
+            }
+        });
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn listener(&self) -> ChangeListener<BackendChange> {
+        self.change_sender.listener()
+    }
+
     #[inline]
     pub fn with_tree<R>(&self,f:impl FnOnce(&ArchiveTree) -> R) -> R {
         f(&self.tree.read())
@@ -8,7 +8,7 @@ use std::path::{Path, PathBuf};
 use either::Either;
 use ignore_regex::IgnoreSource;
 use immt_ontology::{
-    content::modules::OpenModule, file_states::FileStateSummary, languages::Language, narration::documents::UncheckedDocument, uris::{ArchiveId, ArchiveURI, ArchiveURIRef, ArchiveURITrait, DocumentURI, Name, NameStep, PathURITrait, URIOrRefTrait, URIRefTrait, URIWithLanguage}, DocumentRange, Unchecked
+    content::modules::OpenModule, file_states::FileStateSummary, languages::Language, narration::{documents::UncheckedDocument, exercises::Exercise, paragraphs::LogicalParagraph, sections::Section, DocumentElement}, uris::{ArchiveId, ArchiveURI, ArchiveURIRef, ArchiveURITrait, DocumentURI, ModuleURI, Name, NameStep, PathURITrait, URIOrRefTrait, URIRefTrait, URIWithLanguage}, DocumentRange, Unchecked
 };
 use immt_utils::{
     change_listener::ChangeSender,
@@ -18,13 +18,13 @@ use immt_utils::{
 use iter::ArchiveIterator;
 use manager::MaybeQuads;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use source_files::{FileStates, SourceDir};
+use source_files::{ContentHashes, FileStates, SourceDir, SourceEntry};
 use spliter::ParallelSpliterator;
 use tracing::instrument;
 
 use crate::{building::{BuildArtifact, BuildResultArtifact}, formats::{BuildTargetId, OMDocResult, SourceFormatId}};
 
-use super::{docfile::PreDocFile, rdf::RDFStore, BackendChange};
+use super::{docfile::PreDocFile, rdf::RDFStore, search::SearchIndex, AnyBackend, BackendChange};
 
 #[derive(Debug)]
 pub(super) struct RepositoryData {
@@ -237,6 +237,31 @@ impl LocalArchive {
         )
     }
 
+    pub(crate) fn load_unchecked(&self,relative_path:&str) -> Option<(UncheckedDocument,Vec<OpenModule<Unchecked>>)> {
+        fn modules_of(elems:&[DocumentElement<Unchecked>],out:&mut Vec<ModuleURI>) {
+            for e in elems { match e {
+                DocumentElement::Module { module, children, .. } => {
+                    out.push(module.clone());
+                    modules_of(children, out);
+                }
+                DocumentElement::Section(Section { children, .. }) |
+                DocumentElement::Paragraph(LogicalParagraph { children, .. }) |
+                DocumentElement::Exercise(Exercise { children, .. }) |
+                DocumentElement::Morphism { children, .. } |
+                DocumentElement::MathStructure { children, .. } |
+                DocumentElement::Extension { children, .. } => modules_of(children, out),
+                _ => ()
+            }}
+        }
+        let doc = PreDocFile::read_from_file(&self.out_dir().join(relative_path).join("doc"))?;
+        let mut uris = Vec::new();
+        modules_of(&doc.elements, &mut uris);
+        let modules = uris.into_iter().filter_map(|uri|
+            self.load_module(uri.path(), uri.name().first_name())
+        ).collect();
+        Some((doc,modules))
+    }
+
     pub fn load_html_body(&self,
         path: Option<&Name>,
         name: &NameStep,
@@ -287,10 +312,10 @@ impl LocalArchive {
     }
 
     /// ### Errors
-    pub fn load<D:BuildArtifact>(&self,relative_path:&str) -> Result<D,std::io::Error> {
+    pub fn load<D:BuildArtifact>(&self,relative_path:&str,backend:&AnyBackend) -> Result<D,std::io::Error> {
         let p = self.out_dir().join(relative_path).join(D::get_type_id().name());
         if p.exists() {
-            D::load(&p)
+            D::load(&p,backend)
         } else {
             Err(std::io::ErrorKind::NotFound.into())
         }
@@ -327,6 +352,7 @@ impl LocalArchive {
 
         er!(bincode::serde::encode_into_std_write(document, &mut buf, bincode::config::standard()));
         //er!(document.into_byte_stream(&mut buf));
+        er!(SearchIndex::save(top, result));
 
         for m in modules {
             let path = m.uri.path();
@@ -354,6 +380,22 @@ impl LocalArchive {
         self.out_dir().join(relative_path).join(target.name()).with_extension("log")
     }
 
+    pub(crate) fn source_hash(&self,relative_path:&str) -> Option<u128> {
+        let hash = source_files::content_hash(&self.source_dir().join(relative_path));
+        if hash.is_none() {
+            tracing::warn!("Failed to hash [{}]{}", self.id(), relative_path);
+        }
+        hash
+    }
+
+    pub(crate) fn save_hashes(&self,relative_path:&str,target:BuildTargetId,source:u128,dependencies:&[PathBuf]) {
+        let hashes = ContentHashes::compute(source, dependencies);
+        let p = self.out_dir().join(relative_path).join(target.name()).with_extension("hash");
+        if let Err(e) = hashes.write(&p) {
+            tracing::error!("Failed to save [{}]{}: {}", self.id(), relative_path, e);
+        }
+    }
+
     #[allow(clippy::cognitive_complexity)]
     pub fn save(&self,relative_path:&str,log:Either<String,PathBuf>,from:BuildTargetId,result:Option<BuildResultArtifact>) {
         macro_rules! err {
@@ -418,6 +460,17 @@ impl Archive {
         }
     }
 
+    /// The relative path of the source file the given document was built from.
+    #[must_use]
+    pub fn find_source(&self,uri:&DocumentURI) -> Option<std::sync::Arc<str>> {
+        let archive = self.uri().owned();
+        self.with_sources(|d| d.dfs()?.find_map(|e| match e {
+            SourceEntry::File(f) if DocumentURI::from_archive_relpath(archive.clone(), &f.relative_path) == *uri =>
+                Some(f.relative_path.clone()),
+            _ => None
+        }))
+    }
+
     pub fn submit_triples(&self,in_doc:&DocumentURI,rel_path:&str,relational:&RDFStore,load:bool,iter:impl Iterator<Item=immt_ontology::rdf::Triple>) {
         match self {
             Self::Local(a) => a.submit_triples(in_doc,rel_path,relational,load,iter)
@@ -535,9 +588,9 @@ impl Archive {
 
     /// ### Errors
     #[inline]
-    pub fn load<D:BuildArtifact>(&self,relative_path:&str) -> Result<D,std::io::Error> {
+    pub fn load<D:BuildArtifact>(&self,relative_path:&str,backend:&AnyBackend) -> Result<D,std::io::Error> {
         match self {
-            Self::Local(a) => a.load(relative_path)
+            Self::Local(a) => a.load(relative_path,backend)
         }
     }
 
@@ -546,6 +599,20 @@ impl Archive {
             Self::Local(a) => a.save(relative_path,log,from,result)
         }
     }
+
+    /// The content hash of the given source file; taken before building it, so that
+    /// changes during the build make the results stale.
+    pub(crate) fn source_hash(&self,relative_path:&str) -> Option<u128> {
+        match self {
+            Self::Local(a) => a.source_hash(relative_path)
+        }
+    }
+
+    pub(crate) fn save_hashes(&self,relative_path:&str,target:BuildTargetId,source:u128,dependencies:&[PathBuf]) {
+        match self {
+            Self::Local(a) => a.save_hashes(relative_path,target,source,dependencies)
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc};
+use std::{path::{Path, PathBuf}, sync::Arc};
 
 use either::Either;
 use immt_ontology::{
@@ -327,12 +327,73 @@ impl SourceDir {
     }
 }
 
+/// 128-bit FNV-1a hash of a file's contents; unlike [`std::hash::DefaultHasher`],
+/// this is stable across platforms and compiler versions, so it can be persisted.
+pub(crate) fn content_hash(path: &Path) -> Option<u128> {
+    const OFFSET: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+    const PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+    let bytes = std::fs::read(path).ok()?;
+    Some(bytes.iter().fold(OFFSET, |h, b| (h ^ u128::from(*b)).wrapping_mul(PRIME)))
+}
+
+/// The content hashes of a source file (taken when the build started) and the dependencies
+/// of a build target at the time it was last built successfully. Stored as `<target>.hash` next to the
+/// target's log file in the archive's `.immt` directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ContentHashes {
+    source: u128,
+    dependencies: Vec<(u128, PathBuf)>,
+}
+impl ContentHashes {
+    pub(crate) fn compute(source: u128, dependencies: &[PathBuf]) -> Self {
+        Self {
+            source,
+            dependencies: dependencies
+                .iter()
+                .filter_map(|p| content_hash(p).map(|h| (h, p.clone())))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> std::io::Result<()> {
+        use std::fmt::Write;
+        let mut s = format!("{:032x}\n", self.source);
+        for (h, p) in &self.dependencies {
+            let _ = writeln!(s, "{h:032x}\t{}", p.display());
+        }
+        std::fs::write(path, s)
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        let s = std::fs::read_to_string(path).ok()?;
+        let mut lines = s.lines();
+        let source = u128::from_str_radix(lines.next()?, 16).ok()?;
+        let dependencies = lines
+            .map(|l| {
+                let (h, p) = l.split_once('\t')?;
+                Some((u128::from_str_radix(h, 16).ok()?, PathBuf::from(p)))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { source, dependencies })
+    }
+
+    #[inline]
+    fn source_is_current(&self, source: Option<u128>) -> bool {
+        source == Some(self.source)
+    }
+
+    fn dependencies_are_current(&self) -> bool {
+        self.dependencies
+            .iter()
+            .all(|(h, p)| content_hash(p) == Some(*h))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash,serde::Serialize, serde::Deserialize)]
 pub struct ChangeState {
     pub last_built: Timestamp,
     pub last_changed: Timestamp,
     //last_watched:Timestamp,
-    //md5:u128 TODO
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash,serde::Serialize, serde::Deserialize)]
@@ -370,6 +431,8 @@ impl FileState {
         format: SourceFormatId,
     ) -> VecMap<BuildTargetId,Self> {
         let out = LocalArchive::out_dir_of(top).join(relative_path);
+        let source_path = LocalArchive::source_dir_of(top).join(relative_path);
+        let source_hash = std::cell::OnceCell::new();
         let mut ret = VecMap::new();
         for t in *format.targets() {
             let log = out.join(t.name()).with_extension("log");
@@ -389,7 +452,16 @@ impl FileState {
                 ret.insert(*t, Self::New);
                 continue;
             };
-            if last_built > last_changed {
+            // mtimes are only a fast pre-check for the source file; after a fresh clone or
+            // checkout they are all newer than the build logs, so compare content hashes.
+            // Dependencies may change without touching the source, so they are always compared.
+            let up_to_date = match ContentHashes::read(&out.join(t.name()).with_extension("hash")) {
+                Some(h) => (last_built > last_changed
+                    || h.source_is_current(*source_hash.get_or_init(|| content_hash(&source_path))))
+                    && h.dependencies_are_current(),
+                None => last_built > last_changed,
+            };
+            if up_to_date {
                 ret.insert(
                     *t,
                     Self::UpToDate(ChangeState {
@@ -466,3 +538,48 @@ impl FileStates {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{content_hash, ContentHashes};
+
+    #[test]
+    fn content_hashes() {
+        let dir = tempfile::tempdir().expect("can create temporary directory");
+        let source = dir.path().join("source.tex");
+        let dependency = dir.path().join("dependency.tex");
+        std::fs::write(&source, "\\importmodule{dependency}").expect("can write file");
+        std::fs::write(&dependency, "\\symdef{foo}{bar}").expect("can write file");
+
+        let source_hash = content_hash(&source).expect("file exists");
+        assert_eq!(content_hash(&source), Some(source_hash));
+        assert_ne!(content_hash(&dependency), Some(source_hash));
+        assert_eq!(content_hash(&dir.path().join("missing.tex")), None);
+
+        // missing dependencies are not recorded
+        let hashes = ContentHashes::compute(
+            source_hash,
+            &[dependency.clone(), dir.path().join("missing.tex")],
+        );
+        let hash_file = dir.path().join("target.hash");
+        hashes.write(&hash_file).expect("can write hash file");
+        let read = ContentHashes::read(&hash_file).expect("hash file is valid");
+        assert_eq!(read, hashes);
+        assert_eq!(read.dependencies.len(), 1);
+        assert!(read.source_is_current(Some(source_hash)));
+        assert!(read.dependencies_are_current());
+
+        // touching a file without changing its content keeps the hashes current
+        std::fs::write(&source, "\\importmodule{dependency}").expect("can write file");
+        assert!(read.source_is_current(content_hash(&source)));
+
+        std::fs::write(&source, "\\importmodule{other}").expect("can write file");
+        assert!(!read.source_is_current(content_hash(&source)));
+        assert!(read.dependencies_are_current());
+
+        std::fs::write(&dependency, "\\symdef{foo}{baz}").expect("can write file");
+        assert!(!read.dependencies_are_current());
+        std::fs::remove_file(&dependency).expect("can remove file");
+        assert!(!read.dependencies_are_current());
+    }
+}
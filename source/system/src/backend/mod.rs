@@ -2,6 +2,8 @@ pub mod archives;
 mod cache;
 mod docfile;
 pub mod rdf;
+pub mod search;
+pub mod watcher;
 
 use archives::{manager::ArchiveManager, source_files::FileState, Archive, ArchiveGroup, ArchiveOrGroup, ArchiveTree, LocalArchive};
 use cache::BackendCache;
@@ -12,15 +14,16 @@ use immt_ontology::{
     }, languages::Language, narration::{
         checking::DocumentChecker, documents::Document, exercises::Exercise, notations::{Notation, PresentationError, Presenter}, paragraphs::LogicalParagraph, sections::Section, DocumentElement, LazyDocRef, NarrationTrait, NarrativeReference
     }, uris::{
-        ArchiveId, ArchiveURI, ArchiveURITrait, ContentURITrait, DocumentElementURI, DocumentURI, ModuleURI, NameStep, PathURIRef, PathURITrait, SymbolURI, URIOrRefTrait, URIWithLanguage
+        ArchiveId, ArchiveURI, ArchiveURITrait, ContentURITrait, DocumentElementURI, DocumentURI, ModuleURI, NameStep, PathURIRef, PathURITrait, SymbolURI, URIOrRefTrait, URIRefTrait, URIWithLanguage
     }, Checked, DocumentRange, LocalBackend, Unchecked
 };
 use immt_utils::{prelude::{HMap, TreeLike}, triomphe, vecmap::{VecMap, VecSet}, CSS};
 use lazy_static::lazy_static;
 use parking_lot::RwLock;
 use rdf::RDFStore;
+use search::SearchIndex;
 use std::{ops::Deref, path::{Path, PathBuf}, rc::Rc};
-use crate::{formats::{HTMLData, SourceFormatId}, settings::Settings};
+use crate::{building::{BuildTask, DependentsIndex, FileKey, PulledIn}, formats::{HTMLData, SourceFormatId}, settings::Settings};
 
 #[derive(Clone, Debug)]
 pub enum BackendChange {
@@ -183,6 +186,54 @@ pub enum AnyBackend{
     Sandbox(SandboxedBackend)
 }
 impl AnyBackend {
+    /// The source files directly depending on `[archive]rel_path`, either by the
+    /// dependencies registered for previous build tasks, or by importing or using one of
+    /// its modules according to the relational store.
+    pub fn dependents_of(&self,archive:&ArchiveId,rel_path:&str) -> Vec<(FileKey,PulledIn)> {
+        let global = GlobalBackend::get();
+        let Some(doc) = self.with_archive(archive, |a|
+            a.map(|a| DocumentURI::from_archive_relpath(a.uri().owned(), rel_path))
+        ) else { return Vec::new() };
+        let modules = global.triple_store().modules_in(&doc).collect::<Vec<_>>();
+        let mut ret = self.dependents().read().dependents(archive, rel_path, &modules);
+        for m in modules {
+            for d in global.triple_store().importing_documents(&m) {
+                let Some(p) = self.with_archive(d.archive_id(), |a| a.and_then(|a| a.find_source(&d))) else {continue};
+                ret.push(((d.archive_id().clone(),p),Some(m.clone())));
+            }
+        }
+        let mut out : Vec<(FileKey,PulledIn)> = Vec::new();
+        for (key,module) in ret {
+            if (key.0 == *archive && &*key.1 == rel_path) || out.iter().any(|(k,_)| *k == key) {
+                continue
+            }
+            out.push((key,PulledIn { archive:archive.clone(), rel_path:rel_path.into(), module }));
+        }
+        out
+    }
+
+    /// The dependents index of this backend; sandboxes keep their own, since their
+    /// archives may differ from the global ones.
+    fn dependents(&self) -> &RwLock<DependentsIndex> {
+        match self {
+            Self::Global(_) | Self::Temp(_) => &GlobalBackend::get().dependents,
+            Self::Sandbox(s) => &s.0.dependents
+        }
+    }
+
+    #[inline]
+    pub fn register_dependencies(&self,task:&BuildTask) {
+        self.dependents().write().register(task);
+    }
+
+    /// Stores the registered dependencies of `[archive]rel_path` with its build results.
+    pub(crate) fn save_dependencies(&self,archive:&Archive,rel_path:&str) {
+        let Archive::Local(a) = archive;
+        if let Err(e) = self.dependents().read().save(a.id(),rel_path,&a.out_dir().join(rel_path)) {
+            tracing::error!("Failed to save dependencies of [{}]{}: {}", a.id(), rel_path, e);
+        }
+    }
+
     pub fn mathhubs(&self) -> Vec<PathBuf> {
         let mut global: Vec<PathBuf> = Settings::get().mathhubs.iter().map(|p| p.to_path_buf()).collect();
         match self {
@@ -354,14 +405,18 @@ impl Backend for AnyBackend {
 pub struct GlobalBackend {
     archives: ArchiveManager,
     cache: RwLock<cache::BackendCache>,
-    triple_store: RDFStore,
+    triple_store: std::sync::OnceLock<RDFStore>,
+    search: SearchIndex,
+    dependents: RwLock<DependentsIndex>,
 }
 
 lazy_static! {
     static ref GLOBAL: GlobalBackend = GlobalBackend {
         archives: ArchiveManager::default(),
         cache: RwLock::new(cache::BackendCache::default()),
-        triple_store: RDFStore::default()
+        triple_store: std::sync::OnceLock::new(),
+        search: SearchIndex::default(),
+        dependents: RwLock::new(DependentsIndex::default())
     };
 }
 
@@ -403,8 +458,26 @@ impl GlobalBackend {
     #[inline]
     pub const fn manager(&self) -> &ArchiveManager {&self.archives}
 
+    /// The relational store; in-memory, unless [`set_triple_store`](Self::set_triple_store)
+    /// was called before its first use.
+    #[inline]
+    pub fn triple_store(&self) -> &RDFStore { self.triple_store.get_or_init(RDFStore::default) }
+
+    /// Sets the relational store to use; called once during [`initialize`](crate::initialize).
+    pub fn set_triple_store(&self,store:RDFStore) {
+        if self.triple_store.set(store).is_err() {
+            tracing::error!(target:"relational","Relational store was already initialized");
+        }
+    }
+
     #[inline]
-    pub const fn triple_store(&self) -> &RDFStore { &self.triple_store }
+    pub const fn search_index(&self) -> &SearchIndex { &self.search }
+
+    /// Loads the dependencies stored by previous builds of documents in the given archives
+    #[inline]
+    pub fn load_dependencies(&self,archives:&[Archive]) {
+        self.dependents.write().load_archives(archives);
+    }
 
     #[inline]
     pub fn all_archives(&self) -> impl Deref<Target = [Archive]> + '_ {
@@ -806,6 +879,7 @@ struct SandboxedBackendI {
     repos: parking_lot::RwLock<Vec<SandboxedRepository>>,
     manager: ArchiveManager,
     cache: RwLock<cache::BackendCache>,
+    dependents: RwLock<DependentsIndex>,
 }
 #[derive(Debug,Clone)]
 pub struct SandboxedBackend(triomphe::Arc<SandboxedBackendI>);
@@ -838,6 +912,7 @@ impl SandboxedBackend {
             repos: parking_lot::RwLock::new(Vec::new()),
             manager: ArchiveManager::default(),
             cache: RwLock::new(cache::BackendCache::default()),
+            dependents: RwLock::new(DependentsIndex::default()),
         };
         SandboxedBackend(triomphe::Arc::new(i))
     }
@@ -899,14 +974,14 @@ impl SandboxedBackend {
             }, Settings::get().mathhubs.iter().map(|p| &**p)), 
             [&*self.0.path]
         );
-        global.triple_store.clear();
+        global.triple_store().clear();
         global_cache.clear();
         sandbox_cache.clear();
         drop(global_cache);
         drop(sandbox_cache);
         immt_utils::background(|| {
             let global = GlobalBackend::get();
-            global.triple_store.load_archives(&global.all_archives());
+            global.triple_store().load_archives(&global.all_archives());
         });
         count
     }
@@ -1007,6 +1082,7 @@ impl SandboxedBackend {
         );
         drop(repos);
         self.0.manager.load(&self.0.path);
+        self.0.dependents.write().load_archives(&self.0.manager.all_archives());
     }
 
     fn copy_archive(&self,a:&LocalArchive) {
@@ -1242,7 +1318,11 @@ impl ModuleChecker for GlobalFlattener<'_> {
     #[inline]
     fn open(&mut self, _elem: &mut OpenDeclaration<Unchecked>) {}
     #[inline]
-    fn close(&mut self, _elem: &mut Declaration) {}
+    fn close(&mut self, elem: &mut Declaration) {
+        if let Declaration::Morphism(m) = elem {
+            m.add_induced_symbols();
+        }
+    }
 }
 
 
@@ -1327,7 +1407,11 @@ impl ModuleChecker for SandboxFlattener<'_> {
     #[inline]
     fn open(&mut self, _elem: &mut OpenDeclaration<Unchecked>) {}
     #[inline]
-    fn close(&mut self, _elem: &mut Declaration) {}
+    fn close(&mut self, elem: &mut Declaration) {
+        if let Declaration::Morphism(m) = elem {
+            m.add_induced_symbols();
+        }
+    }
 }
 
 pub struct TermPresenter<'a,W:std::fmt::Write,B:Backend>{
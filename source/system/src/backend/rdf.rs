@@ -3,7 +3,7 @@ use immt_ontology::narration::exercises::CognitiveDimension;
 use immt_ontology::narration::LOKind;
 use immt_ontology::rdf::ontologies::ulo2;
 use immt_ontology::rdf::{NamedNode, Quad, Triple};
-use immt_ontology::uris::{ArchiveURIRef, DocumentElementURI, DocumentURI, PathURITrait, SymbolURI, URIOrRefTrait, URIRefTrait, URITrait};
+use immt_ontology::uris::{ArchiveId, ArchiveURIRef, ArchiveURITrait, DocumentElementURI, DocumentURI, ModuleURI, PathURITrait, SymbolURI, URIOrRefTrait, URIRefTrait, URITrait};
 use oxigraph::sparql::QuerySolutionIter;
 use oxrdfio::RdfFormat;
 use std::fmt::{Debug, Display};
@@ -12,6 +12,7 @@ use std::marker::PhantomData;
 use std::ops::Deref;
 use std::path::Path;
 use std::string::FromUtf8Error;
+use immt_utils::prelude::{HMap, HSet};
 use tracing::instrument;
 
 pub mod sparql {
@@ -301,8 +302,15 @@ impl Iterator for LOIter {
 
 }
 
+/// Name of the file in a persistent store's directory recording, for each archive, when it
+/// was last synchronized with the `index.ttl` files of that archive.
+const SYNC_MARKER: &str = "immt.synced";
+
 pub struct RDFStore {
     store: oxigraph::store::Store,
+    path: Option<Box<Path>>,
+    /// seconds since the epoch of the last synchronization, per archive
+    synced: parking_lot::Mutex<HMap<ArchiveId,u64>>
 }
 impl Debug for RDFStore {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -317,14 +325,54 @@ impl Default for RDFStore {
             .bulk_loader()
             .load_quads(immt_ontology::rdf::ontologies::ulo2::QUADS.iter().copied())
             .unwrap_or_else(|_| unreachable!());
-        Self { store }
+        Self { store, path: None, synced: parking_lot::Mutex::default() }
     }
 }
 
 impl RDFStore {
+    /// Opens (or creates) a persistent store in the given directory, falling back to
+    /// an in-memory store on failure.
+    #[cfg(feature="rocksdb")]
+    #[must_use]
+    pub fn open(path:&Path) -> Self {
+        match oxigraph::store::Store::open(path) {
+            Ok(store) => {
+                let _ = store.bulk_loader()
+                    .load_quads(immt_ontology::rdf::ontologies::ulo2::QUADS.iter().copied());
+                let synced = parking_lot::Mutex::new(Self::read_synced(path));
+                Self { store, path: Some(path.into()), synced }
+            }
+            Err(e) => {
+                tracing::error!(target:"relational","Failed to open relational store at {}: {e}",path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// A persistent store in the given directory (if any), or an in-memory store otherwise.
+    #[must_use]
+    pub fn new(path:Option<&Path>) -> Self {
+        #[cfg(feature="rocksdb")]
+        if let Some(path) = path {
+            return Self::open(path)
+        }
+        #[cfg(not(feature="rocksdb"))]
+        if path.is_some() {
+            tracing::warn!(target:"relational","Persistent relational store requires the `rocksdb` feature; using an in-memory store");
+        }
+        Self::default()
+    }
+
+    #[inline]
+    #[must_use]
+    pub const fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
     #[inline]
     pub fn clear(&self) {
-        self.store.clear();
+        let _ = self.store.clear();
+        self.synced.lock().clear();
     }
     #[inline]
     #[must_use]
@@ -345,6 +393,60 @@ impl RDFStore {
         )
     }
 
+    /// The modules declared in the given document.
+    #[must_use]
+    pub fn modules_in(&self,doc:&DocumentURI) -> RetIter<ModuleURI> {
+        self.query_str(format!(
+            "SELECT DISTINCT ?m WHERE {{ {} ulo:contains ?m . ?m rdf:type ulo:theory }}",
+            doc.to_iri()
+        )).map(QueryResult::into_uris).unwrap_or_default()
+    }
+
+    /// The document containing the given (top-level) module.
+    #[must_use]
+    pub fn document_of(&self,module:&ModuleURI) -> Option<DocumentURI> {
+        self.query_str(format!(
+            "SELECT DISTINCT ?d WHERE {{ ?d ulo:contains {} . ?d rdf:type ulo:document }}",
+            module.to_iri()
+        )).ok()?.into_uris().next()
+    }
+
+    /// The documents that import (via some contained module) or use the given module.
+    #[must_use]
+    pub fn importing_documents(&self,module:&ModuleURI) -> RetIter<DocumentURI> {
+        let iri = module.to_iri();
+        self.query_str(format!(
+            "SELECT DISTINCT ?d WHERE {{ {{ ?d ulo:contains ?n . ?n ulo:imports {iri} }} UNION {{ ?d dc:requires {iri} }} }}"
+        )).map(QueryResult::into_uris).unwrap_or_default()
+    }
+
+    /// The modules (or structures) imported by the given module (or structure).
+    #[must_use]
+    pub fn imported_modules(&self,module:&ModuleURI) -> RetIter<ModuleURI> {
+        self.query_str(format!(
+            "SELECT DISTINCT ?m WHERE {{ {} ulo:imports ?m }}",
+            module.to_iri()
+        )).map(QueryResult::into_uris).unwrap_or_default()
+    }
+
+    /// The modules (or structures) that import the given module (or structure).
+    #[must_use]
+    pub fn importing_modules(&self,module:&ModuleURI) -> RetIter<ModuleURI> {
+        self.query_str(format!(
+            "SELECT DISTINCT ?m WHERE {{ ?m ulo:imports {} }}",
+            module.to_iri()
+        )).map(QueryResult::into_uris).unwrap_or_default()
+    }
+
+    /// The documents that reference the given symbol.
+    #[must_use]
+    pub fn referencing_documents(&self,symbol:&SymbolURI) -> RetIter<DocumentURI> {
+        self.query_str(format!(
+            "SELECT DISTINCT ?d WHERE {{ ?d ulo:crossrefs {} }}",
+            symbol.to_iri()
+        )).map(QueryResult::into_uris).unwrap_or_default()
+    }
+
     pub fn export(&self, iter: impl Iterator<Item = Triple>, p: &Path, uri: &DocumentURI) {
         if let Ok(file) = std::fs::File::create(p) {
             let writer = BufWriter::new(file);
@@ -398,6 +500,10 @@ impl RDFStore {
             return;
         };
         let buf = BufReader::new(file);
+        // replaces the previous triples of the document
+        if let Err(e) = self.store.clear_graph(graph.as_ref()) {
+            tracing::warn!("Failed to clear graph {graph}: {e}");
+        }
         let loader = self.store.bulk_loader();
         let reader = oxigraph::io::RdfParser::from_format(RdfFormat::Turtle)
             .with_default_graph(graph)
@@ -411,6 +517,9 @@ impl RDFStore {
         use rayon::prelude::*;
         tracing::info!(target:"relational","Loading relational for {} archives...",archives.len());
         let old = self.store.len().unwrap_or_default();
+        let started = std::time::SystemTime::now();
+        let synced = self.synced.lock().clone();
+        let seen = parking_lot::Mutex::new(HSet::default());
         archives
             .par_iter()
             .filter_map(|a| match a {
@@ -419,6 +528,7 @@ impl RDFStore {
             })
             .for_each(|a| {
                 let out = a.out_dir();
+                let synced = synced.get(a.id()).map(|secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(*secs));
                 if out.exists() && out.is_dir() {
                     for e in walkdir::WalkDir::new(out)
                         .into_iter()
@@ -428,11 +538,79 @@ impl RDFStore {
                         let Some(graph) = Self::get_iri(a.uri(), out, &e) else {
                             continue;
                         };
-                        self.load(e.path(),graph);
+                        let up_to_date = synced.is_some_and(|synced|
+                            e.metadata().ok().and_then(|m| m.modified().ok()).is_some_and(|m| m <= synced)
+                        ) && self.store.contains_named_graph(graph.as_ref()).unwrap_or_default();
+                        if !up_to_date {
+                            self.load(e.path(),graph.clone());
+                        }
+                        seen.lock().insert(graph);
                     }
                 }
             });
-        tracing::info!(target:"relational","Loaded {} relations", self.store.len().unwrap_or_default() - old);
+        if self.path.is_some() {
+            self.remove_stale(archives,&seen.into_inner());
+            self.mark_synced(archives,started);
+        }
+        tracing::info!(target:"relational","Loaded {} relations", self.store.len().unwrap_or_default().saturating_sub(old));
+    }
+
+    fn read_synced(path:&Path) -> HMap<ArchiveId,u64> {
+        std::fs::read_to_string(path.join(SYNC_MARKER)).map(|s| s.lines().filter_map(|l| {
+            let (id,secs) = l.split_once('\t')?;
+            Some((ArchiveId::new(id),secs.trim().parse().ok()?))
+        }).collect()).unwrap_or_default()
+    }
+
+    fn write_synced(&self,synced:&HMap<ArchiveId,u64>) {
+        use std::fmt::Write;
+        let Some(path) = self.path.as_ref() else { return };
+        let mut s = String::new();
+        for (id,secs) in synced {
+            let _ = writeln!(s,"{id}\t{secs}");
+        }
+        if let Err(e) = std::fs::write(path.join(SYNC_MARKER), s) {
+            tracing::warn!(target:"relational","Failed to write {SYNC_MARKER}: {e}");
+        }
+    }
+
+    fn mark_synced(&self,archives:&[Archive],at:std::time::SystemTime) {
+        let secs = at.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let mut synced = self.synced.lock();
+        for a in archives {
+            synced.insert(a.id().clone(),secs);
+        }
+        self.write_synced(&synced);
+    }
+
+    /// Removes the graphs of documents in the given archives that have no `index.ttl` anymore.
+    fn remove_stale(&self,archives:&[Archive],seen:&HSet<NamedNode>) {
+        self.remove_graphs(|n,uri|
+            !seen.contains(n) && archives.iter().any(|a| a.id() == uri.archive_id())
+        );
+    }
+
+    /// Removes all relations of documents in the given (e.g. deleted) archive.
+    pub fn remove_archive(&self,id:&ArchiveId) {
+        self.remove_graphs(|_,uri| uri.archive_id() == id);
+        let mut synced = self.synced.lock();
+        if synced.remove(id).is_some() {
+            self.write_synced(&synced);
+        }
+    }
+
+    fn remove_graphs(&self,remove:impl Fn(&NamedNode,&DocumentURI) -> bool) {
+        let stale = self.store.named_graphs().filter_map(|g| match g {
+            Ok(oxigraph::model::NamedOrBlankNode::NamedNode(n)) => {
+                let uri : DocumentURI = immt_utils::escaping::IRI_ESCAPE.unescape(n.as_str()).to_string().parse().ok()?;
+                if remove(&n,&uri) { Some(n) } else { None }
+            }
+            _ => None
+        }).collect::<Vec<_>>();
+        for g in stale {
+            tracing::debug!(target:"relational","Removing stale graph {g}");
+            let _ = self.store.remove_named_graph(g.as_ref());
+        }
     }
 
     fn get_iri(a: ArchiveURIRef, out: &Path, e: &walkdir::DirEntry) -> Option<NamedNode> {
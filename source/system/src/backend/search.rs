@@ -0,0 +1,454 @@
+use std::{collections::BTreeMap, ops::Bound, path::Path};
+
+use immt_ontology::{
+    content::{declarations::OpenDeclaration, modules::OpenModule},
+    languages::Language,
+    narration::{documents::UncheckedDocument, DocumentElement},
+    uris::{ArchiveId, ArchiveURITrait, DocumentURI, URIWithLanguage},
+    Unchecked,
+};
+use immt_utils::prelude::HMap;
+use parking_lot::RwLock;
+
+use super::archives::Archive;
+use crate::formats::OMDocResult;
+
+/// The name of the file (next to the `shtml` and `doc` files) the search entries of a document are stored in.
+const SEARCH_FILE: &str = "search";
+
+pub use immt_ontology::search::SearchKind;
+
+/// A single searchable item: a symbol (with the text of its definitions), a
+/// paragraph, or a document/section title.
+#[derive(Debug,Clone,serde::Serialize,serde::Deserialize)]
+pub struct SearchEntry {
+    pub kind: SearchKind,
+    pub uri: Box<str>,
+    pub name: Box<str>,
+    pub macroname: Option<Box<str>>,
+    pub text: Box<str>,
+    pub language: Language
+}
+
+#[derive(Debug,serde::Serialize,serde::Deserialize)]
+struct SearchDocument {
+    uri: DocumentURI,
+    entries: Vec<SearchEntry>
+}
+
+#[derive(Debug)]
+struct Indexed {
+    entry: SearchEntry,
+    names: Box<[Box<str>]>,
+    words: Box<[Box<str>]>
+}
+impl From<SearchEntry> for Indexed {
+    fn from(entry: SearchEntry) -> Self {
+        let mut names = tokenize(&entry.name);
+        if let Some(m) = &entry.macroname {
+            names.extend(tokenize(m));
+        }
+        let words = tokenize(&entry.text);
+        Self { entry, names: names.into_boxed_slice(), words: words.into_boxed_slice() }
+    }
+}
+
+/// An occurrence of a term in an entry
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+struct Posting {
+    entry: usize,
+    /// whether the term occurs in the name or macro name (rather than the text)
+    name: bool
+}
+
+#[derive(Debug,Default)]
+struct Index {
+    /// entries by id; the ids of removed entries are reused
+    entries: Vec<Option<Indexed>>,
+    free: Vec<usize>,
+    documents: HMap<DocumentURI,Vec<usize>>,
+    /// the inverted index; ordered, so that prefix matches are a range
+    terms: BTreeMap<Box<str>,Vec<Posting>>
+}
+impl Index {
+    fn insert(&mut self,uri:DocumentURI,entries:Vec<SearchEntry>) {
+        self.remove(&uri);
+        let mut ids = Vec::with_capacity(entries.len());
+        for e in entries {
+            let e = Indexed::from(e);
+            let id = self.free.pop().unwrap_or(self.entries.len());
+            let names = e.names.iter().map(|w| (w,true));
+            for (w,name) in names.chain(e.words.iter().map(|w| (w,false))) {
+                let postings = self.terms.entry(w.clone()).or_default();
+                let p = Posting { entry:id, name };
+                // all postings of this entry are at the end
+                if !postings.iter().rev().take_while(|p| p.entry == id).any(|q| *q == p) { postings.push(p); }
+            }
+            if id == self.entries.len() { self.entries.push(Some(e)); } else { self.entries[id] = Some(e); }
+            ids.push(id);
+        }
+        self.documents.insert(uri,ids);
+    }
+
+    fn remove(&mut self,uri:&DocumentURI) {
+        let Some(ids) = self.documents.remove(uri) else { return };
+        for id in ids {
+            let Some(e) = self.entries.get_mut(id).and_then(Option::take) else { continue };
+            for w in e.names.iter().chain(e.words.iter()) {
+                if let Some(p) = self.terms.get_mut(w) {
+                    p.retain(|p| p.entry != id);
+                    if p.is_empty() { self.terms.remove(w); }
+                }
+            }
+            self.free.push(id);
+        }
+    }
+
+    /// The best score of every entry containing a term matching the query word `q`
+    /// (exactly, as a prefix or with a small edit distance).
+    fn matches(&self,q:&str) -> HMap<usize,f32> {
+        let mut ret = HMap::default();
+        let mut add = |score:f32,postings:&[Posting]| for p in postings {
+            let score = if p.name { 2.0 * score } else { score };
+            let best = ret.entry(p.entry).or_insert(0.0f32);
+            if score > *best { *best = score; }
+        };
+        for (t,postings) in self.terms.range::<str,_>((Bound::Included(q),Bound::Unbounded)) {
+            if !t.starts_with(q) { break }
+            add(if &**t == q { 1.0 } else { 0.8 },postings);
+        }
+        let len = q.chars().count();
+        let max = match len {
+            0..4 => 0,
+            4..8 => 1,
+            _ => 2
+        };
+        if max > 0 {
+            for (t,postings) in &self.terms {
+                if t.starts_with(q) || t.chars().count().abs_diff(len) > max { continue }
+                if edit_distance(q,t) <= max { add(0.5,postings); }
+            }
+        }
+        ret
+    }
+}
+
+/// Full-text index over symbol names, macro names, definienda,
+/// paragraph texts and section titles of all built documents.
+#[derive(Debug,Default)]
+pub struct SearchIndex {
+    index: RwLock<Index>
+}
+
+impl SearchIndex {
+    /// Extracts the search entries from `result` and stores them in `out` (the output
+    /// directory of the document).
+    pub(crate) fn save(out:&Path,result:&OMDocResult) -> std::io::Result<()> {
+        let doc = SearchDocument {
+            uri: result.document.uri.clone(),
+            entries: extract(result)
+        };
+        let file = std::fs::File::create(out.join(SEARCH_FILE))?;
+        let mut buf = std::io::BufWriter::new(file);
+        bincode::serde::encode_into_std_write(&doc, &mut buf, bincode::config::standard())
+            .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn num_entries(&self) -> usize {
+        self.index.read().documents.values().map(Vec::len).sum()
+    }
+
+    /// Loads the search entries of the document built from `rel_path` in `archive`, if any.
+    pub fn reload(&self,archive:&Archive,rel_path:&str) {
+        let Archive::Local(a) = archive;
+        let out = rel_path.split('/').fold(a.out_dir().to_path_buf(),|p,s| p.join(s));
+        self.load_file(&out.join(SEARCH_FILE));
+    }
+
+    fn load_file(&self,path:&Path) {
+        let Ok(file) = std::fs::File::open(path) else { return };
+        let mut file = std::io::BufReader::new(file);
+        match bincode::serde::decode_from_std_read::<SearchDocument,_,_>(&mut file, bincode::config::standard()) {
+            Ok(SearchDocument{uri,entries}) => self.index.write().insert(uri,entries),
+            Err(e) => tracing::warn!(target:"search","Failed to load {}: {e}",path.display())
+        }
+    }
+
+    /// Removes the search entries of the given (e.g. deleted) document.
+    pub fn remove_document(&self,uri:&DocumentURI) {
+        self.index.write().remove(uri);
+    }
+
+    /// Removes the search entries of all documents in the given (e.g. deleted) archive.
+    pub fn remove_archive(&self,id:&ArchiveId) {
+        let mut index = self.index.write();
+        let docs = index.documents.keys().filter(|uri| uri.archive_id() == id).cloned().collect::<Vec<_>>();
+        for d in docs {
+            index.remove(&d);
+        }
+    }
+
+    /// Loads the search entries of all documents in the given archives.
+    pub fn load_archives(&self, archives: &[Archive]) {
+        use rayon::prelude::*;
+        tracing::info!(target:"search","Loading search index for {} archives...",archives.len());
+        archives
+            .par_iter()
+            .filter_map(|a| match a {
+                Archive::Local(a) => Some(a),
+                _ => None,
+            })
+            .for_each(|a| {
+                let out = a.out_dir();
+                if out.exists() && out.is_dir() {
+                    for e in walkdir::WalkDir::new(out)
+                        .into_iter()
+                        .filter_map(Result::ok)
+                        .filter(|entry| entry.file_name() == SEARCH_FILE)
+                    {
+                        self.load_file(e.path());
+                    }
+                }
+            });
+        tracing::info!(target:"search","Loaded {} search entries", self.num_entries());
+    }
+
+    /// Returns the (at most `limit`) best matches for `query`, optionally restricted to
+    /// entries of the given `kind`. Every word in the query has to match (exactly, as a
+    /// prefix or with a small edit distance) some word in the name, macro name or
+    /// text of an entry.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn search(&self,query:&str,kind:Option<SearchKind>,limit:usize) -> Vec<(f32,SearchEntry)> {
+        let query = tokenize(query);
+        let index = self.index.read();
+        let mut words = query.iter();
+        let Some(first) = words.next() else { return Vec::new() };
+        let mut scores = index.matches(first);
+        for q in words {
+            if scores.is_empty() { break }
+            let matches = index.matches(q);
+            scores.retain(|e,total| matches.get(e).is_some_and(|s| { *total += s; true }));
+        }
+        let mut ret = scores.into_iter().filter_map(|(id,total)| {
+            let e = index.entries.get(id)?.as_ref()?;
+            if kind.is_some_and(|k| k != e.entry.kind) { return None }
+            // prefer short names, i.e. names that are (almost) fully matched
+            let score = total / query.len() as f32 + 1.0 / (1 + e.names.len()) as f32;
+            Some((score,e.entry.clone()))
+        }).collect::<Vec<_>>();
+        ret.sort_by(|(a,_),(b,_)| b.total_cmp(a));
+        ret.truncate(limit);
+        ret
+    }
+}
+
+fn edit_distance(a:&str,b:&str) -> usize {
+    let b : Vec<char> = b.chars().collect();
+    let mut row : Vec<usize> = (0..=b.len()).collect();
+    for (i,ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j,cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb { prev } else { 1 + prev.min(cur).min(row[j]) };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+fn tokenize(s:&str) -> Vec<Box<str>> {
+    s.split(|c:char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase().into_boxed_str())
+        .collect()
+}
+
+/// Returns the plain text content of the given HTML fragment.
+fn html_text(html:&str) -> String {
+    let mut ret = String::with_capacity(html.len() / 2);
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => { in_tag = false; ret.push(' '); }
+            _ if in_tag => (),
+            c => ret.push(c)
+        }
+    }
+    let ret = ret.replace("&nbsp;"," ").replace("&lt;","<").replace("&gt;",">")
+        .replace("&quot;","\"").replace("&amp;","&");
+    ret.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn extract(result:&OMDocResult) -> Vec<SearchEntry> {
+    let OMDocResult{document,html,modules} = result;
+    let mut ex = Extractor {
+        html: &html.html,
+        language: document.uri.language(),
+        entries: Vec::new(),
+        definitions: HMap::default()
+    };
+    ex.document(document);
+    for m in modules {
+        ex.module(m);
+    }
+    ex.entries
+}
+
+struct Extractor<'a> {
+    html: &'a str,
+    language: Language,
+    entries: Vec<SearchEntry>,
+    definitions: HMap<String,Vec<String>>
+}
+impl Extractor<'_> {
+    fn text(&self,range:immt_ontology::DocumentRange) -> String {
+        self.html.get(range.start..range.end).map(html_text).unwrap_or_default()
+    }
+
+    fn document(&mut self,doc:&UncheckedDocument) {
+        let name = doc.title.as_deref().map_or_else(
+            || doc.uri.name().to_string(),
+            html_text
+        );
+        self.entries.push(SearchEntry {
+            kind: SearchKind::Document,
+            uri: doc.uri.to_string().into_boxed_str(),
+            text: name.clone().into_boxed_str(),
+            name: name.into_boxed_str(),
+            macroname: None,
+            language: self.language
+        });
+        self.elements(&doc.elements);
+    }
+
+    fn elements(&mut self,elems:&[DocumentElement<Unchecked>]) {
+        for e in elems {
+            match e {
+                DocumentElement::Section(s) => {
+                    if let Some(title) = s.title {
+                        let title = self.text(title);
+                        self.entries.push(SearchEntry {
+                            kind: SearchKind::Document,
+                            uri: s.uri.to_string().into_boxed_str(),
+                            text: title.clone().into_boxed_str(),
+                            name: title.into_boxed_str(),
+                            macroname: None,
+                            language: self.language
+                        });
+                    }
+                    self.elements(&s.children);
+                }
+                DocumentElement::Paragraph(p) => {
+                    let text = self.text(p.range);
+                    if p.kind.is_definition_like(&p.styles) {
+                        for (s,_) in p.fors.iter() {
+                            self.definitions.entry(s.to_string()).or_default().push(text.clone());
+                        }
+                    }
+                    let name = p.title.map_or_else(
+                        || p.uri.name().last_name().as_ref().to_string(),
+                        |t| self.text(t)
+                    );
+                    self.entries.push(SearchEntry {
+                        kind: SearchKind::Paragraph,
+                        uri: p.uri.to_string().into_boxed_str(),
+                        name: name.into_boxed_str(),
+                        macroname: None,
+                        text: text.into_boxed_str(),
+                        language: self.language
+                    });
+                    self.elements(&p.children);
+                }
+                DocumentElement::Definiendum { range, uri } => {
+                    let text = self.text(*range);
+                    if !text.is_empty() {
+                        self.definitions.entry(uri.to_string()).or_default().push(text);
+                    }
+                }
+                DocumentElement::Exercise(e) => self.elements(&e.children),
+                DocumentElement::Module { children, .. } |
+                DocumentElement::Morphism { children, .. } |
+                DocumentElement::MathStructure { children, .. } |
+                DocumentElement::Extension { children, .. } => self.elements(children),
+                _ => ()
+            }
+        }
+    }
+
+    fn module(&mut self,m:&OpenModule<Unchecked>) {
+        self.declarations(&m.elements);
+    }
+
+    fn declarations(&mut self,decls:&[OpenDeclaration<Unchecked>]) {
+        for d in decls {
+            match d {
+                OpenDeclaration::Symbol(s) => self.symbol(&s.uri, s.macroname.as_deref()),
+                OpenDeclaration::MathStructure(s) => {
+                    self.symbol(&s.uri, s.macroname.as_deref());
+                    self.declarations(&s.elements);
+                }
+                OpenDeclaration::NestedModule(m) => self.declarations(&m.elements),
+                OpenDeclaration::Extension(e) => self.declarations(&e.elements),
+                OpenDeclaration::Import(_) | OpenDeclaration::Morphism(_) => ()
+            }
+        }
+    }
+
+    fn symbol(&mut self,uri:&immt_ontology::uris::SymbolURI,macroname:Option<&str>) {
+        let key = uri.to_string();
+        let text = self.definitions.get(&key).map(|v| v.join(" ")).unwrap_or_default();
+        self.entries.push(SearchEntry {
+            kind: SearchKind::Symbol,
+            uri: key.into_boxed_str(),
+            name: uri.name().last_name().as_ref().into(),
+            macroname: macroname.map(Into::into),
+            text: text.into_boxed_str(),
+            language: self.language
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use immt_ontology::{languages::Language, uris::DocumentURI};
+    use super::{SearchEntry, SearchIndex, SearchKind};
+
+    fn entry(kind:SearchKind,name:&str,text:&str) -> SearchEntry {
+        SearchEntry {
+            kind, uri:name.into(), name:name.into(), macroname:None,
+            text:text.into(), language:Language::English
+        }
+    }
+
+    #[test]
+    fn search_and_remove() {
+        let index = SearchIndex::default();
+        let doc : DocumentURI = "https://mathhub.info?a=some/archive&d=sets&l=en".parse().expect("is valid");
+        index.index.write().insert(doc.clone(),vec![
+            entry(SearchKind::Symbol,"natural number","the numbers 0, 1, 2 and so on"),
+            entry(SearchKind::Paragraph,"sets","a set is a collection of numbers")
+        ]);
+        assert_eq!(index.num_entries(),2);
+
+        let names = |q:&str,kind| index.search(q,kind,10).into_iter().map(|(_,e)| e.name.to_string()).collect::<Vec<_>>();
+        // name matches rank above text matches
+        assert_eq!(names("numbers",None),vec!["natural number","sets"]);
+        assert_eq!(names("numbers",Some(SearchKind::Paragraph)),vec!["sets"]);
+        // prefixes and typos
+        assert_eq!(names("natur",None),vec!["natural number"]);
+        assert_eq!(names("colection",None),vec!["sets"]);
+        // all words have to match
+        assert!(names("natural collection",None).is_empty());
+
+        index.remove_document(&doc);
+        assert_eq!(index.num_entries(),0);
+        assert!(names("numbers",None).is_empty());
+        assert!(index.index.read().terms.is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use std::{path::{Path, PathBuf}, sync::mpsc, time::{Duration, Instant}};
+
+use immt_ontology::uris::{ArchiveId, ArchiveURITrait, DocumentURI};
+use immt_utils::vecmap::VecMap;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{building::queue_manager::{QueueId, QueueManager}, settings::Settings};
+
+use super::{archives::Archive, Backend, GlobalBackend};
+
+/// Events arriving within this interval of each other are handled together
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// ...but never delayed for longer than this, even if events keep arriving
+const MAX_DEBOUNCE: Duration = Duration::from_secs(5);
+
+enum Affected {
+    Rescan,
+    Source(ArchiveId,String),
+    Nothing
+}
+
+/// Watches all configured `MathHub` directories in a background thread, refreshing
+/// the source files of archives on changes (and rescanning for archives if
+/// necessary). If `auto_build` is set, changed files are enqueued into the global
+/// build queue.
+pub fn start(auto_build:bool) {
+    let (sender,receiver) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(w) => w,
+        Err(e) => {
+            tracing::error!(target:"archives","Failed to initialize file watcher: {e}");
+            return
+        }
+    };
+    for p in &*Settings::get().mathhubs {
+        if let Err(e) = watcher.watch(p, RecursiveMode::Recursive) {
+            tracing::error!(target:"archives","Failed to watch {}: {e}",p.display());
+        }
+    }
+    #[cfg(feature="tokio")]
+    let rt = tokio::runtime::Handle::try_current().ok();
+    std::thread::spawn(move || {
+        #[cfg(feature="tokio")]
+        let _guard = rt.as_ref().map(tokio::runtime::Handle::enter);
+        // dropping the watcher would stop it
+        let _watcher = watcher;
+        while let Ok(event) = receiver.recv() {
+            let mut paths = Vec::new();
+            if !collect(event,&mut paths) { continue }
+            let deadline = Instant::now() + MAX_DEBOUNCE;
+            let mut last = Instant::now();
+            loop {
+                let timeout = (last + DEBOUNCE).min(deadline).saturating_duration_since(Instant::now());
+                if timeout.is_zero() { break }
+                match receiver.recv_timeout(timeout) {
+                    Ok(event) => if collect(event,&mut paths) { last = Instant::now(); },
+                    Err(_) => break
+                }
+            }
+            handle(&paths,auto_build);
+        }
+    });
+}
+
+/// Adds the relevant paths of `event` to `paths`; returns whether there were any.
+fn collect(event:notify::Result<notify::Event>,paths:&mut Vec<PathBuf>) -> bool {
+    match event {
+        Ok(event) if matches!(event.kind,EventKind::Access(_)) => false,
+        Ok(event) => {
+            let mut relevant = false;
+            for p in event.paths {
+                if ignored(&p) { continue }
+                relevant = true;
+                if !paths.contains(&p) { paths.push(p); }
+            }
+            relevant
+        }
+        Err(e) => {
+            tracing::warn!(target:"archives","File watcher error: {e}");
+            false
+        }
+    }
+}
+
+/// Changes in build outputs (`<archive>/.immt`, written by the build queue itself)
+/// and git internals never affect sources.
+fn ignored(path:&Path) -> bool {
+    path.components().any(|c| c.as_os_str() == ".immt" || c.as_os_str() == ".git")
+}
+
+fn affected(path:&Path) -> Affected {
+    let backend = GlobalBackend::get();
+    backend.archive_of(path, |a,_| {
+        if let Ok(rel) = path.strip_prefix(a.source_dir()) {
+            let Some(rel) = rel.to_str() else { return Affected::Nothing };
+            #[cfg(target_os = "windows")]
+            let rel = rel.replace('\\', "/");
+            #[cfg(not(target_os = "windows"))]
+            let rel = rel.to_string();
+            Affected::Source(a.id().clone(),rel)
+        } else if path == a.path() || path.starts_with(a.path().join("META-INF")) {
+            Affected::Rescan
+        } else {
+            Affected::Nothing
+        }
+    }).unwrap_or_else(||
+        if path.file_name().is_some_and(|n| n == "MANIFEST.MF") {
+            Affected::Rescan
+        } else { Affected::Nothing }
+    )
+}
+
+#[tracing::instrument(level = "info",
+    target = "archives",
+    name = "Processing file changes",
+    skip_all
+)]
+fn handle(paths:&[PathBuf],auto_build:bool) {
+    let backend = GlobalBackend::get();
+    let mut rescan = false;
+    let mut changed : VecMap<ArchiveId,Vec<String>> = VecMap::default();
+    for p in paths {
+        match affected(p) {
+            Affected::Rescan => rescan = true,
+            Affected::Source(id,rel_path) => {
+                let files = changed.get_or_insert_mut(id, Vec::new);
+                if !files.contains(&rel_path) { files.push(rel_path); }
+            }
+            Affected::Nothing => ()
+        }
+    }
+    if rescan {
+        let (new,deleted) = backend.manager().rescan(Settings::get().mathhubs.iter().map(|p| &**p));
+        for uri in deleted {
+            backend.triple_store().remove_archive(uri.archive_id());
+            backend.search_index().remove_archive(uri.archive_id());
+        }
+        for uri in new {
+            backend.with_archive(uri.archive_id(), |a| if let Some(a) = a {
+                backend.triple_store().load_archives(std::slice::from_ref(a));
+                backend.search_index().load_archives(std::slice::from_ref(a));
+                backend.load_dependencies(std::slice::from_ref(a));
+            });
+        }
+    }
+    for (id,files) in changed.iter() {
+        backend.manager().refresh_sources(id);
+        backend.with_archive(id, |a| if let Some(a) = a {
+            let Archive::Local(la) = a;
+            for rel_path in files.iter().filter(|f| !la.source_dir().join(f).exists()) {
+                backend.search_index().remove_document(&DocumentURI::from_archive_relpath(a.uri().owned(), rel_path));
+            }
+        });
+    }
+    if auto_build && !changed.is_empty() {
+        enqueue(&changed.0);
+    }
+}
+
+fn enqueue(files:&[(ArchiveId,Vec<String>)]) {
+    let count = QueueManager::get().with_queue(QueueId::global(), |q|
+        q.map_or(0,|q| q.enqueue_files(files, true))
+    );
+    if count > 0 {
+        tracing::info!(target:"archives","Enqueued {count} changed files");
+        let _ = QueueManager::get().start_queue(QueueId::global());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn ignores_build_outputs() {
+        assert!(super::ignored(Path::new("/mh/smglom/sets/.immt/mod/en/index.ttl")));
+        assert!(super::ignored(Path::new("/mh/smglom/sets/.git/index")));
+        assert!(!super::ignored(Path::new("/mh/smglom/sets/source/mod.en.tex")));
+        assert!(!super::ignored(Path::new("/mh/smglom/sets/source/my.immt.tex")));
+    }
+}
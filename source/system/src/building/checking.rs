@@ -0,0 +1,255 @@
+use std::path::Path;
+
+use either::Either;
+use immt_ontology::{
+    content::{
+        checking::ModuleChecker, declarations::{symbols::Symbol, Declaration, DeclarationTrait, OpenDeclaration}, modules::{Module, OpenModule}, terms::Term, ContentReference, ModuleLike
+    }, narration::{
+        checking::DocumentChecker, documents::{Document, UncheckedDocument}, paragraphs::LogicalParagraph, DocumentElement
+    }, uris::{ArchiveURITrait, ContentURI, ContentURITrait, DocumentURI, ModuleURI, SymbolURI}, Checked, LocalBackend, MaybeResolved, Unchecked
+};
+
+use crate::{backend::{AnyBackend, AsChecker, Backend}, formats::{BuildArtifactTypeId, OMDOC}};
+
+use super::{BuildArtifact, BuildResult, BuildResultArtifact, BuildTask, Dependency};
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum CheckError {
+    /// The unchecked OMDoc to be checked has not been built (yet)
+    MissingInput { document:DocumentURI },
+    UnresolvedImport { module:ModuleURI },
+    UnresolvedModule { module:ModuleURI },
+    UnknownSymbol { symbol:SymbolURI },
+    ArityMismatch { symbol:SymbolURI, expected:u8, found:usize }
+}
+impl CheckError {
+    /// The (top-level) module that could not be resolved, if this error is due to a missing module.
+    #[must_use]
+    pub fn missing_module(&self) -> Option<ModuleURI> {
+        match self {
+            Self::UnresolvedImport { module } | Self::UnresolvedModule { module } => Some(!module.clone()),
+            Self::MissingInput { .. } | Self::UnknownSymbol { .. } | Self::ArityMismatch { .. } => None
+        }
+    }
+}
+impl std::fmt::Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingInput { document } => write!(f,"missing input: no unchecked OMDoc found for {document}"),
+            Self::UnresolvedImport { module } => write!(f,"unresolved import: {module}"),
+            Self::UnresolvedModule { module } => write!(f,"unresolved module: {module}"),
+            Self::UnknownSymbol { symbol } => write!(f,"unknown symbol: {symbol}"),
+            Self::ArityMismatch { symbol, expected, found } =>
+                write!(f,"arity mismatch: {symbol} expects {expected} arguments, but is applied to {found}")
+        }
+    }
+}
+
+/// The checked OMDoc of a single source file, as yielded by the `check` target.
+pub struct CheckedOMDoc {
+    pub document: Document,
+    pub modules: Vec<Module>
+}
+
+impl BuildArtifact for CheckedOMDoc {
+    #[inline]
+    fn get_type_id() -> BuildArtifactTypeId where Self:Sized {
+        OMDOC
+    }
+    #[inline]
+    fn get_type(&self) -> BuildArtifactTypeId {
+        OMDOC
+    }
+    fn write(&self,path:&Path) -> Result<(),std::io::Error> {
+        let file = std::fs::File::create(path)?;
+        let mut buf = std::io::BufWriter::new(file);
+        bincode::serde::encode_into_std_write((&self.document,&self.modules), &mut buf, bincode::config::standard())
+            .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+    /// Loads the stored OMDoc and checks it (again) against `backend`.
+    fn load(path:&Path,backend:&AnyBackend) -> Result<Self,std::io::Error> where Self:Sized {
+        let file = std::fs::File::open(path)?;
+        let file = std::io::BufReader::new(file);
+        let (document,modules) : (UncheckedDocument,Vec<OpenModule<Unchecked>>) =
+            bincode::serde::decode_from_reader(file, bincode::config::standard())
+            .map_err(std::io::Error::other)?;
+        let (omdoc,errors) = check_omdoc(backend, document, modules);
+        if !errors.is_empty() {
+            tracing::warn!(target:"checking","{}: {} errors when checking stored OMDoc",path.display(),errors.len());
+        }
+        Ok(omdoc)
+    }
+    #[inline]
+    fn as_any(&self) -> &dyn std::any::Any {self}
+}
+
+struct BuildChecker<'a> {
+    inner: AsChecker<'a,AnyBackend>,
+    local: Vec<Module>,
+    errors: Vec<CheckError>,
+}
+
+impl BuildChecker<'_> {
+    fn error(&mut self,e:CheckError) {
+        if !self.errors.contains(&e) {
+            self.errors.push(e);
+        }
+    }
+
+    fn missing_module(&mut self,uri:&ModuleURI,import:bool) {
+        self.error(if import {
+            CheckError::UnresolvedImport { module: uri.clone() }
+        } else {
+            CheckError::UnresolvedModule { module: uri.clone() }
+        });
+    }
+
+    fn check_symbol(&mut self,uri:&SymbolURI,applied_to:Option<usize>) {
+        let Some(s) = self.get_declaration::<Symbol>(uri) else {
+            self.error(CheckError::UnknownSymbol { symbol: uri.clone() });
+            return
+        };
+        let expected = s.as_ref().arity.num();
+        if let Some(found) = applied_to {
+            if found != expected as usize {
+                self.error(CheckError::ArityMismatch { symbol: uri.clone(), expected, found });
+            }
+        }
+    }
+
+    fn check_term(&mut self,tm:&Term) {
+        for t in tm.subterm_iter() {
+            match t {
+                Term::OMA { head, args } => if let Term::OMID(ContentURI::Symbol(s)) = &**head {
+                    self.check_symbol(s,Some(args.len()));
+                },
+                Term::OMID(ContentURI::Symbol(s)) => self.check_symbol(s,None),
+                _ => ()
+            }
+        }
+    }
+
+    fn check_module(&mut self,m:&MaybeResolved<ModuleLike>,import:bool) {
+        if !m.is_resolved() {
+            let uri = m.id().into_owned();
+            self.missing_module(&uri,import);
+        }
+    }
+}
+
+impl LocalBackend for BuildChecker<'_> {
+    #[inline]
+    fn get_document(&mut self, uri: &DocumentURI) -> Option<Document> {
+        self.inner.get_document(uri)
+    }
+    fn get_module(&mut self, uri: &ModuleURI) -> Option<ModuleLike> {
+        let top = !uri.clone();
+        if let Some(m) = self.local.iter().find(|m| *m.uri() == top) {
+            return ModuleLike::in_module(m, uri.name())
+        }
+        self.inner.get_module(uri)
+    }
+    fn get_declaration<T: DeclarationTrait>(&mut self, uri: &SymbolURI) -> Option<ContentReference<T>> {
+        let m = self.get_module(uri.module())?;
+        ContentReference::new(&m, uri.name())
+    }
+}
+
+impl DocumentChecker for BuildChecker<'_> {
+    #[inline]
+    fn open(&mut self, _elem: &mut DocumentElement<Unchecked>) {}
+    fn close(&mut self, elem: &mut DocumentElement<Checked>) {
+        match elem {
+            DocumentElement::Module { module, .. } => self.check_module(module,false),
+            DocumentElement::ImportModule(m) | DocumentElement::UseModule(m) => self.check_module(m,true),
+            DocumentElement::SymbolDeclaration(s) if !s.is_resolved() => {
+                let symbol = s.id().into_owned();
+                self.error(CheckError::UnknownSymbol { symbol });
+            }
+            DocumentElement::SymbolReference { uri, .. } |
+            DocumentElement::Definiendum { uri, .. } => self.check_symbol(uri,None),
+            DocumentElement::TopTerm { term, .. } => self.check_term(term),
+            DocumentElement::Paragraph(LogicalParagraph { fors, .. }) => {
+                for (s,tm) in fors.iter() {
+                    self.check_symbol(s,None);
+                    if let Some(tm) = tm { self.check_term(tm); }
+                }
+            }
+            _ => ()
+        }
+    }
+}
+
+impl ModuleChecker for BuildChecker<'_> {
+    #[inline]
+    fn open(&mut self, _elem: &mut OpenDeclaration<Unchecked>) {}
+    fn close(&mut self, elem: &mut Declaration) {
+        match elem {
+            Declaration::Import(m) => self.check_module(m,true),
+            Declaration::Symbol(s) => {
+                if let Some(tp) = &s.tp { self.check_term(tp); }
+                if let Some(df) = &s.df { self.check_term(df); }
+            }
+            Declaration::Morphism(m) => {
+                self.check_module(&m.domain,true);
+                for a in &m.assignments {
+                    self.check_symbol(&a.original,None);
+                    if let Some(df) = &a.definiens { self.check_term(df); }
+                }
+                m.add_induced_symbols();
+            }
+            _ => ()
+        }
+    }
+}
+
+/// Checks an unchecked document and the modules declared in it against the backend.
+/// Returns the checked OMDoc together with all errors found, which are empty iff the
+/// document is well-formed.
+pub fn check_omdoc(backend:&AnyBackend,document:UncheckedDocument,modules:Vec<OpenModule<Unchecked>>) -> (CheckedOMDoc,Vec<CheckError>) {
+    let mut checker = BuildChecker {
+        inner: backend.as_checker(),
+        local: Vec::new(),
+        errors: Vec::new()
+    };
+    for m in modules {
+        let m = m.check(&mut checker);
+        checker.local.push(m);
+    }
+    let document = document.check(&mut checker);
+    let BuildChecker { local:modules, errors, .. } = checker;
+    (CheckedOMDoc { document, modules },errors)
+}
+
+pub(crate) fn check(backend:&AnyBackend,task:&BuildTask) -> BuildResult {
+    let Some((document,modules)) = backend.with_local_archive(task.archive().archive_id(), |a|
+        a.and_then(|a| a.load_unchecked(task.rel_path()))
+    ) else {
+        let error = CheckError::MissingInput { document:task.document_uri() };
+        return BuildResult {
+            log:Either::Left(error.to_string()),
+            result:Err(Vec::new())
+        }
+    };
+    let (omdoc,errors) = check_omdoc(backend, document, modules);
+    if errors.is_empty() {
+        return BuildResult {
+            log:Either::Left(String::new()),
+            result:Ok(BuildResultArtifact::Data(Box::new(omdoc)))
+        }
+    }
+    let mut log = String::new();
+    let mut missing = Vec::new();
+    for e in &errors {
+        log.push_str(&e.to_string());
+        log.push('\n');
+        if let Some(m) = e.missing_module() {
+            if !missing.contains(&m) { missing.push(m); }
+        }
+    }
+    BuildResult {
+        log:Either::Left(log),
+        result:Err(missing.into_iter().map(|uri| Dependency::Logical { uri, strict:true }).collect())
+    }
+}
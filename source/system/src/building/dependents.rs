@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use immt_ontology::uris::{ArchiveId, ArchiveURITrait, ModuleURI};
+use immt_utils::{prelude::HMap, vecmap::VecSet};
+
+use crate::backend::archives::Archive;
+
+use super::{BuildTask, Dependency};
+
+/// A source file, identified by its archive and relative path.
+pub type FileKey = (ArchiveId,std::sync::Arc<str>);
+
+/// Why a task was added to a queue without having been requested (or stale) itself.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct PulledIn {
+    pub archive:ArchiveId,
+    pub rel_path:std::sync::Arc<str>,
+    pub module:Option<ModuleURI>
+}
+impl std::fmt::Display for PulledIn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.module {
+            Some(m) => write!(f,"depends on {m} in [{}]{}",self.archive,self.rel_path),
+            None => write!(f,"depends on [{}]{}",self.archive,self.rel_path)
+        }
+    }
+}
+
+/// The name of the file (in the output directory of a document) its dependencies are stored in,
+/// so that the [`DependentsIndex`] survives restarts.
+const DEPENDENCIES_FILE: &str = "dependencies";
+
+/// Something a file depends on
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum Target {
+    File(FileKey),
+    Module(ModuleURI)
+}
+
+#[derive(serde::Serialize,serde::Deserialize)]
+struct DependenciesFile {
+    archive:ArchiveId,
+    rel_path:String,
+    files:Vec<(ArchiveId,String)>,
+    modules:Vec<ModuleURI>
+}
+
+/// Reverse dependency index: maps source files and modules to the files that depend on them.
+/// Populated from the dependencies registered for build tasks, and from the
+/// dependencies stored by previous builds (see [`load_archives`](Self::load_archives)).
+#[derive(Debug,Default)]
+pub struct DependentsIndex {
+    files: HMap<ArchiveId,HMap<std::sync::Arc<str>,VecSet<FileKey>>>,
+    modules: HMap<ModuleURI,VecSet<FileKey>>,
+    /// the registered dependencies of every file, for removing them again
+    forward: HMap<FileKey,Vec<Target>>
+}
+
+impl DependentsIndex {
+    /// (Re)registers the dependencies of all steps of `task`, replacing
+    /// previously registered ones.
+    pub fn register(&mut self,task:&BuildTask) {
+        let key = (task.archive().archive_id().clone(),task.0.rel_path.clone());
+        let mut targets = Vec::new();
+        for s in task.steps() {
+            for d in s.0.requires.read().iter() {
+                let target = match d {
+                    Dependency::Physical { task:t, .. } => Target::File((t.archive.clone(),t.rel_path.clone())),
+                    Dependency::Resolved { task:t, .. } => Target::File((t.archive().archive_id().clone(),t.0.rel_path.clone())),
+                    Dependency::Logical { uri, .. } => Target::Module(uri.clone())
+                };
+                if !targets.contains(&target) { targets.push(target); }
+            }
+        }
+        self.insert(key,targets);
+    }
+
+    fn insert(&mut self,key:FileKey,targets:Vec<Target>) {
+        self.remove(&key);
+        for t in &targets {
+            let set = match t {
+                Target::File((archive,rel_path)) => self.files
+                    .entry(archive.clone()).or_default()
+                    .entry(rel_path.clone()).or_default(),
+                Target::Module(uri) => self.modules.entry(uri.clone()).or_default()
+            };
+            set.insert(key.clone());
+        }
+        self.forward.insert(key,targets);
+    }
+
+    fn remove(&mut self,key:&FileKey) {
+        let Some(targets) = self.forward.remove(key) else { return };
+        for t in targets {
+            let set = match &t {
+                Target::File((archive,rel_path)) => self.files.get_mut(archive).and_then(|m| m.get_mut(rel_path)),
+                Target::Module(uri) => self.modules.get_mut(uri)
+            };
+            if let Some(set) = set {
+                set.0.retain(|k| k != key);
+            }
+        }
+    }
+
+    /// The files directly depending on the file `[archive]rel_path`, which
+    /// declares the given `modules`.
+    #[must_use]
+    pub fn dependents(&self,archive:&ArchiveId,rel_path:&str,modules:&[ModuleURI]) -> Vec<(FileKey,Option<ModuleURI>)> {
+        let mut ret = Vec::new();
+        if let Some(v) = self.files.get(archive).and_then(|m| m.get(rel_path)) {
+            ret.extend(v.iter().map(|k| (k.clone(),None)));
+        }
+        for m in modules {
+            if let Some(v) = self.modules.get(m) {
+                ret.extend(v.iter().map(|k| (k.clone(),Some(m.clone()))));
+            }
+        }
+        ret
+    }
+
+    /// Stores the registered dependencies of `[archive]rel_path` in `out`
+    /// (the output directory of the document).
+    pub(crate) fn save(&self,archive:&ArchiveId,rel_path:&str,out:&Path) -> std::io::Result<()> {
+        let key = (archive.clone(),rel_path.into());
+        let Some(targets) = self.forward.get(&key) else { return Ok(()) };
+        let mut file = DependenciesFile { archive:archive.clone(), rel_path:rel_path.to_string(), files:Vec::new(), modules:Vec::new() };
+        for t in targets { match t {
+            Target::File((a,p)) => file.files.push((a.clone(),p.to_string())),
+            Target::Module(m) => file.modules.push(m.clone())
+        }}
+        std::fs::create_dir_all(out)?;
+        let f = std::fs::File::create(out.join(DEPENDENCIES_FILE))?;
+        let mut buf = std::io::BufWriter::new(f);
+        bincode::serde::encode_into_std_write(&file, &mut buf, bincode::config::standard())
+            .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    /// Loads the dependencies stored by previous builds of documents in the given archives;
+    /// dependencies registered in the meantime take precedence.
+    pub fn load_archives(&mut self,archives:&[Archive]) {
+        for a in archives {
+            let Archive::Local(a) = a;
+            self.load_dir(a.out_dir());
+        }
+    }
+
+    /// Loads all dependencies files (recursively) in the directory `out`.
+    fn load_dir(&mut self,out:&Path) {
+        if !out.is_dir() { return }
+        for entry in walkdir::WalkDir::new(out)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() == DEPENDENCIES_FILE)
+        {
+            let Ok(f) = std::fs::File::open(entry.path()) else { continue };
+            let mut f = std::io::BufReader::new(f);
+            let file = match bincode::serde::decode_from_std_read::<DependenciesFile,_,_>(&mut f, bincode::config::standard()) {
+                Ok(file) => file,
+                Err(e) => {
+                    tracing::warn!("Failed to load dependencies {}: {e}",entry.path().display());
+                    continue
+                }
+            };
+            let key = (file.archive,file.rel_path.into());
+            if self.forward.contains_key(&key) { continue }
+            let targets = file.files.into_iter().map(|(a,p)| Target::File((a,p.into())))
+                .chain(file.modules.into_iter().map(Target::Module))
+                .collect();
+            self.insert(key,targets);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use immt_ontology::uris::{ArchiveId, ArchiveURI, BaseURI, ModuleURI};
+    use super::{DependentsIndex, Target};
+
+    fn key(archive:&str,rel_path:&str) -> super::FileKey {
+        (ArchiveId::new(archive),rel_path.into())
+    }
+
+    #[test]
+    fn reregistering_replaces_dependencies() {
+        let mut index = DependentsIndex::default();
+        let a = key("some/archive","a.tex");
+        let b = key("some/archive","b.tex");
+        let c = key("other/archive","c.tex");
+        index.insert(c.clone(),vec![Target::File(a.clone())]);
+        assert_eq!(index.dependents(&a.0,&a.1,&[]),vec![(c.clone(),None)]);
+
+        index.insert(c.clone(),vec![Target::File(b.clone())]);
+        assert!(index.dependents(&a.0,&a.1,&[]).is_empty());
+        assert_eq!(index.dependents(&b.0,&b.1,&[]),vec![(c.clone(),None)]);
+
+        index.remove(&c);
+        assert!(index.dependents(&b.0,&b.1,&[]).is_empty());
+        assert!(index.forward.is_empty());
+    }
+
+    #[test]
+    fn module_dependents() {
+        let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        let module : ModuleURI = (archive | "module").expect("is valid");
+        let a = key("some/archive","a.tex");
+        let b = key("some/archive","b.tex");
+        let c = key("other/archive","c.tex");
+        let mut index = DependentsIndex::default();
+        index.insert(b.clone(),vec![Target::Module(module.clone())]);
+        index.insert(c.clone(),vec![Target::File(a.clone()),Target::Module(module.clone())]);
+
+        // only files declaring the module are depended on via the module
+        assert_eq!(index.dependents(&a.0,&a.1,&[]),vec![(c.clone(),None)]);
+        let mut dependents = index.dependents(&a.0,&a.1,std::slice::from_ref(&module));
+        dependents.sort_by(|(l,_),(r,_)| l.1.cmp(&r.1));
+        assert_eq!(dependents,vec![
+            (b.clone(),Some(module.clone())),
+            (c.clone(),None),
+            (c.clone(),Some(module.clone()))
+        ]);
+
+        index.remove(&b);
+        assert_eq!(index.dependents(&b.0,&b.1,std::slice::from_ref(&module)),vec![(c,Some(module))]);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+        let module : ModuleURI = (archive | "module").expect("is valid");
+        let a = key("some/archive","a.tex");
+        let b = key("some/archive","sub/b.tex");
+        let c = key("other/archive","c.tex");
+        let dir = tempfile::tempdir().expect("can create temporary directory");
+
+        let mut index = DependentsIndex::default();
+        index.insert(b.clone(),vec![Target::File(a.clone()),Target::Module(module.clone())]);
+        index.insert(c.clone(),vec![Target::File(b.clone())]);
+        index.save(&b.0,&b.1,&dir.path().join("sub/b")).expect("can save dependencies");
+        index.save(&c.0,&c.1,&dir.path().join("c")).expect("can save dependencies");
+        // nothing registered => nothing stored
+        index.save(&a.0,&a.1,&dir.path().join("a")).expect("can save dependencies");
+        assert!(!dir.path().join("a").exists());
+
+        let mut loaded = DependentsIndex::default();
+        // dependencies registered in the meantime take precedence
+        loaded.insert(c.clone(),vec![Target::File(a.clone())]);
+        loaded.load_dir(dir.path());
+        assert_eq!(loaded.forward.get(&b),index.forward.get(&b));
+        assert_eq!(loaded.forward.get(&c),Some(&vec![Target::File(a.clone())]));
+        let mut dependents = loaded.dependents(&a.0,&a.1,&[]);
+        dependents.sort_by(|(l,_),(r,_)| l.1.cmp(&r.1));
+        assert_eq!(dependents,vec![(c,None),(b.clone(),None)]);
+        assert_eq!(loaded.dependents(&b.0,&b.1,&[module.clone()]),vec![(b,Some(module))]);
+    }
+}
@@ -5,12 +5,16 @@ use immt_ontology::{languages::Language, uris::{ArchiveId, ArchiveURI, ArchiveUR
 use immt_utils::{time::Eta, triomphe::Arc, vecmap::{VecMap, VecSet}};
 use parking_lot::RwLock;
 
-use crate::formats::{BuildArtifactTypeId, BuildTargetId};
+use crate::{backend::AnyBackend, formats::{BuildArtifactTypeId, BuildTargetId}};
 
 pub mod queue_manager;
+pub mod checking;
+mod dependents;
+pub use dependents::{DependentsIndex,FileKey,PulledIn};
 mod queue;
 pub use queue::QueueName;
 mod queueing;
+pub mod report;
 
 #[cfg(all(test,feature="tokio"))]
 mod tests;
@@ -51,7 +55,8 @@ struct BuildTaskI {
     archive: ArchiveURI,
     steps: Box<[BuildStep]>,
     source: Either<PathBuf,String>,
-    rel_path:std::sync::Arc<str>
+    rel_path:std::sync::Arc<str>,
+    pulled_in_by:Option<PulledIn>
 }
 
 #[derive(Debug,Clone,PartialEq,Eq)]
@@ -89,6 +94,12 @@ impl BuildTask {
         &self.0.rel_path
     }
 
+    /// If this task was only queued because a file it depends on is stale, that dependency.
+    #[inline]#[must_use]
+    pub const fn pulled_in_by(&self) -> Option<&PulledIn> {
+        self.0.pulled_in_by.as_ref()
+    }
+
     #[inline]#[must_use]
     pub fn steps(&self) -> &[BuildStep] {
         &self.0.steps
@@ -112,6 +123,7 @@ impl BuildTask {
             archive: self.0.archive.archive_id().clone(),
             rel_path: self.0.rel_path.clone(),
             steps: self.steps().iter().map(|s| (s.0.target,*s.0.state.read())).collect(),
+            pulled_in_by: self.0.pulled_in_by.clone()
         }
     }
 }
@@ -148,8 +160,10 @@ impl BuildStep {
 
 pub trait BuildArtifact: Any+'static {
     fn get_type_id() -> BuildArtifactTypeId where Self:Sized;
+    /// Loads the artifact stored at `p`; `backend` is the backend owning it, against which
+    /// references in the artifact are resolved.
     /// #### Errors
-    fn load(p:&Path) -> Result<Self,std::io::Error> where Self:Sized;
+    fn load(p:&Path,backend:&AnyBackend) -> Result<Self,std::io::Error> where Self:Sized;
     fn get_type(&self) -> BuildArtifactTypeId;
     /// ### Errors
     fn write(&self,path:&Path) -> Result<(),std::io::Error>;
@@ -188,7 +202,8 @@ pub struct QueueEntry {
     pub id:BuildTaskId,
     pub archive:ArchiveId,
     pub rel_path:std::sync::Arc<str>,
-    pub steps:VecMap<BuildTargetId,TaskState>
+    pub steps:VecMap<BuildTargetId,TaskState>,
+    pub pulled_in_by:Option<PulledIn>
 }
 
 #[derive(Debug,Clone)]
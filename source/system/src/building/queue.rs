@@ -1,11 +1,11 @@
-use std::{collections::VecDeque, num::NonZeroU32};
+use std::{collections::VecDeque, num::NonZeroU32, path::PathBuf};
 use either::Either;
-use immt_ontology::uris::{ArchiveId,ArchiveURITrait};
+use immt_ontology::uris::{ArchiveId,ArchiveURITrait,ModuleURI};
 use immt_utils::{change_listener::{ChangeListener, ChangeSender}, prelude::{HMap, TreeLike}, time::Delta, triomphe::Arc};
 use parking_lot::RwLock;
 use tracing::{instrument, Instrument};
-use crate::{backend::{archives::{source_files::SourceEntry, ArchiveOrGroup}, AnyBackend, Backend}, formats::{BuildTargetId, FormatOrTargets}};
-use super::{queue_manager::{QueueId, Semaphore}, BuildResult, BuildTask, BuildTaskId, Eta, QueueMessage, TaskRef, TaskState };
+use crate::{backend::{archives::{source_files::SourceEntry, Archive, ArchiveOrGroup}, AnyBackend, Backend, GlobalBackend}, formats::{BuildTargetId, FormatOrTargets}};
+use super::{queue_manager::{QueueId, Semaphore}, report::{BuildReport, TaskOutcome, TaskReport}, BuildResult, BuildTask, BuildTaskId, Dependency, Eta, QueueMessage, TaskRef, TaskState };
 use immt_utils::time::Timestamp;
 
 #[derive(Debug)]
@@ -57,7 +57,8 @@ pub(super) struct QueueI {
   pub id:QueueId,
   pub(super) map:RwLock<TaskMap>,
   sender:ChangeSender<QueueMessage>,
-  pub(super) state:RwLock<QueueState>
+  pub(super) state:RwLock<QueueState>,
+  report:RwLock<Option<BuildReport>>
 }
 
 #[derive(Debug,Clone)]
@@ -70,7 +71,8 @@ impl Queue {
       backend,
       map:RwLock::default(),
       sender:ChangeSender::new(32),
-      state:RwLock::new(QueueState::Idle) 
+      state:RwLock::new(QueueState::Idle),
+      report:RwLock::new(None)
     }))
   }
 
@@ -105,6 +107,10 @@ impl Queue {
   #[inline]#[must_use]
   pub fn name(&self) -> &QueueName { &self.0.name }
 
+  /// The report of the last completed run of this queue, if any
+  #[must_use]
+  pub fn last_report(&self) -> Option<BuildReport> { self.0.report.read().clone() }
+
   #[instrument(level = "info",
     target = "buildqueue",
     name = "Running buildqueue",
@@ -117,7 +123,7 @@ impl Queue {
     let map = self.0.map.read();
     let mut running = RunningQueue::new(map.total);
     tracing::info_span!("sorting...").in_scope(|| {
-      Self::sort(&map,&mut running);
+      Self::sort(map.map.values().cloned().collect(),&mut running);
       tracing::info!("Done");
     });
     self.0.sender.lazy_send(|| QueueMessage::Started { 
@@ -140,10 +146,12 @@ impl Queue {
 
   #[inline]
   fn run_sync(&self) {
-    while let Some((task,id)) = self.get_next() {
-      self.run_task(task, id);
+    loop {
+      while let Some((task,id)) = self.get_next() {
+        self.run_task(task, id);
+      }
+      if self.finish() { break }
     }
-    self.finish();
   }
 
   #[cfg(feature="tokio")]
@@ -153,25 +161,65 @@ impl Queue {
         break
       };
       let Some((task,id)) = self.get_next() else {
-        break
+        if self.finish() { break }
+        continue
       };
       let selfclone = self.clone();
       tokio::task::spawn_blocking(move || selfclone.run_task_async(task,id,permit));
     }
-    self.finish();
   }
 
-  fn finish(&self) {
-    let state = &mut *self.0.state.write();
-    let QueueState::Running(RunningQueue{done,failed,..}) = state else {unreachable!()};
+  /// Returns `false` (without finishing) if tasks have been scheduled in the meantime
+  fn finish(&self) -> bool {
+    let mut state = self.0.state.write();
+    let QueueState::Running(RunningQueue{queue,blocked,done,failed,reports,timer,..}) = &mut *state else {unreachable!()};
+    if !queue.is_empty() || !blocked.is_empty() { return false }
     let done = std::mem::take(done);
     let failed = std::mem::take(failed);
+    *self.0.report.write() = Some(BuildReport {
+      queue:self.0.name.to_string(),
+      started:timer.started,
+      finished:Timestamp::now(),
+      tasks:std::mem::take(reports)
+    });
     self.0.sender.lazy_send(||
       QueueMessage::Finished { 
         failed: failed.iter().map(BuildTask::as_message).collect(), 
         done: done.iter().map(BuildTask::as_message).collect() }
     );
     *state = QueueState::Finished(FinishedQueue{done,failed});
+    drop(state);
+    if let Some(report) = &*self.0.report.read() {
+      if let Err(e) = report.write_junit(&crate::settings::Settings::get().log_dir.join("reports")) {
+        tracing::warn!(target:"buildqueue","Failed to write build report: {e}");
+      }
+    }
+    true
+  }
+
+  /// [`start`](Self::start) only sorts the tasks present at that point; tasks
+  /// (re)enqueued while the queue is running are scheduled here.
+  fn schedule_new(&self) {
+    let mut state = self.0.state.write();
+    let QueueState::Running(running) = &mut *state else { return };
+    let new = self.0.map.read().map.values().filter(|t|
+      !running.running.contains(t) &&
+      t.steps().iter().all(|s| *s.0.state.read() == TaskState::None)
+    ).cloned().collect::<Vec<_>>();
+    if new.is_empty() { return }
+    running.queue.retain(|t| !new.contains(t));
+    running.blocked.retain(|t| !new.contains(t));
+    running.done.retain(|t| !new.contains(t));
+    running.failed.retain(|t| !new.contains(t));
+    running.timer.steps += new.iter().map(|t| t.steps().len()).sum::<usize>();
+    Self::sort(new,running);
+    self.0.sender.lazy_send(|| QueueMessage::Started {
+      running: running.running.iter().map(BuildTask::as_message).collect(),
+      queue: running.queue.iter().map(BuildTask::as_message).collect(),
+      blocked: running.blocked.iter().map(BuildTask::as_message).collect(),
+      failed: running.failed.iter().map(BuildTask::as_message).collect(),
+      done: running.done.iter().map(BuildTask::as_message).collect()
+    });
   }
 
 
@@ -197,7 +245,8 @@ impl Queue {
           rel_path: t.0.rel_path.clone(),
           archive: t.0.archive.clone(),
           steps: t.0.steps.clone(),
-          source: t.0.source.clone()
+          source: t.0.source.clone(),
+          pulled_in_by: t.0.pulled_in_by.clone()
         }))
       );
       map.counter = map.counter.saturating_add(1);
@@ -218,6 +267,10 @@ impl Queue {
     self.0.sender.lazy_send(|| QueueMessage::TaskStarted{
       id:task.0.id,target
     });
+    let started = Timestamp::now();
+    let source_hash = self.0.backend.with_archive(task.archive().archive_id(), |a|
+      a.and_then(|a| a.source_hash(task.rel_path()))
+    );
     let BuildResult {log,result} = 
       tracing::info_span!(target:"buildqueue","Running task",
         archive = %task.0.archive.archive_id(),
@@ -225,15 +278,42 @@ impl Queue {
         format = %target
       ).in_scope(|| (target.run())(&self.0.backend,&task));
     let (idx,_) = task.steps().iter().enumerate().find(|(_,s)| s.0.target == target).unwrap_or_else(|| unreachable!());
+    // file I/O happens before locking the queue state
+    let (result,report) = match result {
+      Err(deps) => {
+        let log = self.0.backend.with_archive(task.archive().archive_id(), |a| {
+          let a = a?;
+          a.save(task.rel_path(), log, target,None);
+          Some(a.get_log(task.rel_path(), target))
+        });
+        (Err(deps),TaskReport::new(
+          task.archive().archive_id().clone(),task.rel_path(),target.name(),
+          started,TaskOutcome::Failed,log.as_deref()
+        ))
+      }
+      Ok(data) => {
+        let deps = self.dependency_paths(&task, target);
+        let log = self.0.backend.with_archive(task.archive().archive_id(), |a| {
+          let a = a?;
+          a.save(task.rel_path(), log,target, Some(data));
+          if let Some(source) = source_hash {
+            a.save_hashes(task.rel_path(), target, source, &deps);
+          }
+          self.0.backend.save_dependencies(a, task.rel_path());
+          Some(a.get_log(task.rel_path(), target))
+        });
+        (Ok(()),TaskReport::new(
+          task.archive().archive_id().clone(),task.rel_path(),target.name(),
+          started,TaskOutcome::Success,log.as_deref()
+        ))
+      }
+    };
     let mut lock = self.0.state.write();
     let QueueState::Running(ref mut state) = &mut *lock else {unreachable!()};
+    state.reports.push(report);
 
-    match result {
+    let success = match result {
       Err(_deps) => { // TODO: handle dependencies
-        self.0.backend.with_archive(task.archive().archive_id(), |a| {
-          let Some(a) = a else {return};
-          a.save(task.rel_path(), log, target,None);
-        });
         let num = (task.steps().len() - idx) as u8;
         state.running.retain(|t| *t != task);
         let mut found = false;
@@ -250,12 +330,9 @@ impl Queue {
           id:task.0.id,target,eta
         });
         state.failed.push(task);
+        false
       }
-      Ok(data) => {
-        self.0.backend.with_archive(task.archive().archive_id(), |a| {
-          let Some(a) = a else {return};
-          a.save(task.rel_path(), log,target, Some(data));
-        });
+      Ok(()) => {
         state.running.retain(|t| *t != task);
 
         let mut found = false;
@@ -274,12 +351,44 @@ impl Queue {
         self.0.sender.lazy_send(|| QueueMessage::TaskSuccess {
           id:task.0.id,target,eta
         });
-        if requeue { state.queue.push_front(task);}
-        else {state.done.push(task);}
+        if requeue { state.queue.push_front(task.clone());}
+        else {state.done.push(task.clone());}
+        true
       }
+    };
+    drop(lock);
+    // the search index has its own lock; no need to block the queue while reading the file
+    if let (true,AnyBackend::Global(b)) = (success,&self.0.backend) {
+      b.with_archive(task.archive().archive_id(), |a|
+        if let Some(a) = a { b.search_index().reload(a, task.rel_path()); }
+      );
     }
   }
 
+  fn dependency_paths(&self,task:&BuildTask,target:BuildTargetId) -> Vec<PathBuf> {
+    let Some(step) = task.get_step(target) else { return Vec::new() };
+    step.0.requires.read().iter().filter_map(|d| match d {
+      Dependency::Resolved { task, .. } => match task.source() {
+        Either::Left(p) => Some(p.to_path_buf()),
+        Either::Right(_) => None
+      },
+      Dependency::Physical { task, .. } => self.0.backend.with_local_archive(&task.archive, |a|
+        a.map(|a| a.source_dir().join(&*task.rel_path))
+      ),
+      Dependency::Logical { uri, .. } => self.module_source(uri)
+    }).collect()
+  }
+
+  /// The source file of the document declaring the given module, according to the relational store
+  fn module_source(&self,module:&ModuleURI) -> Option<PathBuf> {
+    let doc = GlobalBackend::get().triple_store().document_of(&!module.clone())?;
+    self.0.backend.with_archive(doc.archive_id(), |a| a.and_then(|a| {
+      let rel_path = a.find_source(&doc)?;
+      let Archive::Local(a) = a;
+      Some(rel_path.split('/').fold(a.source_dir(),|p,s| p.join(s)))
+    }))
+  }
+
   fn maybe_restart(&self) {
     let mut state = self.0.state.write();
     if let QueueState::Finished(_) = &mut *state {
@@ -297,7 +406,7 @@ impl Queue {
     if let AnyBackend::Sandbox(b) = &self.0.backend {
       b.require(id);
     }
-    self.0.backend.with_archive_or_group(id, |g| match g {
+    let count = self.0.backend.with_archive_or_group(id, |g| match g {
       None => 0,
       Some(ArchiveOrGroup::Archive(id)) => {
         self.0.backend.with_archive(id, |a| {
@@ -305,7 +414,7 @@ impl Queue {
           archive.with_sources(|d| {
             let Some(d) = d.dfs() else {return 0};
             let map = &mut *self.0.map.write();
-            Self::enqueue(map,&self.0.backend,archive,target, stale_only, 
+            Self::enqueue(map,&self.0.backend,archive,target, stale_only, None, 
               d.filter_map(|e| match e {
                 SourceEntry::Dir(_) => None,
                 SourceEntry::File(f) => Some(f)
@@ -326,7 +435,7 @@ impl Queue {
             let Some(archive) = a else {return 0};
             archive.with_sources(|d| {
               let Some(d) = d.dfs() else {return 0};
-              Self::enqueue(map,&self.0.backend,archive,target, stale_only, 
+              Self::enqueue(map,&self.0.backend,archive,target, stale_only, None, 
                 d.filter_map(|e| match e {
                   SourceEntry::Dir(_) => None,
                   SourceEntry::File(f) => Some(f)
@@ -337,7 +446,14 @@ impl Queue {
         }
         ret
       }
-    })
+    });
+    let count = if stale_only {
+      let map = &mut *self.0.map.write();
+      let roots = map.map.keys().cloned().collect();
+      count + Self::enqueue_dependents(map,&self.0.backend,target.targets(),roots)
+    } else { count };
+    self.schedule_new();
+    count
   }
 
   #[instrument(level = "info",
@@ -350,14 +466,14 @@ impl Queue {
     if let AnyBackend::Sandbox(b) = &self.0.backend {
       b.require(id);
     }
-    self.0.backend.with_archive(id, |archive| {
+    let count = self.0.backend.with_archive(id, |archive| {
       let Some(archive) = archive else { return 0 };
       archive.with_sources(|d| {
         match rel_path {
           None => {
             let Some(d) = d.dfs() else {return 0};
             let map = &mut *self.0.map.write();
-            Self::enqueue(map,&self.0.backend,archive,target, stale_only, 
+            Self::enqueue(map,&self.0.backend,archive,target, stale_only, None, 
               d.filter_map(|e| match e {
                 SourceEntry::Dir(_) => None,
                 SourceEntry::File(f) => Some(f)
@@ -370,7 +486,7 @@ impl Queue {
               Either::Left(d) => {
                 let Some(d) = d.dfs() else {return 0};
                 let map = &mut *self.0.map.write();
-                Self::enqueue(map,&self.0.backend,archive,target, stale_only, 
+                Self::enqueue(map,&self.0.backend,archive,target, stale_only, None, 
                   d.filter_map(|e| match e {
                     SourceEntry::Dir(_) => None,
                     SourceEntry::File(f) => Some(f)
@@ -379,13 +495,55 @@ impl Queue {
               }
               Either::Right(f) => {
                 let map = &mut *self.0.map.write();
-                Self::enqueue(map,&self.0.backend,archive,target, stale_only, std::iter::once(f))
+                Self::enqueue(map,&self.0.backend,archive,target, stale_only, None, std::iter::once(f))
               }
             }
           }
         }
       })
-    })
+    });
+    let count = if stale_only {
+      let map = &mut *self.0.map.write();
+      let roots = map.map.keys().cloned().collect();
+      count + Self::enqueue_dependents(map,&self.0.backend,target.targets(),roots)
+    } else { count };
+    self.schedule_new();
+    count
+  }
+
+  /// Enqueues the given files (e.g. files changed on disk) with their respective
+  /// formats, followed by all files depending on them. Unlike calling
+  /// [`enqueue_archive`](Self::enqueue_archive) for every file, dependents are only
+  /// computed once for the whole batch.
+  #[instrument(level = "info",
+    target = "buildqueue",
+    name = "Queueing tasks",
+    skip_all
+  )]
+  pub fn enqueue_files(&self,files:&[(ArchiveId,Vec<String>)],stale_only:bool) -> usize {
+    self.maybe_restart();
+    if let AnyBackend::Sandbox(b) = &self.0.backend {
+      for (id,_) in files { b.require(id); }
+    }
+    let mut guard = self.0.map.write();
+    let map = &mut *guard;
+    let mut count = 0;
+    let mut roots = Vec::new();
+    for (id,paths) in files {
+      count += self.0.backend.with_archive(id, |archive| {
+        let Some(archive) = archive else { return 0 };
+        archive.with_sources(|d| paths.iter().map(|p| {
+          let Some(Either::Right(f)) = d.find(p) else { return 0 };
+          let n = Self::enqueue(map,&self.0.backend,archive,FormatOrTargets::Format(f.format),stale_only,None,std::iter::once(f));
+          if n > 0 { roots.push((id.clone(),f.relative_path.clone())); }
+          n
+        }).sum())
+      });
+    }
+    count += Self::enqueue_dependents(map,&self.0.backend,None,roots);
+    drop(guard);
+    self.schedule_new();
+    count
   }
 
 }
@@ -397,11 +555,12 @@ pub struct RunningQueue {
   pub(super) done:Vec<BuildTask>,
   pub(super) failed:Vec<BuildTask>,
   pub(super) running:Vec<BuildTask>,
+  reports:Vec<TaskReport>,
   timer:Timer
 }
 impl RunningQueue {
   fn new(total:usize) -> Self {
-    Self { queue:VecDeque::new(),failed:Vec::new(),blocked:Vec::new(),done:Vec::new(),running:Vec::new(),timer:Timer::new(total) }
+    Self { queue:VecDeque::new(),failed:Vec::new(),blocked:Vec::new(),done:Vec::new(),running:Vec::new(),reports:Vec::new(),timer:Timer::new(total) }
   }
 }
 
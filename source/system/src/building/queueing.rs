@@ -1,20 +1,19 @@
 use std::collections::hash_map::Entry;
 
 use either::Either;
-use immt_ontology::uris::{ArchiveURITrait, URIRefTrait};
+use immt_ontology::uris::{ArchiveId, ArchiveURITrait, URIRefTrait};
 use immt_utils::{triomphe::Arc, vecmap::VecSet};
 use parking_lot::RwLock;
 
 use crate::{backend::{archives::{source_files::{FileState, SourceFile}, Archive}, AnyBackend}, formats::{BuildTargetId, FormatOrTargets}};
 
-use super::{queue::{Queue, QueueState, RunningQueue, TaskMap}, BuildStep, BuildStepI, BuildTask, BuildTaskI, BuildTaskId, Dependency, TaskState};
+use super::{queue::{Queue, QueueState, RunningQueue, TaskMap}, BuildStep, BuildStepI, BuildTask, BuildTaskI, BuildTaskId, Dependency, PulledIn, TaskState};
 
 impl Queue {
 
   #[allow(clippy::significant_drop_in_scrutinee)]
-  pub(super) fn sort(map:&TaskMap,state:&mut RunningQueue) {
+  pub(super) fn sort(mut tasks:Vec<BuildTask>,state:&mut RunningQueue) {
     let RunningQueue {queue,done,blocked,failed,..} = state;
-    let mut tasks = map.map.values().cloned().collect::<Vec<_>>();
     let mut weak = true;
     while !tasks.is_empty() {
       let mut changed = false;
@@ -129,6 +128,7 @@ impl Queue {
     archive:&Archive,
     target:FormatOrTargets,
     stale_only:bool,
+    pulled_in_by:Option<&PulledIn>,
     files:I
   ) -> usize {
     let targets = match target {
@@ -169,6 +169,7 @@ impl Queue {
               )
             },
             rel_path:f.relative_path.clone(),
+            pulled_in_by:pulled_in_by.cloned()
           });
           e.insert(BuildTask(task_i.clone()));
           BuildTask(task_i)
@@ -183,12 +184,38 @@ impl Queue {
       };
       if let FormatOrTargets::Format(fmt) = target {
         (fmt.dependencies())(backend,&task);
+        backend.register_dependencies(&task);
         Self::process_dependencies(&task, map);
       }
     }
     count
   }
 
+  /// Transitively enqueues all files depending on the files `roots`
+  /// (e.g. because they import a module declared in one of them), across archives.
+  /// If no `targets` are given, dependents are built with their respective formats.
+  pub(super) fn enqueue_dependents(map:&mut TaskMap,backend:&AnyBackend,targets:Option<&[BuildTargetId]>,roots:Vec<(ArchiveId,std::sync::Arc<str>)>) -> usize {
+    let mut todo = roots;
+    let mut count = 0;
+    while let Some((archive,rel_path)) = todo.pop() {
+      for (key,reason) in backend.dependents_of(&archive, &rel_path) {
+        if map.map.contains_key(&key) { continue }
+        count += backend.with_archive(&key.0, |a| {
+          let Some(a) = a else {return 0};
+          a.with_sources(|d| {
+            let Some(Either::Right(f)) = d.find(&key.1) else {return 0};
+            let target = targets.map_or(FormatOrTargets::Format(f.format),FormatOrTargets::Targets);
+            Self::enqueue(map,backend,a,target,false,Some(&reason),std::iter::once(f))
+          })
+        });
+        if map.map.contains_key(&key) {
+          todo.push(key);
+        }
+      }
+    }
+    count
+  }
+
   fn process_dependencies(task:&BuildTask,map:&mut TaskMap) {
     for s in task.steps() {
       let key = task.get_task_ref(s.0.target);
@@ -0,0 +1,224 @@
+use std::{fmt::Write, path::Path};
+
+use immt_ontology::uris::ArchiveId;
+use immt_utils::time::Timestamp;
+
+/// The maximum number of error lines extracted from a single log file
+const MAX_ERROR_LINES: usize = 20;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,serde::Serialize,serde::Deserialize)]
+pub enum TaskOutcome {
+  Success,
+  Failed
+}
+
+/// The result of running a single build step.
+#[derive(Debug,Clone,serde::Serialize,serde::Deserialize)]
+pub struct TaskReport {
+  pub archive:ArchiveId,
+  pub rel_path:String,
+  pub target:String,
+  pub duration_ms:u64,
+  pub outcome:TaskOutcome,
+  /// error lines extracted from the log file of the step
+  pub errors:Vec<String>
+}
+
+/// A structured summary of a complete run of a build queue.
+#[derive(Debug,Clone,serde::Serialize,serde::Deserialize)]
+pub struct BuildReport {
+  pub queue:String,
+  pub started:Timestamp,
+  pub finished:Timestamp,
+  pub tasks:Vec<TaskReport>
+}
+
+impl TaskReport {
+  pub(crate) fn new(archive:ArchiveId,rel_path:&str,target:&str,started:Timestamp,outcome:TaskOutcome,log:Option<&Path>) -> Self {
+    let errors = log.and_then(|p| std::fs::read(p).ok())
+      .map(|v| error_lines(&String::from_utf8_lossy(&v),outcome == TaskOutcome::Failed))
+      .unwrap_or_default();
+    Self {
+      archive,
+      rel_path:rel_path.to_string(),
+      target:target.to_string(),
+      duration_ms:Timestamp::now().0.get().saturating_sub(started.0.get()),
+      outcome,
+      errors
+    }
+  }
+}
+
+/// Prefixes of the messages logged by the [`check`](super::checking) target
+const CHECK_ERRORS: [&str;4] = ["unresolved import:","unresolved module:","unknown symbol:","arity mismatch:"];
+
+/// Extracts the relevant lines from a log: TeX errors (`! ...`, including
+/// the subsequent `l.<line>` context), errors of the check target and lines
+/// mentioning an `Error`. If none of those occur and `failed` is set, the first
+/// (non-empty) lines of the log are used, so that a failure is never reported without a message.
+fn error_lines(log:&str,failed:bool) -> Vec<String> {
+  let mut ret = Vec::new();
+  let mut in_error = false;
+  for line in log.lines() {
+    if ret.len() >= MAX_ERROR_LINES { break }
+    let trimmed = line.trim();
+    if trimmed.starts_with("! ") {
+      in_error = true;
+      ret.push(trimmed.to_string());
+    } else if in_error && trimmed.starts_with("l.") {
+      in_error = false;
+      ret.push(trimmed.to_string());
+    } else if trimmed.contains("Error") || trimmed.starts_with("ERROR")
+      || CHECK_ERRORS.iter().any(|p| trimmed.starts_with(p)) {
+      ret.push(trimmed.to_string());
+    }
+  }
+  if failed && ret.is_empty() {
+    ret = log.lines().map(str::trim).filter(|l| !l.is_empty())
+      .take(MAX_ERROR_LINES).map(ToString::to_string).collect();
+  }
+  ret
+}
+
+impl BuildReport {
+  #[must_use]
+  pub fn num_failed(&self) -> usize {
+    self.tasks.iter().filter(|t| t.outcome == TaskOutcome::Failed).count()
+  }
+
+  /// Renders this report as JUnit XML, with one `testsuite` per archive and
+  /// one `testcase` per build step.
+  #[must_use]
+  #[allow(clippy::cast_precision_loss)]
+  pub fn to_junit(&self) -> String {
+    let mut archives : Vec<(&ArchiveId,Vec<&TaskReport>)> = Vec::new();
+    for t in &self.tasks {
+      match archives.iter_mut().find(|(a,_)| *a == &t.archive) {
+        Some((_,v)) => v.push(t),
+        None => archives.push((&t.archive,vec![t]))
+      }
+    }
+    let secs = |ms:u64| ms as f64 / 1000.0;
+    let mut s = String::new();
+    let _ = writeln!(s,r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(s,r#"<testsuites name="{}" tests="{}" failures="{}" time="{:.3}">"#,
+      escape(&self.queue),self.tasks.len(),self.num_failed(),
+      secs(self.finished.0.get().saturating_sub(self.started.0.get()))
+    );
+    for (archive,tasks) in archives {
+      let failures = tasks.iter().filter(|t| t.outcome == TaskOutcome::Failed).count();
+      let time = tasks.iter().map(|t| t.duration_ms).sum();
+      let _ = writeln!(s,r#"  <testsuite name="{}" tests="{}" failures="{failures}" time="{:.3}">"#,
+        escape(archive.as_ref()),tasks.len(),secs(time)
+      );
+      for t in tasks {
+        let _ = write!(s,r#"    <testcase classname="{}" name="{} ({})" time="{:.3}""#,
+          escape(archive.as_ref()),escape(&t.rel_path),escape(&t.target),secs(t.duration_ms)
+        );
+        if t.outcome == TaskOutcome::Failed {
+          let _ = writeln!(s,">");
+          let _ = writeln!(s,r#"      <failure message="{} failed">{}</failure>"#,
+            escape(&t.target),escape(&t.errors.join("\n"))
+          );
+          let _ = writeln!(s,"    </testcase>");
+        } else {
+          let _ = writeln!(s,"/>");
+        }
+      }
+      let _ = writeln!(s,"  </testsuite>");
+    }
+    let _ = writeln!(s,"</testsuites>");
+    s
+  }
+
+  /// Writes this report as JUnit XML to `<dir>/<queue>.xml`.
+  /// ### Errors
+  pub fn write_junit(&self,dir:&Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(format!("{}.xml",self.queue)), self.to_junit())
+  }
+}
+
+fn escape(s:&str) -> String {
+  let mut ret = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '<' => ret.push_str("&lt;"),
+      '>' => ret.push_str("&gt;"),
+      '&' => ret.push_str("&amp;"),
+      '"' => ret.push_str("&quot;"),
+      '\'' => ret.push_str("&apos;"),
+      c if c.is_control() && c != '\n' && c != '\t' => (),
+      c => ret.push(c)
+    }
+  }
+  ret
+}
+
+#[cfg(test)]
+mod tests {
+  use std::num::NonZeroU64;
+
+  use immt_ontology::uris::ArchiveId;
+  use immt_utils::time::Timestamp;
+
+  use super::{error_lines, BuildReport, TaskOutcome, TaskReport};
+
+  #[test]
+  fn extract_errors() {
+    let log = "This is pdfTeX\n! Undefined control sequence.\n<argument> \\foo\nl.12 \\foo\nsome output\nLaTeX Error: File `bar.sty' not found.\n";
+    assert_eq!(error_lines(log,true),vec![
+      "! Undefined control sequence.","l.12 \\foo","LaTeX Error: File `bar.sty' not found."
+    ]);
+    assert!(error_lines("everything fine\n",false).is_empty());
+    assert_eq!(error_lines("\n  something went wrong \n",true),vec!["something went wrong"]);
+  }
+
+  #[test]
+  fn junit() {
+    let dir = tempfile::tempdir().expect("can create temporary directory");
+    let log = |name:&str,content:&str| {
+      let path = dir.path().join(name);
+      std::fs::write(&path,content).expect("can write log");
+      path
+    };
+    let task = |archive:&str,rel_path:&str,outcome:TaskOutcome,log:Option<&std::path::Path>,duration_ms:u64| TaskReport {
+      duration_ms,
+      ..TaskReport::new(ArchiveId::new(archive),rel_path,"check",Timestamp::now(),outcome,log)
+    };
+    let a = log("a.log","unknown symbol: http://example.com/?a&b\nsome other line\n");
+    let c = log("c.log","\n  something went wrong\n");
+    let time = |ms| Timestamp(NonZeroU64::new(ms).expect("is not zero"));
+    let report = BuildReport {
+      queue:"my.queue".to_string(),
+      started:time(1000),
+      finished:time(4000),
+      tasks:vec![
+        task("some/archive","a.tex",TaskOutcome::Failed,Some(&a),1500),
+        task("some/archive","b.tex",TaskOutcome::Success,None,500),
+        task("other/archive","c.tex",TaskOutcome::Failed,Some(&c),0)
+      ]
+    };
+    assert_eq!(report.num_failed(),2);
+    assert_eq!(report.tasks[0].errors,vec!["unknown symbol: http://example.com/?a&b"]);
+    assert_eq!(report.to_junit(),r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="my.queue" tests="3" failures="2" time="3.000">
+  <testsuite name="some/archive" tests="2" failures="1" time="2.000">
+    <testcase classname="some/archive" name="a.tex (check)" time="1.500">
+      <failure message="check failed">unknown symbol: http://example.com/?a&amp;b</failure>
+    </testcase>
+    <testcase classname="some/archive" name="b.tex (check)" time="0.500"/>
+  </testsuite>
+  <testsuite name="other/archive" tests="1" failures="1" time="0.000">
+    <testcase classname="other/archive" name="c.tex (check)" time="0.000">
+      <failure message="check failed">something went wrong</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#);
+
+    let out = dir.path().join("reports");
+    report.write_junit(&out).expect("can write report");
+    assert_eq!(std::fs::read_to_string(out.join("my.queue.xml")).expect("report exists"),report.to_junit());
+  }
+}
@@ -2,7 +2,13 @@ use std::{path::PathBuf, str::FromStr};
 
 use immt_utils::settings::{BuildQueueSettings, ServerSettings, SettingsSpec};
 
-use crate::{backend::AnyBackend, building::{BuildTask,BuildResult},build_result, build_target, formats::CHECK, source_format};
+use immt_ontology::{
+  content::{declarations::{symbols::{ArgSpec, Symbol}, OpenDeclaration}, modules::OpenModule, terms::{Arg, ArgMode, Term}},
+  narration::{documents::OpenDocument, DocumentElement}, uris::{ArchiveURI, BaseURI, ContentURI, DocumentURI, ModuleURI, SymbolURI},
+  DocumentRange
+};
+
+use crate::{backend::{AnyBackend, TemporaryBackend}, building::{checking::{check_omdoc, CheckError, CheckedOMDoc}, BuildArtifact, BuildTask,BuildResult},build_result, build_target, formats::CHECK, source_format};
 
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
@@ -40,6 +46,73 @@ async fn test() {
   crate::initialize(TEST_SETTINGS.clone());
 }
 
+#[test]
+fn check_errors() {
+  fn symbol(module:&ModuleURI,name:&str,arity:&str) -> OpenDeclaration<immt_ontology::Unchecked> {
+    OpenDeclaration::Symbol(Symbol {
+      uri: (module.clone() | name).expect("is valid"),
+      arity: arity.parse().unwrap_or_else(|()| ArgSpec::default()),
+      macroname:None, role:Box::new([]), tp:None, df:None, assoctype:None, reordering:None
+    })
+  }
+  fn apply(head:&SymbolURI,args:usize) -> Term {
+    Term::OMA {
+      head:Box::new(Term::OMID(ContentURI::Symbol(head.clone()))),
+      args:(0..args).map(|_| Arg { term: Term::OMID(ContentURI::Symbol(head.clone())), mode: ArgMode::Normal }).collect()
+    }
+  }
+  let archive : ArchiveURI = BaseURI::new_unchecked("http://example.com/") & "some/archive";
+  let module : ModuleURI = (archive.clone() | "module").expect("is valid");
+  let missing : ModuleURI = (archive.clone() | "missing").expect("is valid");
+  let zero : SymbolURI = (module.clone() | "zero").expect("is valid");
+  let plus : SymbolURI = (module.clone() | "plus").expect("is valid");
+  let unknown : SymbolURI = (module.clone() | "unknown").expect("is valid");
+  let doc = DocumentURI::from_archive_relpath(archive, "doc.en.tex");
+  let range = DocumentRange { start:0, end:0 };
+
+  let modules = vec![OpenModule {
+    uri: module.clone(), meta: None, signature: None,
+    elements: vec![symbol(&module, "zero", "0"), symbol(&module, "plus", "2")]
+  }];
+  let term = |name:&str,term:Term| DocumentElement::TopTerm {
+    uri: (doc.clone() & name).expect("is valid"), term
+  };
+  let document = OpenDocument {
+    uri: doc.clone(), title: None,
+    elements: vec![DocumentElement::Module { range, module: module.clone(), children: vec![
+      DocumentElement::ImportModule(missing.clone()),
+      term("ok", apply(&plus, 2)),
+      term("too_few", apply(&plus, 1)),
+      term("too_many", apply(&zero, 1)),
+      DocumentElement::SymbolReference { range, uri: unknown.clone(), notation: None }
+    ]}]
+  };
+
+  let (omdoc,errors) = check_omdoc(&AnyBackend::Temp(TemporaryBackend::default()), document, modules);
+  assert_eq!(omdoc.modules.len(), 1);
+  let expected = [
+    CheckError::UnresolvedImport { module: missing.clone() },
+    CheckError::ArityMismatch { symbol: plus, expected: 2, found: 1 },
+    CheckError::ArityMismatch { symbol: zero, expected: 0, found: 1 },
+    CheckError::UnknownSymbol { symbol: unknown }
+  ];
+  assert_eq!(errors.len(), expected.len(), "{errors:?}");
+  for e in &expected {
+    assert!(errors.contains(e), "missing {e}");
+  }
+  let missing_modules : Vec<_> = errors.iter().filter_map(CheckError::missing_module).collect();
+  assert_eq!(missing_modules, vec![missing]);
+  assert_eq!(CheckError::MissingInput { document: doc.clone() }.missing_module(), None);
+
+  // stored OMDoc is checked again against the backend passed to `load`
+  let dir = tempfile::tempdir().expect("can create temporary directory");
+  let path = dir.path().join("omdoc");
+  omdoc.write(&path).expect("can write OMDoc");
+  let loaded = CheckedOMDoc::load(&path, &AnyBackend::Temp(TemporaryBackend::default())).expect("can load OMDoc");
+  assert_eq!(loaded.document.uri(), &doc);
+  assert_eq!(loaded.modules.len(), 1);
+}
+
 lazy_static::lazy_static! {
   static ref TEST_SETTINGS : SettingsSpec = SettingsSpec {
     mathhubs:vec![PathBuf::from("/insert/your/path/here/MathHub").into()],
@@ -54,7 +127,9 @@ lazy_static::lazy_static! {
     },
     log_dir:None,
     buildqueue:BuildQueueSettings {
-      num_threads:Some(4)
+      num_threads:Some(4),
+      watch:None,
+      auto_build:None
     }
   };
 }
\ No newline at end of file
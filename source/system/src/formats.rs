@@ -43,7 +43,7 @@ macro_rules! build_target {
 
 build_target!(check [UNCHECKED_OMDOC] => [OMDOC]
   @ "Resolve OMDoc dependencies and type check"
-  = |_,_| { BuildResult::empty() }
+  = crate::building::checking::check
 );
 
 global! {SER BuildArtifactType {name,
@@ -264,7 +264,7 @@ impl BuildArtifact for OMDocResult {
   fn write(&self,_path:&std::path::Path) -> Result<(),std::io::Error> {
       unreachable!()
   }
-  fn load(_p:&std::path::Path) -> Result<Self,std::io::Error> where Self:Sized {
+  fn load(_p:&std::path::Path,_backend:&AnyBackend) -> Result<Self,std::io::Error> where Self:Sized {
       unreachable!()
   }
   #[inline]
@@ -287,4 +287,13 @@ macro_rules! immt_extension {
 pub enum FormatOrTargets<'a> {
   Format(SourceFormatId),
   Targets(&'a[BuildTargetId])
-}
\ No newline at end of file
+}impl<'a> FormatOrTargets<'a> {
+  /// The explicitly given targets, if any
+  #[inline]#[must_use]
+  pub const fn targets(self) -> Option<&'a [BuildTargetId]> {
+    match self {
+      Self::Format(_) => None,
+      Self::Targets(t) => Some(t)
+    }
+  }
+}
@@ -15,6 +15,8 @@ use settings::SettingsSpec;
 use backend::GlobalBackend;
 
 static LOG : std::sync::OnceLock<logging::LogStore> = std::sync::OnceLock::new();
+/// whether the relational store and search index have loaded all archives (see [`initialize`])
+static LOADED : (parking_lot::Mutex<bool>,parking_lot::Condvar) = (parking_lot::Mutex::new(false),parking_lot::Condvar::new());
 
 #[cfg(feature="gitlab")]
 lazy_static::lazy_static!{
@@ -46,6 +48,7 @@ pub fn initialize(settings: SettingsSpec) {
 
         }
     }
+    GlobalBackend::get().set_triple_store(backend::rdf::RDFStore::new(settings.rdf_database.as_deref()));
     let backend = GlobalBackend::get().manager();
     let mhs = &*settings.mathhubs;
     for p in mhs.iter().rev() {
@@ -53,13 +56,21 @@ pub fn initialize(settings: SettingsSpec) {
     }
     let f = || {
         let backend = GlobalBackend::get();
-        backend.triple_store().load_archives(&backend.all_archives());
+        let archives = backend.all_archives();
+        backend.triple_store().load_archives(&archives);
+        backend.search_index().load_archives(&archives);
+        backend.load_dependencies(&archives);
+        *LOADED.0.lock() = true;
+        LOADED.1.notify_all();
     };
     #[cfg(feature="tokio")]
     background(f);
     #[cfg(not(feature="tokio"))]
     f();
     QueueManager::initialize(settings.num_threads);
+    if settings.watch && !settings.lsp {
+        backend::watcher::start(settings.auto_build);
+    }
     for e in IMMTExtension::all() {
         let span = tracing::info_span!("Initializing",extension=e.name());
         let f = move || {
@@ -74,6 +85,15 @@ pub fn initialize(settings: SettingsSpec) {
     }
 }
 
+/// Blocks until the relational store and search index have loaded all archives,
+/// which [`initialize`] does in the background
+pub fn wait_for_initial_load() {
+    let mut loaded = LOADED.0.lock();
+    while !*loaded {
+        LOADED.1.wait(&mut loaded);
+    }
+}
+
 /// ### Panics
 pub fn logger() -> &'static logging::LogStore {
     LOG.get().expect("log should be initialized")
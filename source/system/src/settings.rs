@@ -20,8 +20,11 @@ pub struct Settings {
     pub ip: std::net::IpAddr,
     pub admin_pwd: Option<Box<str>>,
     pub database: Box<Path>,
+    pub rdf_database: Option<Box<Path>>,
     temp_dir: parking_lot::RwLock<Option<tempfile::TempDir>>,
     pub num_threads: u8,
+    pub watch: bool,
+    pub auto_build: bool,
     pub gitlab_url: Option<Box<str>>,
     pub gitlab_token: Option<Box<str>>,
     pub gitlab_app_id:Option<Box<str>>,
@@ -43,6 +46,11 @@ impl Settings {
             .expect("Error initializing settings");
     }
 
+    #[inline]
+    pub(crate) fn try_get() -> Option<&'static Self> {
+        SETTINGS.get()
+    }
+
     #[allow(clippy::missing_panics_doc)]
     pub fn get() -> &'static Self {
         SETTINGS.get().expect("Settings not initialized")
@@ -66,6 +74,7 @@ impl Settings {
             log_dir: Some(self.log_dir.clone()),
             temp_dir: Some(self.temp_dir.read().as_ref().expect("This should never happen!").path().to_path_buf().into_boxed_path()),
             database: Some(self.database.clone()),
+            rdf_database: self.rdf_database.clone(),
             server: ServerSettings {
                 port: self.port,
                 ip: Some(self.ip),
@@ -73,6 +82,8 @@ impl Settings {
             },
             buildqueue: BuildQueueSettings {
                 num_threads: Some(self.num_threads),
+                watch: Some(self.watch),
+                auto_build: Some(self.auto_build),
             },
             gitlab: GitlabSettings {
                 url: self.gitlab_url.clone(),
@@ -130,6 +141,7 @@ impl From<SettingsSpec> for Settings {
                     .join("users.sqlite")
                     .into_boxed_path()
             }),
+            rdf_database: spec.rdf_database,
             num_threads: spec.buildqueue.num_threads.unwrap_or_else(|| {
                 #[cfg(feature = "tokio")]
                 {
@@ -140,6 +152,8 @@ impl From<SettingsSpec> for Settings {
                     1
                 }
             }),
+            watch: spec.buildqueue.watch.unwrap_or_default(),
+            auto_build: spec.buildqueue.auto_build.unwrap_or_default(),
             lsp: spec.lsp,
             gitlab_token: spec.gitlab.token,
             gitlab_url: spec.gitlab.url,
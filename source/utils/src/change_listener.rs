@@ -18,6 +18,17 @@ impl<T: Clone + Send + Sync> ChangeListener<T> {
     pub async fn read(&mut self) -> Option<T> {
         self.inner.recv().await.ok()
     }
+    /// Like [`read`](Self::read), but distinguishes a closed channel (`Ok(None)`) from
+    /// this listener having fallen behind, in which case `Err(n)` with the number `n`
+    /// of missed messages is returned
+    /// #### Errors
+    pub async fn read_or_lag(&mut self) -> Result<Option<T>,u64> {
+        match self.inner.recv().await {
+            Ok(t) => Ok(Some(t)),
+            Err(async_broadcast::RecvError::Overflowed(n)) => Err(n),
+            Err(async_broadcast::RecvError::Closed) => Ok(None)
+        }
+    }
 }
 impl<T: Clone> Clone for ChangeListener<T> {
     fn clone(&self) -> Self {
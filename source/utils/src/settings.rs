@@ -20,6 +20,10 @@ pub struct SettingsSpec {
     pub lsp:bool,
     #[cfg_attr(feature = "serde", serde(default))]
     pub database: Option<Box<Path>>,
+    /// directory of a persistent relational (RDF) store; if not given, the store
+    /// is kept in memory and rebuilt on every start
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rdf_database: Option<Box<Path>>,
     #[cfg_attr(feature = "serde", serde(default))]
     pub gitlab: GitlabSettings,
 }
@@ -37,6 +41,7 @@ impl Add for SettingsSpec {
             log_dir: self.log_dir.or(rhs.log_dir),
             temp_dir: self.temp_dir.or(rhs.temp_dir),
             database: self.database.or(rhs.database),
+            rdf_database: self.rdf_database.or(rhs.rdf_database),
             buildqueue: self.buildqueue + rhs.buildqueue,
             gitlab:self.gitlab + rhs.gitlab,
             lsp:self.lsp || rhs.lsp
@@ -60,6 +65,9 @@ impl AddAssign for SettingsSpec {
         if self.database.is_none() {
             self.database = rhs.database;
         }
+        if self.rdf_database.is_none() {
+            self.rdf_database = rhs.rdf_database;
+        }
         self.gitlab += rhs.gitlab;
         self.buildqueue += rhs.buildqueue;
     }
@@ -83,6 +91,9 @@ impl SettingsSpec {
             database: std::env::var("IMMT_DATABASE")
                 .ok()
                 .map(|s| PathBuf::from(s).into_boxed_path()),
+            rdf_database: std::env::var("IMMT_RDF_DATABASE")
+                .ok()
+                .map(|s| PathBuf::from(s).into_boxed_path()),
             server: ServerSettings {
                 port: std::env::var("IMMT_PORT")
                     .ok()
@@ -98,6 +109,12 @@ impl SettingsSpec {
                 num_threads: std::env::var("IMMT_NUM_THREADS")
                     .ok()
                     .and_then(|s| s.parse().ok()),
+                watch: std::env::var("IMMT_WATCH")
+                    .ok()
+                    .and_then(|s| s.parse().ok()),
+                auto_build: std::env::var("IMMT_AUTO_BUILD")
+                    .ok()
+                    .and_then(|s| s.parse().ok()),
             },
             gitlab: GitlabSettings {
                 url:std::env::var("IMMT_GITLAB_URL")
@@ -157,12 +174,21 @@ impl AddAssign for ServerSettings {
 pub struct BuildQueueSettings {
     #[cfg_attr(feature = "serde", serde(default))]
     pub num_threads: Option<u8>,
+    /// whether to watch the `MathHub` directories for changes
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub watch: Option<bool>,
+    /// whether to automatically enqueue changed files into the global build queue
+    /// (requires `watch`)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub auto_build: Option<bool>,
 }
 impl Add for BuildQueueSettings {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
         Self {
             num_threads: self.num_threads.or(rhs.num_threads),
+            watch: self.watch.or(rhs.watch),
+            auto_build: self.auto_build.or(rhs.auto_build),
         }
     }
 }
@@ -171,6 +197,12 @@ impl AddAssign for BuildQueueSettings {
         if self.num_threads.is_none() {
             self.num_threads = rhs.num_threads;
         }
+        if self.watch.is_none() {
+            self.watch = rhs.watch;
+        }
+        if self.auto_build.is_none() {
+            self.auto_build = rhs.auto_build;
+        }
     }
 }
 
@@ -51,11 +51,14 @@ use crate::inject_css;
 #[component]
 pub fn Collapsible<Ch:IntoView+'static>(
     #[prop(optional)] header:Option<Header>,
+    /// whether the content is initially shown
+    #[prop(optional)] expanded:bool,
     children:TypedChildren<Ch>
 ) -> impl IntoView {
   let children = children.into_inner();
-    let expanded = RwSignal::new(false);
-    view!{<details>
+    let open = expanded;
+    let expanded = RwSignal::new(expanded);
+    view!{<details open=open>
         <summary on:click=move |_| expanded.update(|b| *b = !*b)>{
             header.map(|c| (c.children)())
         }</summary>